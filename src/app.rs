@@ -1,4 +1,5 @@
 
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
 use std::env;
 use std::io::Error;
@@ -18,6 +19,7 @@ use sdl2::AudioSubsystem;
 use sdl2::{video::Window, Sdl, render::Canvas, keyboard::Keycode};
 use serde::{Deserialize, Serialize};
 
+use crate::chart::modifiers::Modifiers;
 use crate::game_object::GameObject;
 use crate::gameplay::{game_calibration, play};
 use crate::gameplay::editor;
@@ -28,9 +30,12 @@ use crate::gameplay::controller;
 use crate::gameplay::calibration;
 use crate::gameplay::manual_calibration;
 use crate::input::button_module::{Button, TextAlign};
-use crate::load_song::Song;
+use crate::load_song::{Song, DEFAULT_CHART};
 
 const NUM_BARS: usize = 20;
+// the keys of each key mode when the player didn't choose them (the keycodes of sdl are the ascii of the letters),
+// 4K uses play_keys that start as d, f, j, k
+const DEFAULT_KEY_MODES: [&str; 10] = [" ", "fj", "f j", "dfjk", "df jk", "sdfjkl", "sdf jkl", "asdfjkl;", "asdf jkl;", "asdfvnjkl;"];
 // const LOGO: &[u8] = include_bytes!("assets/non_modifiable_image.png")
 
 // in this file we will have the main work flow of our app, as a struct defined mainly to do what we want to do:
@@ -48,8 +53,8 @@ pub enum GameState {
 }
 
 pub struct CoordinationData {
-    pub base_time: i128,
-    pub end_time: i128,
+    pub base_time: f64,
+    pub end_time: f64,
     pub key_speed: f32,
     pub saved_key_speed: f32,
 }
@@ -57,7 +62,9 @@ pub struct CoordinationData {
 pub struct AppState {
     pub is_running: bool,
     pub state: GameState,
-    pub song_folder: Option<String>
+    pub song_folder: Option<String>,
+    // the chart of the folder that is played or edited, see Song::charts
+    pub song_chart: String,
 }
 
 pub struct Testing {
@@ -80,14 +87,75 @@ pub struct Textures {
     pub purple_hold: Option<Texture>,
     pub blue_hold: Option<Texture>,
     pub mute_hold: Option<Texture>,
+    pub mine_note: Option<Texture>,
+    pub roll_note: Option<Texture>,
+    pub roll_hold: Option<Texture>,
+    pub fake_note: Option<Texture>,
+    pub fake_hold: Option<Texture>,
     pub background: Option<Texture>,
 }
 
+impl Textures {
+    // how see-through the keys and notes are drawn (255 is solid), the lane fades of the storyboard change it for each lane
+    pub fn set_lane_alpha(&mut self, alpha: u8) {
+        let lane_textures = [
+            &mut self.red_key, &mut self.yellow_key, &mut self.purple_key, &mut self.blue_key,
+            &mut self.red_note, &mut self.yellow_note, &mut self.purple_note, &mut self.blue_note, &mut self.mute_note,
+            &mut self.red_hold, &mut self.yellow_hold, &mut self.purple_hold, &mut self.blue_hold, &mut self.mute_hold,
+            &mut self.mine_note, &mut self.roll_note, &mut self.roll_hold, &mut self.fake_note, &mut self.fake_hold,
+        ];
+        for texture in lane_textures.into_iter().flatten() {
+            texture.set_alpha_mod(alpha);
+        }
+    }
+}
+
 pub struct Visualizer {
     pub bars: bool,
     pub circle: bool
 }
 
+// where the lyrics of the song are shown while playing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LyricsPosition {
+    Top,
+    Middle,
+    Bottom,
+    Off,
+}
+
+impl LyricsPosition {
+    pub fn next(self) -> LyricsPosition {
+        match self {
+            LyricsPosition::Top => LyricsPosition::Middle,
+            LyricsPosition::Middle => LyricsPosition::Bottom,
+            LyricsPosition::Bottom => LyricsPosition::Off,
+            LyricsPosition::Off => LyricsPosition::Top,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LyricsPosition::Top => "top",
+            LyricsPosition::Middle => "middle",
+            LyricsPosition::Bottom => "bottom",
+            LyricsPosition::Off => "off",
+        }
+    }
+
+    // the y of the current line, the next one goes under it. None when the lyrics are off
+    pub fn y(self, height: u32) -> Option<f32> {
+        match self {
+            LyricsPosition::Top => Some(60.0),
+            // under the combo
+            LyricsPosition::Middle => Some(height as f32 / 2.0 + 40.0),
+            // over the keys
+            LyricsPosition::Bottom => Some(height as f32 - 260.0),
+            LyricsPosition::Off => None,
+        }
+    }
+}
+
 pub struct App {
     pub context: Sdl,
     pub mixer_context: (),
@@ -96,7 +164,10 @@ pub struct App {
     pub height: u32,
     pub canvas: Canvas<Window>,
     pub coordination_data: CoordinationData,
+    // the keys of 4K, they are also the keys to move on the menus
     pub play_keys: [i32; 4],
+    // the keys of the other key modes, by amount of lanes
+    pub key_modes: BTreeMap<usize, Vec<i32>>,
     pub volume_percentage: i32,
     pub paused: bool,
     pub start_pause: Instant,
@@ -109,12 +180,18 @@ pub struct App {
     pub texture_creator: TextureCreator<WindowContext>,
     pub textures: Textures,
     pub visualizer_settings: Visualizer,
+    pub lyrics_position: LyricsPosition,
     pub ctrl_string: String,
+    // chosen on the song selector, they change the chart of every song played until they are turned off
+    pub modifiers: Modifiers,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct GameController {
     pub(crate) controller_array: [i32; 4],
+    // settings saved before the key modes existed don't have them
+    #[serde(default)]
+    pub(crate) key_modes: BTreeMap<usize, Vec<i32>>,
 }
 
 impl App {
@@ -126,6 +203,10 @@ impl App {
 
         // sdl2_mixer
         mixer::init(InitFlag::FLAC | InitFlag::MOD | InitFlag::MP3 | InitFlag::OGG).expect("Failed to initialize SDL2_mixer");
+        // opus is asked apart because older SDL2_mixer builds don't have it, the other formats still work without it
+        if let Err(err) = mixer::init(InitFlag::OPUS) {
+            eprintln!("Opus audio is not available: {}", err);
+        }
         let mixer_context = mixer::open_audio(44100, AUDIO_S16LSB, DEFAULT_CHANNELS, 1024).expect("Failed to open audio device");
         mixer::allocate_channels(DEFAULT_CHANNELS);
         let current_display = video_susbsystem.current_display_mode(0).unwrap();
@@ -156,10 +237,15 @@ impl App {
             purple_hold: texture_creator.load_texture("assets/sprites/hold/PurpleHold.png").ok(), 
             blue_hold: texture_creator.load_texture("assets/sprites/hold/BlueHold.png").ok(),
             mute_hold: texture_creator.load_texture("assets/sprites/hold/MuteHold.png").ok(),
+            mine_note: texture_creator.load_texture("assets/sprites/notes/MineNote.png").ok(),
+            roll_note: texture_creator.load_texture("assets/sprites/notes/RollNote.png").ok(),
+            roll_hold: texture_creator.load_texture("assets/sprites/hold/RollHold.png").ok(),
+            fake_note: texture_creator.load_texture("assets/sprites/notes/FakeNote.png").ok(),
+            fake_hold: texture_creator.load_texture("assets/sprites/hold/FakeHold.png").ok(),
             background: texture_creator.load_texture("assets/sprites/background.png").ok()
         };
 
-        let mut settings = GameController { controller_array: [100,102,106,107] };
+        let mut settings = GameController { controller_array: [100,102,106,107], key_modes: BTreeMap::new() };
 
         match Self::load_settings() {
             Ok(settings_data) => settings = settings_data,
@@ -173,8 +259,9 @@ impl App {
             width,
             height,
             canvas,
-            coordination_data: CoordinationData { base_time: 0, end_time: 0, key_speed: 700.0, saved_key_speed: 700.0},
+            coordination_data: CoordinationData { base_time: 0.0, end_time: 0.0, key_speed: 700.0, saved_key_speed: 700.0},
             play_keys: settings.controller_array,
+            key_modes: settings.key_modes,
             volume_percentage: 15,
             paused: false,
             start_pause: Instant::now(),
@@ -187,14 +274,16 @@ impl App {
             texture_creator,
             textures,
             visualizer_settings: Visualizer { bars: true, circle: true },
+            lyrics_position: LyricsPosition::Top,
             ctrl_string: "".to_owned(),
+            modifiers: Modifiers::default(),
         }
     }
 
     
 
     pub fn render(mut self) {
-        let mut app_state = AppState { is_running: true, state: GameState::Calibrating, song_folder: None };
+        let mut app_state = AppState { is_running: true, state: GameState::Calibrating, song_folder: None, song_chart: DEFAULT_CHART.to_owned() };
 
         let fft_data = Arc::new(Mutex::new(vec![0.0; NUM_BARS]));
         let data_inside_closure = fft_data.clone();
@@ -353,7 +442,7 @@ impl App {
     }
 
     fn load_settings() -> Result<GameController, Error> {
-        let mut settings = GameController { controller_array: [100,102,106,107] };
+        let mut settings = GameController { controller_array: [100,102,106,107], key_modes: BTreeMap::new() };
         match std::fs::read_to_string("settings.json") {
             Ok(file_contents) => {
                 settings = serde_json::from_str(&file_contents)?;
@@ -365,6 +454,28 @@ impl App {
         Ok(settings)
     }
 
+    // the keys of every lane of a key mode, from the left
+    pub fn lane_keys(&self, lane_count: usize) -> Vec<i32> {
+        if lane_count == 4 {
+            return self.play_keys.to_vec();
+        }
+        match self.key_modes.get(&lane_count) {
+            Some(keys) if keys.len() == lane_count => keys.clone(),
+            _ => DEFAULT_KEY_MODES.get(lane_count.wrapping_sub(1)).map_or(vec![], |keys| keys.chars().map(|key| key as i32).collect()),
+        }
+    }
+
+    // changes the key of a lane of a key mode, 4K changes play_keys
+    pub fn set_lane_key(&mut self, lane_count: usize, lane: usize, key: i32) {
+        if lane_count == 4 {
+            self.play_keys[lane] = key;
+            return;
+        }
+        let mut keys = self.lane_keys(lane_count);
+        keys[lane] = key;
+        self.key_modes.insert(lane_count, keys);
+    }
+
     fn controller_str(&mut self, apps_state: &AppState) -> String {
         let mut ctrl_string = "".to_owned();
 
@@ -391,7 +502,7 @@ impl App {
 
         match apps_state.state {
             GameState::SelectingSong => {
-                ctrl_string += &(", space-[Sync]");
+                ctrl_string += ", space-[Sync], M R S H L-[Modifiers]";
 
             },
             _ => {}
@@ -0,0 +1,226 @@
+use crate::{input::keybutton::NoteKind, load_song::{Song, MIN_EDITOR_HOLD_MS}};
+
+// notes closer than this are pressed together (a jump or a chord)
+const CHORD_MS: f64 = 10.0;
+// the same key again before this is a jack
+const JACK_MS: f64 = 250.0;
+// single notes this close to each other are part of a stream
+const STREAM_MS: f64 = 180.0;
+// a stream needs at least this many notes, less is only a short burst
+const STREAM_MIN_NOTES: usize = 8;
+// the notes per second are counted on windows of this size
+const NPS_WINDOW_MS: f64 = 1000.0;
+// the rating follows the hardest parts of the song, this part of the seconds is what counts
+const HARDEST_PART: f64 = 0.3;
+
+// what a chart has, the numbers only count the notes the player has to hit (mines and fake notes are left out)
+#[derive(Debug, Clone, Default)]
+pub struct ChartAnalysis {
+    pub note_count: usize,
+    // milliseconds from the first note to the end of the last one
+    pub length: f64,
+    pub average_nps: f64,
+    // the most notes inside a single second
+    pub peak_nps: f64,
+    // the notes of every second from the first note
+    pub nps_curve: Vec<f64>,
+    pub patterns: Patterns,
+    // milliseconds of holding per second of the chart, more than 1000 means more than one hold at a time (the short holds are left out)
+    pub hold_density: f64,
+    pub rating: f64,
+}
+
+// how many notes are part of each pattern, a note can be on more than one
+#[derive(Debug, Clone, Default)]
+pub struct Patterns {
+    pub jacks: usize,
+    pub streams: usize,
+    // two notes at the same time
+    pub jumps: usize,
+    // three or more notes at the same time
+    pub chords: usize,
+    pub holds: usize,
+}
+
+// the notes pressed at the same moment
+struct Row {
+    time: f64,
+    lanes: Vec<bool>,
+    count: usize,
+}
+
+pub fn analyze(song: &Song) -> ChartAnalysis {
+    let mut notes: Vec<(f64, usize, f64)> = vec![];
+    for (lane, list) in song.lanes.iter().enumerate() {
+        for note in list.iter().filter(|note| matches!(note.kind, NoteKind::Normal | NoteKind::Roll) && note.time.is_finite()) {
+            notes.push((note.time, lane, note.holding.max(0.0)));
+        }
+    }
+    notes.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut analysis = ChartAnalysis { note_count: notes.len(), ..ChartAnalysis::default() };
+    let Some(first) = notes.first() else { return analysis };
+    let start = first.0;
+    let end = notes.iter().map(|(time, _, holding)| time + holding).fold(start, f64::max);
+    analysis.length = end - start;
+
+    let mut rows: Vec<Row> = vec![];
+    for (time, lane, _) in &notes {
+        match rows.last_mut() {
+            Some(row) if time - row.time < CHORD_MS => {
+                if !row.lanes[*lane] {
+                    row.count += 1;
+                }
+                row.lanes[*lane] = true;
+            },
+            _ => {
+                let mut lanes = vec![false; song.lanes.len()];
+                lanes[*lane] = true;
+                rows.push(Row { time: *time, lanes, count: 1 });
+            },
+        }
+    }
+
+    analysis.patterns = patterns(&rows, &notes, song.lanes.len());
+    analysis.hold_density = notes.iter().map(|(_, _, holding)| holding).filter(|holding| **holding >= MIN_EDITOR_HOLD_MS).sum::<f64>() / (analysis.length / 1000.0).max(1.0);
+
+    // the notes of each second, and the most notes inside any second (not only the ones that start on a whole second)
+    let seconds = (analysis.length / NPS_WINDOW_MS).floor() as usize + 1;
+    analysis.nps_curve = vec![0.0; seconds];
+    for (time, _, _) in &notes {
+        analysis.nps_curve[((time - start) / NPS_WINDOW_MS) as usize] += 1.0;
+    }
+    let mut window_start = 0;
+    for (i, (time, _, _)) in notes.iter().enumerate() {
+        while time - notes[window_start].0 >= NPS_WINDOW_MS {
+            window_start += 1;
+        }
+        analysis.peak_nps = analysis.peak_nps.max((i - window_start + 1) as f64 * 1000.0 / NPS_WINDOW_MS);
+    }
+    analysis.average_nps = notes.len() as f64 / (analysis.length / 1000.0).max(1.0);
+
+    analysis.rating = rating(&analysis);
+    analysis
+}
+
+fn patterns(rows: &[Row], notes: &[(f64, usize, f64)], lane_count: usize) -> Patterns {
+    // the shorter holds are mostly taps recorded with the key down a moment too long, they play like taps
+    let mut patterns = Patterns { holds: notes.iter().filter(|(_, _, holding)| *holding >= MIN_EDITOR_HOLD_MS).count(), ..Patterns::default() };
+    let mut last_press: Vec<Option<f64>> = vec![None; lane_count];
+    let mut stream_length = 0;
+
+    for (i, row) in rows.iter().enumerate() {
+        match row.count {
+            1 => {},
+            2 => patterns.jumps += 2,
+            count => patterns.chords += count,
+        }
+
+        for (pressed, last) in row.lanes.iter().zip(last_press.iter_mut()) {
+            if *pressed {
+                if last.is_some_and(|last| row.time - last < JACK_MS) {
+                    patterns.jacks += 1;
+                }
+                *last = Some(row.time);
+            }
+        }
+
+        // a stream is a run of single notes close to each other that don't repeat the key
+        let follows = i > 0 && row.count == 1 && rows[i - 1].count == 1 && row.time - rows[i - 1].time < STREAM_MS && row.lanes != rows[i - 1].lanes;
+        if follows {
+            stream_length += 1;
+        } else {
+            if stream_length >= STREAM_MIN_NOTES {
+                patterns.streams += stream_length;
+            }
+            stream_length = if row.count == 1 { 1 } else { 0 };
+        }
+    }
+    if stream_length >= STREAM_MIN_NOTES {
+        patterns.streams += stream_length;
+    }
+    patterns
+}
+
+// a number that goes up with how many notes the hardest parts have and how hard their patterns are to press,
+// a chart of 1 note per second is about 1 and the hardest charts of the game are a bit over 10
+fn rating(analysis: &ChartAnalysis) -> f64 {
+    if analysis.note_count == 0 {
+        return 0.0;
+    }
+
+    let mut seconds = analysis.nps_curve.clone();
+    seconds.sort_by(|a, b| b.total_cmp(a));
+    let hardest = ((seconds.len() as f64 * HARDEST_PART).ceil() as usize).max(1);
+    let strain = seconds.iter().take(hardest).sum::<f64>() / hardest as f64;
+
+    let part = |amount: usize| amount as f64 / analysis.note_count as f64;
+    let patterns = &analysis.patterns;
+    let technique = 1.0 + 0.6 * part(patterns.jacks) + 0.4 * part(patterns.chords) + 0.2 * part(patterns.jumps) + 0.2 * part(patterns.streams);
+
+    let rating = strain * technique * 0.9 + analysis.peak_nps * 0.1 + analysis.hold_density / 1000.0 * 0.5;
+    (rating * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::keybutton::Note;
+
+    // a 4K song with the notes as (time, lane)
+    fn song(notes: &[(f64, usize)]) -> Song {
+        let mut lanes = vec![vec![]; 4];
+        for (time, lane) in notes {
+            lanes[*lane].push(Note::new(*time, 0.0));
+        }
+        Song { lanes, end: 20000.0, ..Song::default() }
+    }
+
+    #[test]
+    fn nps_counts_every_second_and_the_busiest_one() {
+        let analysis = analyze(&song(&[(0.0, 0), (100.0, 1), (200.0, 2), (1500.0, 3), (2500.0, 0)]));
+
+        assert_eq!(analysis.length, 2500.0);
+        assert_eq!(analysis.nps_curve, vec![3.0, 1.0, 1.0]);
+        assert_eq!(analysis.peak_nps, 3.0);
+        assert_eq!(analysis.average_nps, 2.0);
+    }
+
+    #[test]
+    fn the_same_key_again_is_a_jack() {
+        let analysis = analyze(&song(&[(0.0, 0), (200.0, 0), (400.0, 0), (1000.0, 0)]));
+
+        assert_eq!(analysis.patterns.jacks, 2);
+        assert_eq!(analysis.patterns.streams, 0);
+    }
+
+    #[test]
+    fn close_single_notes_on_other_keys_are_a_stream() {
+        let notes: Vec<(f64, usize)> = (0..8).map(|i| (i as f64 * 100.0, i % 4)).collect();
+        let analysis = analyze(&song(&notes));
+
+        assert_eq!(analysis.patterns.streams, 8);
+        assert_eq!(analysis.patterns.jacks, 0);
+
+        // one note less is only a burst
+        assert_eq!(analyze(&song(&notes[..7])).patterns.streams, 0);
+    }
+
+    #[test]
+    fn notes_at_the_same_time_are_jumps_and_chords() {
+        let analysis = analyze(&song(&[(0.0, 0), (0.0, 1), (0.0, 2), (1000.0, 0), (1005.0, 3)]));
+
+        assert_eq!(analysis.patterns.chords, 3);
+        assert_eq!(analysis.patterns.jumps, 2);
+    }
+
+    #[test]
+    fn the_rating_goes_up_with_the_density() {
+        let slow: Vec<(f64, usize)> = (0..10).map(|i| (i as f64 * 1000.0, i % 4)).collect();
+        let fast: Vec<(f64, usize)> = (0..80).map(|i| (i as f64 * 125.0, i % 4)).collect();
+
+        assert_eq!(analyze(&song(&slow)).rating, 1.0);
+        assert!(analyze(&song(&fast)).rating > 8.0);
+        assert_eq!(analyze(&song(&[])).rating, 0.0);
+    }
+}
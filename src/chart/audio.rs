@@ -0,0 +1,118 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use rodio::{Decoder, Source};
+use rustfft::{num_complex::Complex, FftPlanner};
+
+use crate::chart::error::ChartError;
+
+// samples of each fft, about 46 ms at 44100 Hz
+const FRAME_SIZE: usize = 2048;
+// samples between the start of two frames, about 12 ms at 44100 Hz
+const HOP_SIZE: usize = 512;
+// the magnitudes are compressed with ln(1 + LOG_GAMMA * magnitude), so the quiet instruments count too
+const LOG_GAMMA: f64 = 100.0;
+// the lowest frequency used for the pitch of a frame, under it there is only rumble
+const MIN_PITCH_HZ: f64 = 40.0;
+
+// the whole song mixed to a single channel, the samples go from -1 to 1
+pub struct Audio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+impl Audio {
+    // milliseconds of audio
+    pub fn length(&self) -> f64 {
+        self.samples.len() as f64 * 1000.0 / self.sample_rate as f64
+    }
+}
+
+// reads mp3, ogg, flac and wav files (opus is only played by the game, it can't be decoded here)
+pub fn decode(path: &Path) -> Result<Audio, ChartError> {
+    let path_text = path.to_string_lossy().to_string();
+    let file = File::open(path).map_err(|err| ChartError::from_io(&path_text, err))?;
+    let decoder = Decoder::new(BufReader::new(file)).map_err(|err| ChartError::invalid("audio", &format!("{} can't be decoded: {}", path_text, err)))?;
+
+    let channels = decoder.channels().max(1) as usize;
+    let sample_rate = decoder.sample_rate();
+    let mut samples = Vec::new();
+    let mut sum = 0.0;
+    for (i, sample) in decoder.enumerate() {
+        sum += sample as f32 / i16::MAX as f32;
+        if i % channels == channels - 1 {
+            samples.push(sum / channels as f32);
+            sum = 0.0;
+        }
+    }
+    if samples.is_empty() {
+        return Err(ChartError::invalid("audio", &format!("{} doesn't have any sound", path_text)));
+    }
+    Ok(Audio { samples, sample_rate })
+}
+
+// how much new sound starts on every frame of the song (the spectral flux), the base of the onset and tempo detection
+pub struct OnsetEnvelope {
+    // the sum of how much every frequency got louder since the frame before
+    pub flux: Vec<f64>,
+    // where the sound that got louder is, from 0 (the lowest frequencies) to 1 (the highest), on a log scale like the notes of a piano
+    pub pitch: Vec<f64>,
+    // how loud the frame is
+    pub energy: Vec<f64>,
+    // milliseconds between two frames
+    pub frame_ms: f64,
+}
+
+impl OnsetEnvelope {
+    pub fn new(audio: &Audio) -> OnsetEnvelope {
+        let frame_ms = HOP_SIZE as f64 * 1000.0 / audio.sample_rate as f64;
+        let mut envelope = OnsetEnvelope { flux: vec![], pitch: vec![], energy: vec![], frame_ms };
+        if audio.samples.len() < FRAME_SIZE {
+            return envelope;
+        }
+
+        let fft = FftPlanner::<f32>::new().plan_fft_forward(FRAME_SIZE);
+        let window: Vec<f32> = (0..FRAME_SIZE).map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / FRAME_SIZE as f32).cos()).collect();
+        let bins = FRAME_SIZE / 2;
+        let bin_hz = audio.sample_rate as f64 / FRAME_SIZE as f64;
+        let first_bin = ((MIN_PITCH_HZ / bin_hz).ceil() as usize).max(1);
+        // the log of the frequency of every bin from 0 to 1, for the pitch
+        let bin_pitch: Vec<f64> = (0..bins).map(|bin| ((bin.max(first_bin) as f64 / first_bin as f64).ln() / (bins as f64 / first_bin as f64).ln()).clamp(0.0, 1.0)).collect();
+
+        let mut previous = vec![0.0; bins];
+        let mut buffer = vec![Complex::new(0.0, 0.0); FRAME_SIZE];
+        for start in (0..=audio.samples.len() - FRAME_SIZE).step_by(HOP_SIZE) {
+            let frame = &audio.samples[start..start + FRAME_SIZE];
+            for (i, sample) in frame.iter().enumerate() {
+                buffer[i] = Complex::new(sample * window[i], 0.0);
+            }
+            fft.process(&mut buffer);
+
+            let (mut flux, mut weighted_pitch) = (0.0, 0.0);
+            for bin in first_bin..bins {
+                let magnitude = (1.0 + LOG_GAMMA * buffer[bin].norm() as f64).ln();
+                let rise = (magnitude - previous[bin]).max(0.0);
+                flux += rise;
+                weighted_pitch += rise * bin_pitch[bin];
+                previous[bin] = magnitude;
+            }
+            envelope.flux.push(flux);
+            envelope.pitch.push(if flux > 0.0 { weighted_pitch / flux } else { 0.5 });
+            envelope.energy.push((frame.iter().map(|sample| (sample * sample) as f64).sum::<f64>() / FRAME_SIZE as f64).sqrt());
+        }
+        // the first frame rises from silence, it is not a real onset
+        if let Some(first) = envelope.flux.first_mut() {
+            *first = 0.0;
+        }
+        envelope
+    }
+
+    // milliseconds of audio at the middle of a frame
+    pub fn time(&self, frame: usize) -> f64 {
+        self.frame_time(frame as f64)
+    }
+
+    // the same for a time between two frames
+    pub fn frame_time(&self, frame: f64) -> f64 {
+        frame * self.frame_ms + FRAME_SIZE as f64 / HOP_SIZE as f64 * self.frame_ms / 2.0
+    }
+}
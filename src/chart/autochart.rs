@@ -0,0 +1,199 @@
+use std::path::Path;
+
+use crate::{chart::{audio::{self, Audio, OnsetEnvelope}, error::ChartError, song_folder::{self, SONGS_FOLDER}, tempo}, input::keybutton::Note, load_song::{Song, SongMetadata, CHARTS_FOLDER, DEFAULT_CHART, LEAD_IN_MS, MAX_LANES, MIN_EDITOR_HOLD_MS}};
+
+pub const MIN_DENSITY: u8 = 1;
+pub const MAX_DENSITY: u8 = 10;
+// an onset has to be the highest of the frames this close to it (about 35 ms)
+const PEAK_FRAMES: usize = 3;
+// the threshold follows the average of the frames this close (about 120 ms), so the quiet parts get notes too
+const AVERAGE_FRAMES: usize = 10;
+// the same key again before this is a jack, the draft never has faster ones
+const JACK_MS: f64 = 250.0;
+// a note is held when there is this much time until the next one and the sound goes on
+const HOLD_GAP_MS: f64 = 700.0;
+// the hold ends when the sound is this part of how loud it was at the onset
+const HOLD_SUSTAIN: f64 = 0.5;
+// the hold ends this long before the next note, so the key can be released
+const HOLD_RELEASE_MS: f64 = 150.0;
+// from this density the loudest onsets get two notes
+const JUMP_DENSITY: u8 = 4;
+// the part of the onsets, counting from the loudest, that can be jumps on the highest density
+const JUMP_PART: f64 = 0.15;
+
+// how the draft is made
+#[derive(Debug, Clone, Copy)]
+pub struct AutoChartOptions {
+    pub lanes: usize,
+    // from MIN_DENSITY (only the strongest beats, about 2 notes per second at most) to MAX_DENSITY (almost every sound, up to 16)
+    pub density: u8,
+}
+
+impl AutoChartOptions {
+    pub fn check(&self) -> Result<(), ChartError> {
+        if self.lanes == 0 || self.lanes > MAX_LANES {
+            return Err(ChartError::invalid("lanes", &format!("a chart needs from 1 to {} lanes", MAX_LANES)));
+        }
+        if self.density < MIN_DENSITY || self.density > MAX_DENSITY {
+            return Err(ChartError::invalid("density", &format!("the density goes from {} to {}", MIN_DENSITY, MAX_DENSITY)));
+        }
+        Ok(())
+    }
+
+    // the shortest time between two onsets
+    fn min_gap(&self) -> f64 {
+        1000.0 / (1.5 * self.density as f64 + 1.0)
+    }
+
+    // how far over the average of its neighbours an onset has to be, in standard deviations of the whole song
+    fn threshold(&self) -> f64 {
+        1.3 - 0.11 * self.density as f64
+    }
+}
+
+// a sound found on the audio, `strength` is how much it is over the threshold
+struct Onset {
+    frame: usize,
+    time: f64,
+    strength: f64,
+    // the part of the onsets of the song that are lower than this one (from 0 to 1), so every lane gets about the same amount of notes
+    pitch: f64,
+}
+
+// a draft chart made from the sounds of the audio, it still needs a person to fix it on the editor.
+// the low sounds go to the left lanes and the high ones to the right, moved when they would make a fast jack or land on a hold
+pub fn generate(audio: &Audio, options: &AutoChartOptions) -> Song {
+    let envelope = OnsetEnvelope::new(audio);
+    let mut onsets = find_onsets(&envelope, options);
+    let mut order: Vec<usize> = (0..onsets.len()).collect();
+    order.sort_by(|a, b| envelope.pitch[onsets[*a].frame].total_cmp(&envelope.pitch[onsets[*b].frame]));
+    for (rank, index) in order.iter().enumerate() {
+        onsets[*index].pitch = rank as f64 / onsets.len() as f64;
+    }
+
+    let mut lanes: Vec<Vec<Note>> = vec![vec![]; options.lanes];
+    // the time the lane is free again and the last time it was pressed
+    let mut busy_until = vec![f64::NEG_INFINITY; options.lanes];
+    let mut last_press = vec![f64::NEG_INFINITY; options.lanes];
+    let mut last_lane = None;
+
+    let mut strengths: Vec<f64> = onsets.iter().map(|onset| onset.strength).collect();
+    strengths.sort_by(|a, b| b.total_cmp(a));
+    let jump_part = JUMP_PART * (options.density.saturating_sub(JUMP_DENSITY) + 1) as f64 / (MAX_DENSITY - JUMP_DENSITY + 1) as f64;
+    let jump_strength = match options.density >= JUMP_DENSITY && options.lanes > 1 {
+        true => strengths.get((strengths.len() as f64 * jump_part) as usize).copied().unwrap_or(f64::INFINITY),
+        false => f64::INFINITY,
+    };
+
+    for (i, onset) in onsets.iter().enumerate() {
+        let wanted = ((onset.pitch * options.lanes as f64) as usize).min(options.lanes - 1);
+        let free = |lane: usize| onset.time >= busy_until[lane] && onset.time - last_press[lane] >= JACK_MS;
+        let Some(lane) = closest_free(wanted, options.lanes, last_lane, &free) else { continue };
+
+        let next_time = onsets.get(i + 1).map_or(audio.length(), |next| next.time);
+        let holding = hold_length(&envelope, onset, next_time);
+        lanes[lane].push(Note::new(onset.time + LEAD_IN_MS, holding));
+        busy_until[lane] = onset.time + holding;
+        last_press[lane] = onset.time;
+        last_lane = Some(lane);
+
+        // the second note of a jump goes on the other side
+        if onset.strength > jump_strength {
+            let free = |lane: usize| onset.time >= busy_until[lane] && onset.time - last_press[lane] >= JACK_MS;
+            if let Some(other) = closest_free(options.lanes - 1 - lane, options.lanes, Some(lane), &free) {
+                lanes[other].push(Note::new(onset.time + LEAD_IN_MS, 0.0));
+                last_press[other] = onset.time;
+            }
+        }
+    }
+
+    Song {
+        name: "Auto chart".to_owned(),
+        lanes,
+        end: audio.length() + LEAD_IN_MS,
+        bpm: None,
+        ..Song::default()
+    }
+}
+
+// the onsets are the peaks of the flux that are over the average of the frames around them, and not too close to the one before
+fn find_onsets(envelope: &OnsetEnvelope, options: &AutoChartOptions) -> Vec<Onset> {
+    let flux = &envelope.flux;
+    if flux.is_empty() {
+        return vec![];
+    }
+    let mean = flux.iter().sum::<f64>() / flux.len() as f64;
+    let deviation = (flux.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / flux.len() as f64).sqrt();
+    let threshold = options.threshold() * deviation;
+
+    let mut onsets: Vec<Onset> = vec![];
+    for frame in 0..flux.len() {
+        let around = |frames: usize| frame.saturating_sub(frames)..(frame + frames + 1).min(flux.len());
+        if flux[around(PEAK_FRAMES)].iter().any(|value| *value > flux[frame]) {
+            continue;
+        }
+        let average = flux[around(AVERAGE_FRAMES)].iter().sum::<f64>() / around(AVERAGE_FRAMES).len() as f64;
+        let strength = flux[frame] - average - threshold;
+        if strength <= 0.0 {
+            continue;
+        }
+
+        let time = envelope.time(frame);
+        match onsets.last_mut() {
+            // of two onsets too close the strongest stays
+            Some(last) if time - last.time < options.min_gap() => {
+                if strength > last.strength {
+                    *last = Onset { frame, time, strength, pitch: 0.0 };
+                }
+            },
+            _ => onsets.push(Onset { frame, time, strength, pitch: 0.0 }),
+        }
+    }
+    onsets
+}
+
+// the free lane nearest to the one wanted, on a tie the one further from the last lane so the hands move
+fn closest_free(wanted: usize, lanes: usize, last_lane: Option<usize>, free: &dyn Fn(usize) -> bool) -> Option<usize> {
+    (0..lanes).filter(|lane| free(*lane))
+        .min_by_key(|lane| (lane.abs_diff(wanted), last_lane.map_or(0, |last| lanes - lane.abs_diff(last))))
+}
+
+// the note is held while the sound stays loud, if there is enough time until the next note. 0 is a tap
+fn hold_length(envelope: &OnsetEnvelope, onset: &Onset, next_time: f64) -> f64 {
+    if next_time - onset.time < HOLD_GAP_MS {
+        return 0.0;
+    }
+    let start_energy = envelope.energy[onset.frame];
+    let mut frame = onset.frame;
+    while frame + 1 < envelope.energy.len() && envelope.time(frame + 1) < next_time - HOLD_RELEASE_MS && envelope.energy[frame + 1] >= start_energy * HOLD_SUSTAIN {
+        frame += 1;
+    }
+    let holding = envelope.time(frame) - onset.time;
+    if holding >= MIN_EDITOR_HOLD_MS { holding.round() } else { 0.0 }
+}
+
+// makes a draft for a song folder from its audio and writes it as charts/auto-<density>.json, returns the chart path.
+// the title, artist and files are taken from the main chart of the folder, so it shows with the other difficulties
+pub fn generate_for_folder(folder: &str, options: &AutoChartOptions) -> Result<String, ChartError> {
+    options.check()?;
+    let main_chart = Song::new(folder, DEFAULT_CHART).ok();
+    let audio_file = main_chart.as_ref().and_then(|song| song.metadata.audio_file.as_ref());
+    let audio_path = song_folder::find_audio(folder, audio_file).ok_or(ChartError::Missing { path: format!("{}/{}/audio.mp3", SONGS_FOLDER, folder) })?;
+
+    let audio = audio::decode(&audio_path)?;
+    let mut song = generate(&audio, options);
+    // with the beat lines the draft can be fixed with the snap of the editor
+    let bpm_list = tempo::detect(&audio, 0.0);
+    song.bpm = if bpm_list.is_empty() { None } else { Some(bpm_list) };
+    song.metadata = SongMetadata {
+        charter: "auto chart".to_owned(),
+        difficulty: Some(format!("Auto {}", options.density)),
+        audio_file: song_folder::file_name(&audio_path),
+        ..main_chart.map_or(SongMetadata::default(), |main| main.metadata)
+    };
+
+    let chart = format!("{}/auto-{}.json", CHARTS_FOLDER, options.density);
+    let folder_path = Path::new(SONGS_FOLDER).join(folder);
+    song_folder::write_chart(&folder_path, &chart, &song).map_err(|err| ChartError::from_io(&folder_path.to_string_lossy(), err))?;
+    Ok(chart)
+}
@@ -0,0 +1,152 @@
+use crate::{input::keybutton::NoteKind, load_song::Song};
+
+// goes up if the data used for the id changes, so old ids never match a new one by accident
+const CHART_ID_VERSION: u8 = 2;
+// FNV-1a of 128 bits, it is the same on every computer and version of rust (the hasher of std is not)
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+struct Fnv(u128);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u128;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    // the times are rounded to whole milliseconds, so saving the same chart again can't change its id
+    fn write_ms(&mut self, value: f64) {
+        self.write(&(value.round() as i64).to_le_bytes());
+    }
+}
+
+// an id made from what is played: the notes, the bpm, the stops, the time signatures and the scroll speed.
+// (version 2 added the scroll speed, a chart that only changes it is played differently)
+// two copies of the same chart have the same id and the name, metadata, sync or the order of the lists don't change it
+pub fn chart_id(song: &Song) -> String {
+    let mut hash = Fnv(FNV_OFFSET);
+    hash.write(&[CHART_ID_VERSION]);
+
+    // the empty lanes count too, so a 5K chart is never the same as a 4K one with the same notes
+    for lane in &song.lanes {
+        let mut notes: Vec<(i64, i64, u8)> = lane.iter()
+            .map(|note| (note.time.round() as i64, note.holding.round() as i64, kind_byte(note.kind)))
+            .collect();
+        notes.sort();
+        hash.write(&(notes.len() as u64).to_le_bytes());
+        for (time, holding, kind) in notes {
+            hash.write(&time.to_le_bytes());
+            hash.write(&holding.to_le_bytes());
+            hash.write(&[kind]);
+        }
+    }
+
+    let mut bpm_list: Vec<_> = song.bpm.iter().flatten().collect();
+    bpm_list.sort_by(|a, b| a.starting_at.total_cmp(&b.starting_at));
+    hash.write(&(bpm_list.len() as u64).to_le_bytes());
+    for change in bpm_list {
+        hash.write_ms(change.starting_at);
+        // the bpm keeps 3 decimals, like stepmania does
+        hash.write(&((change.bpm * 1000.0).round() as i64).to_le_bytes());
+    }
+
+    let mut stops: Vec<_> = song.stops.iter().collect();
+    stops.sort_by(|a, b| a.starting_at.total_cmp(&b.starting_at));
+    hash.write(&(stops.len() as u64).to_le_bytes());
+    for stop in stops {
+        hash.write_ms(stop.starting_at);
+        hash.write_ms(stop.duration);
+        hash.write(&[stop.delay as u8]);
+    }
+
+    let mut signatures: Vec<_> = song.time_signatures.iter().collect();
+    signatures.sort_by(|a, b| a.starting_at.total_cmp(&b.starting_at));
+    hash.write(&(signatures.len() as u64).to_le_bytes());
+    for signature in signatures {
+        hash.write_ms(signature.starting_at);
+        hash.write(&signature.numerator.to_le_bytes());
+        hash.write(&signature.denominator.to_le_bytes());
+    }
+
+    let mut scroll_velocity: Vec<_> = song.scroll_velocity.iter().collect();
+    scroll_velocity.sort_by(|a, b| a.starting_at.total_cmp(&b.starting_at));
+    hash.write(&(scroll_velocity.len() as u64).to_le_bytes());
+    for change in scroll_velocity {
+        hash.write_ms(change.starting_at);
+        hash.write(&((change.multiplier * 1000.0).round() as i64).to_le_bytes());
+        hash.write_ms(change.ease_ms);
+    }
+
+    format!("{:032x}", hash.0)
+}
+
+// a number for each kind that never changes, even if the enum gets new kinds or a different order
+fn kind_byte(kind: NoteKind) -> u8 {
+    match kind {
+        NoteKind::Normal => 0,
+        NoteKind::Mine => 1,
+        NoteKind::Roll => 2,
+        NoteKind::Fake => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chart::scroll::ScrollVelocity, input::keybutton::Note, load_song::Bpm};
+
+    fn song() -> Song {
+        Song {
+            lanes: vec![vec![Note::new(1000.0, 0.0), Note::new(2000.0, 500.0)], vec![Note::new(1500.0, 0.0)], vec![], vec![]],
+            bpm: Some(vec![Bpm { bpm: 120.0, starting_at: 0.0 }, Bpm { bpm: 140.0, starting_at: 4000.0 }]),
+            scroll_velocity: vec![
+                ScrollVelocity { starting_at: 1000.0, multiplier: 2.0, ease_ms: 0.0 },
+                ScrollVelocity { starting_at: 3000.0, multiplier: 1.0, ease_ms: 200.0 },
+            ],
+            end: 10000.0,
+            ..Song::default()
+        }
+    }
+
+    #[test]
+    fn the_order_of_the_lists_does_not_change_it() {
+        let mut reordered = song();
+        reordered.lanes[0].reverse();
+        reordered.bpm.as_mut().unwrap().reverse();
+        reordered.scroll_velocity.reverse();
+
+        assert_eq!(chart_id(&song()), chart_id(&reordered));
+    }
+
+    #[test]
+    fn metadata_and_sync_do_not_change_it() {
+        let mut other = song();
+        other.name = "another name".to_owned();
+        other.metadata.title = "Title".to_owned();
+        other.metadata.charter = "someone".to_owned();
+        other.sync = Some(120.0);
+        other.id = Some(42);
+
+        assert_eq!(chart_id(&song()), chart_id(&other));
+    }
+
+    #[test]
+    fn what_is_played_changes_it() {
+        let id = chart_id(&song());
+
+        let mut notes = song();
+        notes.lanes[1][0].time += 10.0;
+        let mut bpm = song();
+        bpm.bpm.as_mut().unwrap()[1].bpm = 150.0;
+        let mut lanes = song();
+        lanes.lanes.push(vec![]);
+        let mut ease = song();
+        ease.scroll_velocity[1].ease_ms = 400.0;
+
+        for changed in [notes, bpm, lanes, ease] {
+            assert_ne!(id, chart_id(&changed));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::{fmt, io};
+
+// everything that can go wrong while reading a chart, so the player can be told why a song doesn't open
+#[derive(Debug)]
+pub enum ChartError {
+    Missing { path: String },
+    Io { path: String, source: io::Error },
+    Syntax { line: usize, column: usize, message: String },
+    Schema { line: usize, column: usize, message: String },
+    InvalidValue { field: String, reason: String },
+}
+
+impl ChartError {
+    pub fn from_io(path: &str, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => ChartError::Missing { path: path.to_owned() },
+            _ => ChartError::Io { path: path.to_owned(), source: err },
+        }
+    }
+
+    pub fn invalid(field: &str, reason: &str) -> Self {
+        ChartError::InvalidValue { field: field.to_owned(), reason: reason.to_owned() }
+    }
+}
+
+impl fmt::Display for ChartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChartError::Missing { path } => write!(f, "the chart file {} doesn't exist", path),
+            ChartError::Io { path, source } => write!(f, "the chart file {} couldn't be read: {}", path, source),
+            ChartError::Syntax { line, column, message } => write!(f, "the chart json is broken at line {}, column {}: {}", line, column, message),
+            ChartError::Schema { line, column, message } => write!(f, "the chart has a wrong value at line {}, column {}: {}", line, column, message),
+            ChartError::InvalidValue { field, reason } => write!(f, "the chart value {} is not valid: {}", field, reason),
+        }
+    }
+}
+
+impl std::error::Error for ChartError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChartError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for ChartError {
+    fn from(err: serde_json::Error) -> Self {
+        // serde adds the position at the end of the message, we keep it apart so it can be shown on its own
+        let message = err.to_string();
+        let message = match message.rfind(" at line ") {
+            Some(index) => message[..index].to_owned(),
+            None => message,
+        };
+
+        match err.classify() {
+            serde_json::error::Category::Data => ChartError::Schema { line: err.line(), column: err.column(), message },
+            serde_json::error::Category::Io => ChartError::Io { path: "".to_owned(), source: io::Error::other(message) },
+            serde_json::error::Category::Syntax | serde_json::error::Category::Eof => ChartError::Syntax { line: err.line(), column: err.column(), message },
+        }
+    }
+}
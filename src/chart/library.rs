@@ -0,0 +1,294 @@
+use std::{collections::{HashMap, HashSet}, fs, path::Path, time::UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{chart::{analysis, song_folder::{self, SONGS_FOLDER}, validate}, input::keybutton::NoteKind, load_song::{Song, LEAD_IN_MS}};
+
+// the parsed charts are saved here, so the next start only reads the charts that changed
+pub const INDEX_FILE: &str = "library_index.json";
+// goes up when ChartEntry changes, so indexes made by older versions are built again
+const INDEX_VERSION: u64 = 5;
+const COVER_EXTENSIONS: [&str; 5] = ["png", "jpeg", "jpg", "svg", "gif"];
+
+// what the song list needs to know about a chart without opening it
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ChartEntry {
+    pub chart: String,
+    // modification time (milliseconds) and size of the file when it was read, if any of them changes the chart is read again
+    pub modified: u64,
+    pub size: u64,
+    pub title: String,
+    pub label: String,
+    // Song::chart_id, empty when the chart can't be read
+    pub chart_id: String,
+    pub charter: String,
+    pub genre: String,
+    pub source: String,
+    pub tags: Vec<String>,
+    pub min_bpm: Option<f64>,
+    pub max_bpm: Option<f64>,
+    // milliseconds of audio the chart lasts
+    pub length: f64,
+    pub note_count: usize,
+    // the key mode, 0 when the chart can't be read
+    pub lanes: usize,
+    // how hard the chart is, from chart::analysis
+    pub rating: f64,
+    pub audio_file: Option<String>,
+    pub cover_file: Option<String>,
+    // the reason the chart can't be played, the song list still shows it
+    pub error: Option<String>,
+    // the problems found by Song::validate
+    pub error_count: usize,
+    pub warning_count: usize,
+}
+
+pub struct LibraryEntry {
+    pub folder: String,
+    pub title: String,
+    pub charts: Vec<ChartEntry>,
+    pub has_audio: bool,
+    pub cover_path: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct LibraryIndex {
+    version: u64,
+    // the key is "<folder>/<chart>"
+    charts: HashMap<String, ChartEntry>,
+}
+
+pub struct Library {
+    pub songs: Vec<LibraryEntry>,
+}
+
+impl LibraryIndex {
+    // the saved entry if the file has the same modification time and size, if not `read` parses it again.
+    // the bool says if the index changed
+    fn entry(&mut self, key: &str, modified: u64, size: u64, read: impl FnOnce() -> ChartEntry) -> (ChartEntry, bool) {
+        match self.charts.get(key) {
+            Some(cached) if cached.modified == modified && cached.size == size => (cached.clone(), false),
+            _ => {
+                let entry = read();
+                self.charts.insert(key.to_owned(), entry.clone());
+                (entry, true)
+            },
+        }
+    }
+
+    // charts that were deleted are removed from the index too, returns true if there was any
+    fn prune(&mut self, used_keys: &HashSet<String>) -> bool {
+        let before = self.charts.len();
+        self.charts.retain(|key, _| used_keys.contains(key));
+        before != self.charts.len()
+    }
+}
+
+impl ChartEntry {
+    fn read(folder: &str, chart: &str, modified: u64, size: u64) -> ChartEntry {
+        let mut entry = ChartEntry {
+            chart: chart.to_owned(),
+            modified,
+            size,
+            title: folder.to_owned(),
+            label: Song::chart_label(chart),
+            chart_id: "".to_owned(),
+            charter: "".to_owned(),
+            genre: "".to_owned(),
+            source: "".to_owned(),
+            tags: vec![],
+            min_bpm: None,
+            max_bpm: None,
+            length: 0.0,
+            note_count: 0,
+            lanes: 0,
+            rating: 0.0,
+            audio_file: None,
+            cover_file: None,
+            error: None,
+            error_count: 0,
+            warning_count: 0,
+        };
+
+        match Song::new(folder, chart) {
+            Ok(song) => {
+                entry.title = song.display_title(folder);
+                if let Some(difficulty) = &song.metadata.difficulty {
+                    entry.label = difficulty.clone();
+                }
+                let bpms: Vec<f64> = song.bpm.iter().flatten().map(|bpm| bpm.bpm).filter(|bpm| *bpm > 0.0).collect();
+                entry.min_bpm = bpms.iter().copied().reduce(f64::min);
+                entry.max_bpm = bpms.iter().copied().reduce(f64::max);
+                entry.length = (song.end - LEAD_IN_MS).max(0.0);
+                (entry.error_count, entry.warning_count) = validate::count(&song.validate());
+                // mines and fake notes are not something the player has to hit
+                entry.note_count = song.lanes.iter()
+                    .map(|lane| lane.iter().filter(|note| matches!(note.kind, NoteKind::Normal | NoteKind::Roll)).count())
+                    .sum();
+                entry.lanes = song.lane_count();
+                entry.rating = analysis::analyze(&song).rating;
+                entry.chart_id = song.chart_id();
+                entry.charter = song.metadata.charter;
+                entry.genre = song.metadata.genre;
+                entry.source = song.metadata.source;
+                entry.tags = song.metadata.tags;
+                entry.audio_file = song.metadata.audio_file;
+                entry.cover_file = song.metadata.cover_file;
+            },
+            Err(err) => entry.error = Some(err.to_string()),
+        }
+        entry
+    }
+}
+
+impl LibraryEntry {
+    pub fn main_chart(&self) -> Option<&ChartEntry> {
+        self.charts.iter().find(|chart| chart.error.is_none()).or(self.charts.first())
+    }
+
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.title.to_lowercase().contains(&query) || self.folder.to_lowercase().contains(&query) ||
+            self.charts.iter().any(|chart| chart.charter.to_lowercase().contains(&query) || chart.tags.iter().any(|tag| tag.to_lowercase() == query))
+    }
+}
+
+impl Library {
+    // reads every folder inside songs, only the charts that changed since the last scan are parsed
+    pub fn scan() -> Library {
+        let mut index: LibraryIndex = fs::read_to_string(INDEX_FILE).ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .filter(|index: &LibraryIndex| index.version == INDEX_VERSION)
+            .unwrap_or_default();
+        let mut changed = index.version != INDEX_VERSION;
+        index.version = INDEX_VERSION;
+
+        let mut folders: Vec<String> = match fs::read_dir(SONGS_FOLDER) {
+            Ok(entries) => entries.flatten()
+                .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_dir()))
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect(),
+            Err(err) => {
+                eprintln!("The songs folder couldn't be read: {}", err);
+                vec![]
+            },
+        };
+        folders.sort_by_key(|folder| folder.to_lowercase());
+
+        let mut songs = vec![];
+        let mut used_keys = HashSet::new();
+
+        for folder in folders {
+            let mut charts = vec![];
+            for chart in Song::charts(&folder) {
+                let key = folder.clone() + "/" + &chart;
+                let (modified, size) = file_stamp(&Song::chart_path(&folder, &chart));
+
+                let (entry, read) = index.entry(&key, modified, size, || ChartEntry::read(&folder, &chart, modified, size));
+                changed |= read;
+                used_keys.insert(key);
+                charts.push(entry);
+            }
+
+            let song_path = Path::new(SONGS_FOLDER).join(&folder);
+            let main_chart = charts.iter().find(|chart| chart.error.is_none());
+            let cover_path = main_chart.and_then(|chart| chart.cover_file.clone())
+                .map(|file| song_path.join(file))
+                .filter(|path| path.exists())
+                .or_else(|| COVER_EXTENSIONS.iter().map(|extension| song_path.join("cover.".to_owned() + extension)).find(|path| path.exists()))
+                .map(|path| path.to_string_lossy().to_string());
+
+            songs.push(LibraryEntry {
+                title: main_chart.map_or(folder.clone(), |chart| chart.title.clone()),
+                has_audio: song_folder::find_audio(&folder, main_chart.and_then(|chart| chart.audio_file.as_ref())).is_some(),
+                cover_path,
+                charts,
+                folder,
+            });
+        }
+
+        changed |= index.prune(&used_keys);
+
+        if changed {
+            let saved = serde_json::to_string(&index).map_err(|err| err.to_string())
+                .and_then(|json_string| fs::write(INDEX_FILE, json_string).map_err(|err| err.to_string()));
+            if let Err(err) = saved {
+                eprintln!("The song library index couldn't be saved: {}", err);
+            }
+        }
+
+        Library { songs }
+    }
+
+    // songs whose title, folder, charter or tags have the text
+    pub fn search(&self, query: &str) -> Vec<&LibraryEntry> {
+        self.songs.iter().filter(|song| song.matches(query)).collect()
+    }
+}
+
+// a bpm like 127.5 keeps its decimals, a whole one is shown without them
+pub fn bpm_text(bpm: f64) -> String {
+    let text = format!("{:.2}", bpm);
+    text.trim_end_matches('0').trim_end_matches('.').to_owned()
+}
+
+fn file_stamp(path: &str) -> (u64, u64) {
+    match fs::metadata(path) {
+        Ok(metadata) => {
+            let modified = metadata.modified().ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_millis() as u64);
+            (modified, metadata.len())
+        },
+        Err(_) => (0, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an entry of a chart that doesn't exist, it only needs the time and size of the file
+    fn entry(modified: u64, size: u64) -> ChartEntry {
+        ChartEntry::read("no such folder", "data.json", modified, size)
+    }
+
+    #[test]
+    fn a_chart_is_read_again_only_when_its_file_changes() {
+        let mut index = LibraryIndex::default();
+        let mut reads = 0;
+        let mut scan = |index: &mut LibraryIndex, modified: u64, size: u64| {
+            index.entry("song/data.json", modified, size, || { reads += 1; entry(modified, size) }).1
+        };
+
+        assert!(scan(&mut index, 10, 200));
+        assert!(!scan(&mut index, 10, 200));
+        // saved again at another time, or with other contents of the same time
+        assert!(scan(&mut index, 20, 200));
+        assert!(scan(&mut index, 20, 300));
+        assert!(!scan(&mut index, 20, 300));
+        assert_eq!(reads, 3);
+        assert_eq!(index.charts["song/data.json"].size, 300);
+    }
+
+    #[test]
+    fn removed_charts_leave_the_index() {
+        let mut index = LibraryIndex::default();
+        index.charts.insert("song/data.json".to_owned(), entry(1, 1));
+        index.charts.insert("song/charts/hard.json".to_owned(), entry(1, 1));
+        index.charts.insert("gone/data.json".to_owned(), entry(1, 1));
+
+        let used: HashSet<String> = ["song/data.json".to_owned(), "song/charts/hard.json".to_owned()].into();
+        assert!(index.prune(&used));
+        assert!(!index.prune(&used));
+        assert_eq!(index.charts.len(), 2);
+        assert!(!index.charts.contains_key("gone/data.json"));
+    }
+
+    #[test]
+    fn bpm_text_keeps_only_the_needed_decimals() {
+        assert_eq!(bpm_text(120.0), "120");
+        assert_eq!(bpm_text(127.5), "127.5");
+        assert_eq!(bpm_text(133.333), "133.33");
+    }
+}
@@ -0,0 +1,253 @@
+use std::{fs, path::{Path, PathBuf}};
+
+use crate::{chart::song_folder::SONGS_FOLDER, load_song::{Song, LEAD_IN_MS}};
+
+// the lyrics file the game looks for when the chart doesn't name one
+const DEFAULT_LYRICS: &str = "lyrics.lrc";
+// a line without a time that ends it stays until the next one, but not longer than this (the last line has no next one)
+const LONGEST_LINE_MS: f64 = 10000.0;
+
+// the words of a line with the time they start, None for the words without a time of their own
+type TimedWords = Vec<(Option<f64>, String)>;
+
+// a part of a line that is sung at once, on a normal lrc file the whole line is a single word
+#[derive(Debug, Clone)]
+pub struct LyricWord {
+    pub time: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LyricLine {
+    pub time: f64,
+    pub end: f64,
+    pub words: Vec<LyricWord>,
+}
+
+impl LyricLine {
+    pub fn text(&self) -> String {
+        self.words.iter().map(|word| word.text.as_str()).collect()
+    }
+}
+
+// the lines of the song in time order. the times are milliseconds of the audio when it is read,
+// and times of the chart (like the notes) after `into_chart_time`
+#[derive(Debug, Clone, Default)]
+pub struct Lyrics {
+    pub lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+    // reads a lrc file: lines like "[01:02.50]some text", a line can have more than one time ("[00:10.00][01:10.00]chorus"),
+    // the [offset:ms] tag moves every line and the enhanced lrc "<01:02.50>word <01:03.10>word" gives the time of every word.
+    // a time without text ends the line before it, the other tags (title, artist...) are not used
+    pub fn parse(text: &str) -> Result<Lyrics, String> {
+        let mut offset = 0.0;
+        // the time of the line and its words
+        let mut parsed: Vec<(f64, TimedWords)> = vec![];
+
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = vec![];
+            while let Some(tag_end) = rest.strip_prefix('[').and_then(|tag| tag.find(']')) {
+                let tag = &rest[1..tag_end + 1];
+                rest = &rest[tag_end + 2..];
+                match parse_time(tag) {
+                    Some(time) => times.push(time),
+                    None => {
+                        if let Some(value) = tag.strip_prefix("offset:") {
+                            offset = value.trim().parse::<f64>().map_err(|_| format!("the offset {} is not a number", value.trim()))?;
+                        }
+                    },
+                }
+            }
+
+            let words = parse_words(rest.trim_end());
+            for time in times {
+                parsed.push((time, words.clone()));
+            }
+        }
+        parsed.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut lines: Vec<LyricLine> = vec![];
+        for (i, (time, words)) in parsed.iter().enumerate() {
+            if words.iter().all(|(_, text)| text.trim().is_empty()) {
+                continue;
+            }
+            // a positive offset shows the lyrics sooner
+            let time = time - offset;
+            let next_line = parsed.get(i + 1).map_or(f64::INFINITY, |(next, _)| next - offset).min(time + LONGEST_LINE_MS);
+            // on the enhanced lrc a time after the last word says when the singing stops
+            let end = match words.last() {
+                Some((Some(word_time), text)) if text.trim().is_empty() => (word_time - offset).min(next_line),
+                _ => next_line,
+            }.max(time);
+
+            let mut line_words: Vec<LyricWord> = vec![];
+            for (word_time, text) in words.iter().filter(|(_, text)| !text.is_empty()) {
+                match (word_time, line_words.last_mut()) {
+                    (None, Some(last)) => last.text.push_str(text),
+                    _ => line_words.push(LyricWord { time: word_time.map_or(time, |word_time| word_time - offset).clamp(time, end), end, text: text.clone() }),
+                }
+            }
+            for word in 1..line_words.len() {
+                line_words[word - 1].end = line_words[word].time;
+            }
+            lines.push(LyricLine { time, end, words: line_words });
+        }
+
+        if lines.is_empty() {
+            return Err("the lyrics don't have any line with a time".to_owned());
+        }
+        Ok(Lyrics { lines })
+    }
+
+    // the lyrics of a song folder: the file named on the chart metadata, then lyrics.lrc, and then any .lrc of the folder.
+    // a folder without lyrics is not an error, most songs don't have them
+    pub fn load(folder: &str, lyrics_file: Option<&String>) -> Result<Option<Lyrics>, String> {
+        let Some(path) = find_lyrics(folder, lyrics_file) else { return Ok(None) };
+        let text = fs::read_to_string(&path).map_err(|err| format!("{} couldn't be read: {}", path.to_string_lossy(), err))?;
+        // some programs save the lrc with the utf-8 mark at the start
+        let lyrics = Self::parse(text.trim_start_matches('\u{feff}')).map_err(|err| format!("{}: {}", path.to_string_lossy(), err))?;
+        Ok(Some(lyrics))
+    }
+
+    // the lyrics of the folder with the times of the chart, so they are shown with the notes
+    pub fn load_for_chart(folder: &str, song: &Song) -> Result<Option<Lyrics>, String> {
+        Ok(Self::load(folder, song.metadata.lyrics_file.as_ref())?.map(|lyrics| lyrics.into_chart_time(song.sync.unwrap_or(0.0))))
+    }
+
+    // the lyrics are timed on the audio, on the chart the audio starts after the lead in and is moved by the sync
+    pub fn into_chart_time(mut self, sync: f64) -> Lyrics {
+        let shift = LEAD_IN_MS - sync;
+        for line in self.lines.iter_mut() {
+            line.time += shift;
+            line.end += shift;
+            for word in line.words.iter_mut() {
+                word.time += shift;
+                word.end += shift;
+            }
+        }
+        self
+    }
+
+    // the line being sung at this time, if there is one
+    pub fn current(&self, time: f64) -> Option<usize> {
+        self.lines.iter().rposition(|line| line.time <= time).filter(|line| time < self.lines[*line].end)
+    }
+
+    // the first line that starts after this time
+    pub fn next(&self, time: f64) -> Option<usize> {
+        self.lines.iter().position(|line| line.time > time)
+    }
+}
+
+// "01:02.50" or "01:02:50" (some programs use a colon for the hundredths) to milliseconds, None for the other tags
+fn parse_time(tag: &str) -> Option<f64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes = minutes.trim().parse::<u32>().ok()?;
+    let seconds = match seconds.split_once(':') {
+        Some((seconds, hundredths)) => format!("{}.{}", seconds, hundredths),
+        None => seconds.to_owned(),
+    };
+    let seconds = seconds.trim().parse::<f64>().ok().filter(|seconds| (0.0..60.0).contains(seconds))?;
+    Some((minutes as f64 * 60.0 + seconds) * 1000.0)
+}
+
+// the text of a line cut on the <mm:ss.xx> times of the enhanced lrc, the spaces stay with the word before them
+fn parse_words(text: &str) -> TimedWords {
+    let mut words = vec![];
+    let mut time = None;
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let Some(length) = rest[start..].find('>') else { break };
+        let Some(word_time) = parse_time(&rest[start + 1..start + length]) else {
+            // a < that is not a time is part of the text
+            words.push((time.take(), rest[..start + 1].to_owned()));
+            rest = &rest[start + 1..];
+            continue;
+        };
+        if start > 0 || time.is_some() {
+            words.push((time, rest[..start].to_owned()));
+        }
+        time = Some(word_time);
+        rest = &rest[start + length + 1..];
+    }
+    words.push((time, rest.to_owned()));
+    words
+}
+
+fn find_lyrics(folder: &str, lyrics_file: Option<&String>) -> Option<PathBuf> {
+    let folder_path = Path::new(SONGS_FOLDER).join(folder);
+
+    if let Some(path) = lyrics_file.map(|file| folder_path.join(file)).filter(|path| path.is_file()) {
+        return Some(path);
+    }
+    let default_path = folder_path.join(DEFAULT_LYRICS);
+    if default_path.is_file() {
+        return Some(default_path);
+    }
+
+    let mut lyrics_files: Vec<PathBuf> = fs::read_dir(&folder_path).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension.to_string_lossy().eq_ignore_ascii_case("lrc")))
+        .collect();
+    lyrics_files.sort();
+    lyrics_files.into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_end_at_the_next_time() {
+        let lyrics = Lyrics::parse("[ti:Song]\n[00:01.00]first\n[00:02.50]second\n[00:04.00]\n").unwrap();
+
+        assert_eq!(lyrics.lines.len(), 2);
+        assert_eq!(lyrics.lines[0].time, 1000.0);
+        assert_eq!(lyrics.lines[0].end, 2500.0);
+        assert_eq!(lyrics.lines[1].text(), "second");
+        assert_eq!(lyrics.lines[1].end, 4000.0);
+        assert_eq!(lyrics.current(3000.0), Some(1));
+        assert_eq!(lyrics.current(4500.0), None);
+        assert_eq!(lyrics.next(1500.0), Some(1));
+    }
+
+    #[test]
+    fn a_line_with_more_times_is_repeated_and_the_offset_moves_them() {
+        let lyrics = Lyrics::parse("[offset:500]\n[00:10.00][01:10.00]chorus\n").unwrap();
+
+        assert_eq!(lyrics.lines.len(), 2);
+        assert_eq!(lyrics.lines[0].time, 9500.0);
+        assert_eq!(lyrics.lines[1].time, 69500.0);
+        // the last line has no next one
+        assert_eq!(lyrics.lines[1].end, 69500.0 + LONGEST_LINE_MS);
+    }
+
+    #[test]
+    fn enhanced_lines_time_every_word() {
+        let lyrics = Lyrics::parse("[00:16.00]<00:16.00>Is <00:16.60>this 1<2 <00:17.50>fantasy?<00:19.00>").unwrap();
+        let words = &lyrics.lines[0].words;
+
+        assert_eq!(lyrics.lines[0].end, 19000.0);
+        assert_eq!(words.len(), 3);
+        assert_eq!((words[1].time, words[1].end), (16600.0, 17500.0));
+        assert_eq!(words[1].text, "this 1<2 ");
+        assert_eq!(lyrics.lines[0].text(), "Is this 1<2 fantasy?");
+    }
+
+    #[test]
+    fn times_move_to_the_chart() {
+        let lyrics = Lyrics::parse("[00:01.00]line").unwrap().into_chart_time(200.0);
+        assert_eq!(lyrics.lines[0].time, 1000.0 + LEAD_IN_MS - 200.0);
+    }
+
+    #[test]
+    fn lyrics_without_times_are_an_error() {
+        assert!(Lyrics::parse("[ar:Someone]\nno time here").is_err());
+        assert!(Lyrics::parse("[offset:soon]\n[00:01.00]line").is_err());
+    }
+}
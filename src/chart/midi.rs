@@ -0,0 +1,392 @@
+use std::{fs, path::Path};
+
+use crate::{chart::{error::ChartError, song_folder}, input::keybutton::Note, load_song::{Bpm, Song, DEFAULT_CHART, DEFAULT_LANES, LEAD_IN_MS, MAX_LANES}};
+
+// a quarter note at 120 bpm, used until the file sets its own tempo
+const DEFAULT_TEMPO: u32 = 500_000;
+// the same threshold the S key recording uses, shorter notes are taps
+const MIN_HOLD_MS: f64 = 200.0;
+
+pub struct MidiNote {
+    pub tick: u64,
+    pub end_tick: u64,
+    pub pitch: u8,
+}
+
+pub struct MidiTrack {
+    pub name: String,
+    pub notes: Vec<MidiNote>,
+}
+
+pub struct MidiFile {
+    pub ticks_per_quarter: u16,
+    pub tracks: Vec<MidiTrack>,
+    // (tick, microseconds per quarter note), on format 1 files all of them are on the first track
+    pub tempos: Vec<(u64, u32)>,
+}
+
+// how the pitches of the track are placed on the lanes
+pub enum LaneMapping {
+    // the pitch range of the track is split in equal parts for that amount of lanes, low pitches on the left
+    Auto(usize),
+    // only the listed pitches are used, for example a drum track: 36 on the left and 38 on the right.
+    // the pitches have the index of their lane, the chart gets `lanes` lanes
+    Pitches { pitches: Vec<(u8, usize)>, lanes: usize },
+}
+
+impl LaneMapping {
+    // reads "36=left,38=up,42=bottom,46=right" (4K), "36=1,38=2,42=3" (the lanes counted from the left),
+    // "auto" (4K) or "auto7" for the auto mapping with other key mode
+    pub fn parse(text: &str) -> Result<LaneMapping, ChartError> {
+        if let Some(count) = text.strip_prefix("auto") {
+            let lanes = if count.is_empty() { DEFAULT_LANES } else { count.parse().unwrap_or(0) };
+            if lanes == 0 || lanes > MAX_LANES {
+                return Err(ChartError::invalid("mapping", &format!("{} is not a key mode, use auto1 to auto{}", text, MAX_LANES)));
+            }
+            return Ok(LaneMapping::Auto(lanes));
+        }
+
+        let mut pitches = vec![];
+        let mut lanes = 1;
+        for pair in text.split(',') {
+            let Some((pitch, lane)) = pair.split_once('=') else {
+                return Err(ChartError::invalid("mapping", &format!("{} is not a pitch=lane pair", pair)));
+            };
+            let pitch: u8 = pitch.trim().parse().map_err(|_| ChartError::invalid("mapping", &format!("{} is not a midi pitch", pitch)))?;
+            // the names are the lanes of 4K, so a mapping with them makes a chart of at least 4 lanes
+            let (lane, count) = match lane.trim() {
+                "left" => (0, DEFAULT_LANES),
+                "up" => (1, DEFAULT_LANES),
+                "bottom" => (2, DEFAULT_LANES),
+                "right" => (3, DEFAULT_LANES),
+                other => match other.parse::<usize>() {
+                    Ok(number) if (1..=MAX_LANES).contains(&number) => (number - 1, number),
+                    _ => return Err(ChartError::invalid("mapping", &format!("{} is not a lane, use left, up, bottom, right or a number from 1 to {}", other, MAX_LANES))),
+                },
+            };
+            lanes = lanes.max(count);
+            pitches.push((pitch, lane));
+        }
+        Ok(LaneMapping::Pitches { pitches, lanes })
+    }
+
+    // the amount of lanes of the chart made with this mapping
+    pub fn lane_count(&self) -> usize {
+        match self {
+            LaneMapping::Auto(lanes) => *lanes,
+            LaneMapping::Pitches { lanes, .. } => *lanes,
+        }
+    }
+
+    fn lane(&self, pitch: u8, lowest: u8, highest: u8) -> Option<usize> {
+        match self {
+            LaneMapping::Auto(lanes) => {
+                let range = (highest - lowest) as usize + 1;
+                Some(((pitch - lowest) as usize * lanes / range).min(lanes - 1))
+            },
+            LaneMapping::Pitches { pitches, .. } => pitches.iter().find(|(mapped, _)| *mapped == pitch).map(|(_, lane)| *lane),
+        }
+    }
+}
+
+impl MidiFile {
+    pub fn parse(bytes: &[u8]) -> Result<MidiFile, ChartError> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(4)? != b"MThd" {
+            return Err(ChartError::invalid("MThd", "the file is not a midi file"));
+        }
+        let header_length = reader.u32()? as usize;
+        let _format = reader.u16()?;
+        let track_count = reader.u16()?;
+        let division = reader.u16()?;
+        reader.take(header_length.saturating_sub(6))?;
+
+        if division == 0 || division & 0x8000 != 0 {
+            return Err(ChartError::invalid("division", "midi files timed with SMPTE frames are not supported"));
+        }
+
+        let mut file = MidiFile { ticks_per_quarter: division, tracks: vec![], tempos: vec![] };
+
+        for _ in 0..track_count {
+            let chunk = reader.take(4)?;
+            let length = reader.u32()? as usize;
+            if chunk != b"MTrk" {
+                // unknown chunks have to be skipped
+                reader.take(length)?;
+                continue;
+            }
+            let track = parse_track(reader.take(length)?, &mut file.tempos)?;
+            file.tracks.push(track);
+        }
+
+        file.tempos.sort_by_key(|(tick, _)| *tick);
+        Ok(file)
+    }
+
+    pub fn tick_to_ms(&self, tick: u64) -> f64 {
+        let mut ms = 0.0;
+        let mut last_tick = 0;
+        let mut tempo = DEFAULT_TEMPO;
+
+        for (change_tick, change_tempo) in self.tempos.iter().take_while(|(change_tick, _)| *change_tick <= tick) {
+            ms += (change_tick - last_tick) as f64 * tempo as f64 / 1000.0 / self.ticks_per_quarter as f64;
+            last_tick = *change_tick;
+            tempo = *change_tempo;
+        }
+        ms + (tick - last_tick) as f64 * tempo as f64 / 1000.0 / self.ticks_per_quarter as f64
+    }
+
+    // the chosen track as a chart, the midi starts with the audio so the times get the lead in
+    pub fn to_song(&self, track_index: usize, mapping: &LaneMapping, name: &str) -> Result<Song, ChartError> {
+        let Some(track) = self.tracks.get(track_index) else {
+            return Err(ChartError::invalid("track", &format!("the file has {} tracks, there is no track {}", self.tracks.len(), track_index)));
+        };
+        if track.notes.is_empty() {
+            return Err(ChartError::invalid("track", &format!("the track {} doesn't have notes", track_index)));
+        }
+
+        let lowest = track.notes.iter().map(|note| note.pitch).min().unwrap_or(0);
+        let highest = track.notes.iter().map(|note| note.pitch).max().unwrap_or(0);
+        let mut lanes: Vec<Vec<Note>> = vec![vec![]; mapping.lane_count()];
+
+        for note in &track.notes {
+            let Some(lane) = mapping.lane(note.pitch, lowest, highest) else { continue };
+            let time = self.tick_to_ms(note.tick) + LEAD_IN_MS;
+            let holding = self.tick_to_ms(note.end_tick) + LEAD_IN_MS - time;
+            lanes[lane].push(Note::new(time, if holding < MIN_HOLD_MS { 0.0 } else { holding }));
+        }
+
+        let mut end: f64 = 0.0;
+        for lane in lanes.iter_mut() {
+            lane.sort_by(|a, b| a.time.total_cmp(&b.time));
+            // chords can put two pitches on the same key at the same time, only one note is kept
+            lane.dedup_by(|next, note| next.time - note.time < 1.0);
+            // a hold can't go over the next note of its key
+            for i in 1..lane.len() {
+                let next_time = lane[i].time;
+                let note = &mut lane[i - 1];
+                if note.time + note.holding >= next_time {
+                    note.holding = if next_time - note.time < MIN_HOLD_MS { 0.0 } else { next_time - note.time - 1.0 };
+                }
+            }
+            for note in lane.iter() {
+                end = end.max(note.time + note.holding);
+            }
+        }
+
+        let mut tempos = self.tempos.clone();
+        if tempos.first().is_none_or(|(tick, _)| *tick > 0) {
+            tempos.insert(0, (0, DEFAULT_TEMPO));
+        }
+        let bpm: Vec<Bpm> = tempos.iter()
+            .map(|(tick, tempo)| Bpm { bpm: 60_000_000.0 / *tempo as f64, starting_at: self.tick_to_ms(*tick) + LEAD_IN_MS })
+            .collect();
+
+        Ok(Song {
+            name: name.to_owned(),
+            lanes,
+            end: end + 1000.0,
+            bpm: Some(bpm),
+            ..Song::default()
+        })
+    }
+}
+
+// reads a .mid file and writes the chosen track inside songs as a new folder, returns the folder name
+// midi files don't have audio, the song rendered from the DAW has to be copied to the folder as audio.mp3 (or any other audio format)
+pub fn import(midi_file: &Path, track: usize, mapping: &LaneMapping) -> Result<String, ChartError> {
+    let path = midi_file.to_string_lossy().to_string();
+    let bytes = fs::read(midi_file).map_err(|err| ChartError::from_io(&path, err))?;
+    let file = MidiFile::parse(&bytes)?;
+
+    let file_name = midi_file.file_stem().map_or("Imported song".to_owned(), |stem| stem.to_string_lossy().to_string());
+    let track_name = file.tracks.get(track).map_or("", |track| track.name.as_str());
+    let name = if track_name.is_empty() { file_name } else { format!("{} [{}]", file_name, track_name) };
+    let song = file.to_song(track, mapping, &name)?;
+
+    let (folder, folder_path) = song_folder::create_song_folder(&name).map_err(|err| ChartError::from_io(&name, err))?;
+    song_folder::write_chart(&folder_path, DEFAULT_CHART, &song).map_err(|err| ChartError::from_io(&folder_path.to_string_lossy(), err))?;
+    Ok(folder)
+}
+
+// a line for every track of the file, so the player knows which number to use on the import
+pub fn describe_tracks(midi_file: &Path) -> Result<Vec<String>, ChartError> {
+    let path = midi_file.to_string_lossy().to_string();
+    let bytes = fs::read(midi_file).map_err(|err| ChartError::from_io(&path, err))?;
+    let file = MidiFile::parse(&bytes)?;
+
+    Ok(file.tracks.iter().enumerate().map(|(i, track)| {
+        let lowest = track.notes.iter().map(|note| note.pitch).min().unwrap_or(0);
+        let highest = track.notes.iter().map(|note| note.pitch).max().unwrap_or(0);
+        format!("{}: {} ({} notes, pitches {} to {})", i, if track.name.is_empty() { "no name" } else { &track.name }, track.notes.len(), lowest, highest)
+    }).collect())
+}
+
+fn parse_track(bytes: &[u8], tempos: &mut Vec<(u64, u32)>) -> Result<MidiTrack, ChartError> {
+    let mut reader = Reader { bytes, position: 0 };
+    let mut track = MidiTrack { name: "".to_owned(), notes: vec![] };
+    // notes waiting for their note off: (channel, pitch, tick)
+    let mut open_notes: Vec<(u8, u8, u64)> = vec![];
+    let mut tick: u64 = 0;
+    let mut running_status: u8 = 0;
+
+    while reader.position < bytes.len() {
+        tick += reader.variable_length()? as u64;
+
+        let mut status = reader.u8()?;
+        if status < 0x80 {
+            // running status, the byte read was already the first data byte
+            reader.position -= 1;
+            status = running_status;
+        }
+
+        match status {
+            0xFF => {
+                let kind = reader.u8()?;
+                let length = reader.variable_length()? as usize;
+                let data = reader.take(length)?;
+                match kind {
+                    0x03 => track.name = String::from_utf8_lossy(data).trim().to_owned(),
+                    0x51 if length == 3 && data != [0, 0, 0] => tempos.push((tick, (data[0] as u32) << 16 | (data[1] as u32) << 8 | data[2] as u32)),
+                    0x2F => break,
+                    _ => {},
+                }
+            },
+            0xF0 | 0xF7 => {
+                let length = reader.variable_length()? as usize;
+                reader.take(length)?;
+            },
+            0x80..=0xEF => {
+                running_status = status;
+                let channel = status & 0x0F;
+                let first = reader.u8()?;
+                // program change and channel pressure have a single data byte
+                let second = if matches!(status & 0xF0, 0xC0 | 0xD0) { 0 } else { reader.u8()? };
+
+                match status & 0xF0 {
+                    0x90 if second > 0 => open_notes.push((channel, first, tick)),
+                    0x80 | 0x90 => {
+                        if let Some(index) = open_notes.iter().position(|(open_channel, pitch, _)| *open_channel == channel && *pitch == first) {
+                            let (_, pitch, start) = open_notes.remove(index);
+                            track.notes.push(MidiNote { tick: start, end_tick: tick, pitch });
+                        }
+                    },
+                    _ => {},
+                }
+            },
+            _ => return Err(ChartError::invalid("MTrk", &format!("unknown midi event {:#x}", status))),
+        }
+    }
+
+    // notes without note off end with the track
+    for (_, pitch, start) in open_notes {
+        track.notes.push(MidiNote { tick: start, end_tick: start, pitch });
+    }
+    track.notes.sort_by_key(|note| note.tick);
+    Ok(track)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ChartError> {
+        if self.position + length > self.bytes.len() {
+            return Err(ChartError::invalid("midi", "the file ends in the middle of a chunk"));
+        }
+        let slice = &self.bytes[self.position..self.position + length];
+        self.position += length;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, ChartError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ChartError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, ChartError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // midi saves times and lengths with 7 bits per byte, the high bit says if another byte follows
+    fn variable_length(&mut self) -> Result<u32, ChartError> {
+        let mut value: u32 = 0;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ChartError::invalid("midi", "a variable length value is longer than 4 bytes"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a format 0 file with 480 ticks a quarter note at 120 bpm: the pitch 60 held a quarter,
+    // and then the pitch 62 for 96 ticks ended with running status and velocity 0
+    fn midi_bytes() -> Vec<u8> {
+        let track: Vec<u8> = vec![
+            0x00, 0xFF, 0x03, 0x04, b'D', b'r', b'u', b'm',
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20,
+            0x00, 0x90, 60, 100,
+            0x83, 0x60, 0x80, 60, 0,
+            0x00, 0x90, 62, 100,
+            0x60, 62, 0,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut bytes = b"MThd".to_vec();
+        bytes.extend([0, 0, 0, 6, 0, 0, 0, 1, 0x01, 0xE0]);
+        bytes.extend(b"MTrk");
+        bytes.extend((track.len() as u32).to_be_bytes());
+        bytes.extend(track);
+        bytes
+    }
+
+    #[test]
+    fn parses_the_notes_of_a_track() {
+        let file = MidiFile::parse(&midi_bytes()).unwrap();
+        let track = &file.tracks[0];
+
+        assert_eq!(file.ticks_per_quarter, 480);
+        assert_eq!(file.tempos, vec![(0, 500_000)]);
+        assert_eq!(track.name, "Drum");
+        assert_eq!(track.notes.len(), 2);
+        assert_eq!((track.notes[0].tick, track.notes[0].end_tick, track.notes[0].pitch), (0, 480, 60));
+        assert_eq!((track.notes[1].tick, track.notes[1].end_tick, track.notes[1].pitch), (480, 576, 62));
+        assert_eq!(file.tick_to_ms(480), 500.0);
+    }
+
+    #[test]
+    fn maps_the_pitches_to_lanes() {
+        let file = MidiFile::parse(&midi_bytes()).unwrap();
+        let song = file.to_song(0, &LaneMapping::parse("60=left,62=right").unwrap(), "Drum").unwrap();
+
+        assert_eq!(song.lanes.len(), 4);
+        assert_eq!((song.lanes[0][0].time, song.lanes[0][0].holding), (LEAD_IN_MS, 500.0));
+        // 100 ms is too short for a hold
+        assert_eq!((song.lanes[3][0].time, song.lanes[3][0].holding), (LEAD_IN_MS + 500.0, 0.0));
+        assert_eq!(song.bpm.as_ref().unwrap()[0].bpm, 120.0);
+    }
+
+    #[test]
+    fn reads_the_lane_mappings() {
+        assert_eq!(LaneMapping::parse("auto").unwrap().lane_count(), DEFAULT_LANES);
+        assert_eq!(LaneMapping::parse("auto7").unwrap().lane_count(), 7);
+        assert_eq!(LaneMapping::parse("36=1,38=6").unwrap().lane_count(), 6);
+        assert!(LaneMapping::parse("auto11").is_err());
+        assert!(LaneMapping::parse("36=middle").is_err());
+        assert!(MidiFile::parse(b"RIFF").is_err());
+    }
+}
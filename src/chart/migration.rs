@@ -0,0 +1,197 @@
+use serde::Deserialize;
+
+use crate::{chart::{error::ChartError, scroll::ScrollVelocity, sections::Section, storyboard::{ChartEvent, EventAction}}, input::keybutton::{Note, NoteKind}, load_song::{Bpm, Song, SongMetadata, Stop, TimeSignature}};
+
+// every time the shape of data.json changes this number goes up and LegacySong learns how to read the old shape
+pub const CURRENT_FORMAT_VERSION: u64 = 4;
+// until version 2 every time in the chart was saved in ticks of 10 milliseconds
+const LEGACY_TICK_MS: f64 = 10.0;
+
+#[derive(Deserialize)]
+struct ChartVersion {
+    format_version: Option<u64>,
+}
+
+// a bpm change of an old chart, its time can still be in ticks
+#[derive(Deserialize)]
+struct LegacyBpmChange {
+    bpm: f64,
+    #[serde(default)]
+    starting_at: f64,
+}
+
+// the bpm used to be a single number (for example "bpm": 200) before the list of bpm changes existed
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyBpm {
+    Single(f64),
+    List(Vec<LegacyBpmChange>),
+}
+
+#[derive(Deserialize)]
+struct LegacyNote {
+    time: f64,
+    #[serde(default)]
+    holding: f64,
+    #[serde(default)]
+    kind: NoteKind,
+}
+
+// every shape a chart had on older versions, so every field is optional and gets a value on `upgrade`
+#[derive(Deserialize)]
+struct LegacySong {
+    format_version: Option<u64>,
+    name: Option<String>,
+    id: Option<i128>,
+    #[serde(default)]
+    left_keys: Vec<LegacyNote>,
+    #[serde(default)]
+    up_keys: Vec<LegacyNote>,
+    #[serde(default)]
+    bottom_keys: Vec<LegacyNote>,
+    #[serde(default)]
+    right_keys: Vec<LegacyNote>,
+    end: Option<f64>,
+    sync: Option<f64>,
+    bpm: Option<LegacyBpm>,
+    // an old chart can have the metadata added by hand before it was upgraded
+    #[serde(default)]
+    metadata: SongMetadata,
+    #[serde(default)]
+    scroll_velocity: Vec<ScrollVelocity>,
+    #[serde(default)]
+    stops: Vec<Stop>,
+    #[serde(default)]
+    time_signatures: Vec<TimeSignature>,
+    #[serde(default)]
+    events: Vec<ChartEvent>,
+    #[serde(default)]
+    sections: Vec<Section>,
+}
+
+impl LegacySong {
+    fn upgrade(self) -> Song {
+        let scale = if self.format_version.unwrap_or(0) < 2 { LEGACY_TICK_MS } else { 1.0 };
+        let notes = |list: Vec<LegacyNote>| -> Vec<Note> {
+            list.iter().map(|note| Note { time: note.time * scale, holding: note.holding * scale, kind: note.kind }).collect()
+        };
+
+        // charts saved without an end just stop a second after the last note
+        let end = match self.end {
+            Some(end) => end,
+            None => [&self.left_keys, &self.up_keys, &self.bottom_keys, &self.right_keys].iter()
+                .flat_map(|list| list.iter())
+                .map(|note| note.time + note.holding + 1000.0 / scale)
+                .fold(0.0, f64::max),
+        };
+
+        // until version 3 the bpm was a whole number and there were no stops or time signatures, the old numbers still fit in a f64
+        let bpm = match self.bpm {
+            Some(LegacyBpm::Single(bpm)) => Some(vec![Bpm { bpm, starting_at: 0.0 }]),
+            Some(LegacyBpm::List(list)) => Some(list.iter().map(|change| Bpm { bpm: change.bpm, starting_at: change.starting_at * scale }).collect()),
+            None => None,
+        };
+
+        Song {
+            format_version: CURRENT_FORMAT_VERSION,
+            name: self.name.unwrap_or_default(),
+            id: self.id,
+            // until version 4 every chart was 4K, with a list for each lane. the lanes go in the order they are on the screen
+            lanes: vec![notes(self.left_keys), notes(self.up_keys), notes(self.bottom_keys), notes(self.right_keys)],
+            end: end * scale,
+            sync: Some(self.sync.unwrap_or(0.0) * scale),
+            bpm,
+            stops: self.stops.into_iter().map(|stop| Stop { starting_at: stop.starting_at * scale, duration: stop.duration * scale, ..stop }).collect(),
+            time_signatures: self.time_signatures.into_iter().map(|signature| TimeSignature { starting_at: signature.starting_at * scale, ..signature }).collect(),
+            scroll_velocity: self.scroll_velocity.into_iter()
+                .map(|change| ScrollVelocity { starting_at: change.starting_at * scale, ease_ms: change.ease_ms * scale, ..change })
+                .collect(),
+            events: self.events.into_iter().map(|mut event| {
+                event.starting_at *= scale;
+                if let EventAction::Flash { duration, .. } | EventAction::Dim { duration, .. } | EventAction::LaneFade { duration, .. } = &mut event.action {
+                    *duration *= scale;
+                }
+                event
+            }).collect(),
+            sections: self.sections.into_iter().map(|section| Section { starting_at: section.starting_at * scale, ..section }).collect(),
+            metadata: self.metadata,
+        }
+    }
+}
+
+// reads the contents of a data.json in any of the known shapes and returns it as the current Song
+// the bool says if the chart had to be upgraded, so the caller knows if the file on disk is outdated.
+// a chart of a newer version is an error, reading it as an old one would lose what this version doesn't know
+pub fn migrate(contents: &str) -> Result<(Song, bool), ChartError> {
+    let version: ChartVersion = serde_json::from_str(contents)?;
+
+    match version.format_version {
+        Some(CURRENT_FORMAT_VERSION) => return Ok((serde_json::from_str(contents)?, false)),
+        Some(newer) if newer > CURRENT_FORMAT_VERSION => {
+            return Err(ChartError::invalid("format_version", &format!("the chart was made with a newer version of the game ({})", newer)));
+        },
+        _ => {},
+    }
+
+    let legacy: LegacySong = serde_json::from_str(contents)?;
+    Ok((legacy.upgrade(), true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_scalar_bpm_becomes_a_list() {
+        let (song, migrated) = migrate(r#"{"name": "old", "bpm": 200, "left_keys": [{"time": 100, "holding": 0}]}"#).unwrap();
+
+        assert!(migrated);
+        let bpm = song.bpm.unwrap();
+        assert_eq!(bpm.len(), 1);
+        assert_eq!((bpm[0].bpm, bpm[0].starting_at), (200.0, 0.0));
+    }
+
+    #[test]
+    fn missing_id_sync_and_end_get_a_value() {
+        let (song, _) = migrate(r#"{"format_version": 3, "right_keys": [{"time": 4000, "holding": 500}]}"#).unwrap();
+
+        assert_eq!(song.id, None);
+        assert_eq!(song.sync, Some(0.0));
+        // a second after the end of the last hold
+        assert_eq!(song.end, 5500.0);
+        assert_eq!(song.lanes.len(), 4);
+        assert_eq!(song.lanes[3][0].time, 4000.0);
+    }
+
+    #[test]
+    fn ticks_are_scaled_below_version_2() {
+        let contents = r#"{"format_version": 1, "end": 500, "sync": 3, "bpm": [{"bpm": 120, "starting_at": 20}],
+            "up_keys": [{"time": 100, "holding": 50}], "stops": [{"starting_at": 30, "duration": 10}]}"#;
+        let (song, _) = migrate(contents).unwrap();
+
+        assert_eq!(song.end, 5000.0);
+        assert_eq!(song.sync, Some(30.0));
+        assert_eq!(song.bpm.unwrap()[0].starting_at, 200.0);
+        assert_eq!((song.lanes[1][0].time, song.lanes[1][0].holding), (1000.0, 500.0));
+        assert_eq!((song.stops[0].starting_at, song.stops[0].duration), (300.0, 100.0));
+
+        let (song, _) = migrate(r#"{"format_version": 2, "end": 500, "up_keys": [{"time": 100, "holding": 50}]}"#).unwrap();
+        assert_eq!(song.end, 500.0);
+        assert_eq!(song.lanes[1][0].time, 100.0);
+    }
+
+    #[test]
+    fn a_current_chart_is_not_migrated() {
+        let contents = serde_json::to_string(&Song::default()).unwrap();
+        let (_, migrated) = migrate(&contents).unwrap();
+
+        assert!(!migrated);
+    }
+
+    #[test]
+    fn a_newer_version_is_refused() {
+        let contents = format!(r#"{{"format_version": {}, "lanes": [[]], "end": 0}}"#, CURRENT_FORMAT_VERSION + 1);
+
+        assert!(migrate(&contents).is_err());
+    }
+}
@@ -0,0 +1,222 @@
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{input::keybutton::{Note, NoteKind}, load_song::Song};
+
+// notes closer than this are at the same time
+const SAME_TIME_MS: f64 = 1.0;
+
+// changes made to the chart before it is played, the chart file is never touched.
+// they are saved with the score, so a score made with an easier chart can be told apart
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Modifiers {
+    // the lanes from right to left
+    #[serde(default)]
+    pub mirror: bool,
+    // the lanes are mixed once for the whole song
+    #[serde(default)]
+    pub random: bool,
+    // every note goes to a lane of its own, without landing on a hold or on another note
+    #[serde(default)]
+    pub shuffle: bool,
+    // the holds and rolls are played as taps
+    #[serde(default)]
+    pub no_holds: bool,
+    // the chart is folded into this many lanes, it does nothing if the chart already has that many or less
+    #[serde(default)]
+    pub lanes: Option<usize>,
+}
+
+impl Modifiers {
+    pub fn is_empty(&self) -> bool {
+        *self == Modifiers::default()
+    }
+
+    // random and shuffle give a different chart every time, the seed is kept so the same one can be made again
+    pub fn uses_seed(&self) -> bool {
+        self.random || self.shuffle
+    }
+
+    // the names of the modifiers that are on, like "mirror, no holds, 4K"
+    pub fn label(&self) -> String {
+        let mut names = vec![];
+        if self.mirror {
+            names.push("mirror".to_owned());
+        }
+        if self.random {
+            names.push("random".to_owned());
+        }
+        if self.shuffle {
+            names.push("shuffle".to_owned());
+        }
+        if self.no_holds {
+            names.push("no holds".to_owned());
+        }
+        if let Some(lanes) = self.lanes {
+            names.push(format!("{}K", lanes));
+        }
+        if names.is_empty() {
+            return "none".to_owned();
+        }
+        names.join(", ")
+    }
+
+    // the next lane count for the lane reduction, it goes down from the lanes of the chart and then back to off
+    pub fn next_lanes(&self, chart_lanes: usize) -> Option<usize> {
+        match self.lanes {
+            None if chart_lanes > 1 => Some(chart_lanes - 1),
+            Some(lanes) if lanes > 1 && lanes <= chart_lanes => Some(lanes - 1),
+            _ => None,
+        }
+    }
+
+    // the chart the player gets, in the order: no holds, fewer lanes, random, shuffle, mirror
+    pub fn apply(&self, song: &Song, seed: u64) -> Song {
+        let mut song = song.clone();
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        if self.no_holds {
+            for note in song.lanes.iter_mut().flatten() {
+                note.holding = 0.0;
+                if note.kind == NoteKind::Roll {
+                    note.kind = NoteKind::Normal;
+                }
+            }
+        }
+        if let Some(lanes) = self.lanes.filter(|lanes| *lanes >= 1 && *lanes < song.lane_count()) {
+            song.lanes = reduce_lanes(&song.lanes, lanes);
+        }
+        if self.random {
+            let mut order: Vec<usize> = (0..song.lane_count()).collect();
+            order.shuffle(&mut rng);
+            song.lanes = order.iter().map(|lane| song.lanes[*lane].clone()).collect();
+        }
+        if self.shuffle {
+            song.lanes = shuffle_notes(&song.lanes, &mut rng);
+        }
+        if self.mirror {
+            song.lanes.reverse();
+        }
+        song
+    }
+}
+
+// every lane goes to the lane of the same part of the keyboard (on 7K to 4K: 0 1 -> 0, 2 3 -> 1 ...),
+// the notes that end up on top of another one or inside a hold are removed
+fn reduce_lanes(lanes: &[Vec<Note>], count: usize) -> Vec<Vec<Note>> {
+    let mut reduced: Vec<Vec<Note>> = vec![vec![]; count];
+    for (lane, notes) in lanes.iter().enumerate() {
+        reduced[lane * count / lanes.len()].extend(notes.iter().copied());
+    }
+
+    reduced.into_iter().map(|mut notes| {
+        // on the same time the longer hold stays
+        notes.sort_by(|a, b| a.time.total_cmp(&b.time).then(b.holding.total_cmp(&a.holding)));
+        let mut busy_until = f64::NEG_INFINITY;
+        notes.retain(|note| {
+            if note.time < busy_until + SAME_TIME_MS {
+                return false;
+            }
+            busy_until = note.time + note.holding.max(0.0);
+            true
+        });
+        notes
+    }).collect()
+}
+
+// the notes are moved one by one in time order to a random lane that is free at that moment,
+// the amount of notes and holds at every moment doesn't change so a free lane is always found on a chart without errors
+fn shuffle_notes(lanes: &[Vec<Note>], rng: &mut StdRng) -> Vec<Vec<Note>> {
+    let mut notes: Vec<Note> = lanes.iter().flatten().copied().collect();
+    notes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let mut shuffled: Vec<Vec<Note>> = vec![vec![]; lanes.len()];
+    let mut busy_until = vec![f64::NEG_INFINITY; lanes.len()];
+    for note in notes {
+        let free: Vec<usize> = (0..lanes.len()).filter(|lane| note.time >= busy_until[*lane] + SAME_TIME_MS).collect();
+        // only a broken chart has no free lane, that note can't be played anyway
+        if let Some(lane) = free.choose(rng) {
+            busy_until[*lane] = note.time + note.holding.max(0.0);
+            shuffled[*lane].push(note);
+        }
+    }
+    shuffled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(time: f64, holding: f64) -> Note {
+        Note::new(time, holding)
+    }
+
+    // a 4K chart with holds that cover other lanes and chords on them
+    fn song() -> Song {
+        Song {
+            lanes: vec![
+                vec![note(0.0, 2000.0), note(3000.0, 0.0)],
+                vec![note(0.0, 0.0), note(500.0, 0.0), note(1000.0, 1000.0)],
+                vec![note(500.0, 0.0), note(1500.0, 0.0), note(2500.0, 0.0)],
+                vec![note(1000.0, 0.0), note(1500.0, 0.0), note(3000.0, 0.0)],
+            ],
+            end: 5000.0,
+            ..Song::default()
+        }
+    }
+
+    fn note_count(song: &Song) -> usize {
+        song.lanes.iter().map(|lane| lane.len()).sum()
+    }
+
+    #[test]
+    fn shuffle_never_puts_a_note_inside_a_hold() {
+        let shuffle = Modifiers { shuffle: true, ..Modifiers::default() };
+        for seed in 0..50 {
+            let shuffled = shuffle.apply(&song(), seed);
+            assert_eq!(note_count(&shuffled), note_count(&song()));
+
+            for lane in &shuffled.lanes {
+                for (i, note) in lane.iter().enumerate().skip(1) {
+                    let before = &lane[i - 1];
+                    assert!(note.time >= before.time + before.holding + SAME_TIME_MS, "seed {} put a note at {} inside a hold", seed, note.time);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_chart() {
+        let modifiers = Modifiers { random: true, shuffle: true, ..Modifiers::default() };
+        let times = |song: &Song| -> Vec<Vec<f64>> { song.lanes.iter().map(|lane| lane.iter().map(|note| note.time).collect()).collect() };
+
+        assert_eq!(times(&modifiers.apply(&song(), 7)), times(&modifiers.apply(&song(), 7)));
+    }
+
+    #[test]
+    fn mirror_reverses_the_lanes() {
+        let mirrored = Modifiers { mirror: true, ..Modifiers::default() }.apply(&song(), 0);
+
+        assert_eq!(mirrored.lanes[0].len(), 3);
+        assert_eq!(mirrored.lanes[3][0].holding, 2000.0);
+    }
+
+    #[test]
+    fn no_holds_turns_holds_and_rolls_into_taps() {
+        let mut rolls = song();
+        rolls.lanes[1][2].kind = NoteKind::Roll;
+        let tapped = Modifiers { no_holds: true, ..Modifiers::default() }.apply(&rolls, 0);
+
+        assert!(tapped.lanes.iter().flatten().all(|note| note.holding == 0.0 && note.kind == NoteKind::Normal));
+        assert_eq!(note_count(&tapped), note_count(&song()));
+    }
+
+    #[test]
+    fn fewer_lanes_drop_what_lands_in_a_hold() {
+        let reduced = Modifiers { lanes: Some(2), ..Modifiers::default() }.apply(&song(), 0);
+
+        assert_eq!(reduced.lane_count(), 2);
+        // lanes 0 and 1 only keep the hold and the note after it, the taps of lane 1 are inside the hold
+        assert_eq!(reduced.lanes[0].iter().map(|note| note.time).collect::<Vec<f64>>(), vec![0.0, 3000.0]);
+    }
+}
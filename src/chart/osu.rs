@@ -0,0 +1,317 @@
+use std::{fs, path::Path};
+
+use crate::{chart::{error::ChartError, scroll::ScrollVelocity, song_folder}, input::keybutton::Note, load_song::{Bpm, Song, SongMetadata, DEFAULT_CHART, LEAD_IN_MS, MAX_LANES}};
+
+// osu!mania places the columns along the 512 pixels of the osu! playfield
+const PLAYFIELD_WIDTH: f64 = 512.0;
+const MANIA_MODE: u32 = 3;
+const HOLD_TYPE: u32 = 128;
+
+pub struct OsuHitObject {
+    pub column: usize,
+    pub time: f64,
+    pub end_time: Option<f64>,
+}
+
+pub struct OsuTimingPoint {
+    pub time: f64,
+    pub beat_length: f64,
+    pub uninherited: bool,
+}
+
+pub struct OsuBeatmap {
+    pub title: String,
+    pub title_unicode: String,
+    pub artist: String,
+    pub creator: String,
+    pub source: String,
+    pub tags: Vec<String>,
+    pub preview_time: Option<f64>,
+    pub version: String,
+    pub audio_filename: String,
+    pub background: Option<String>,
+    pub mode: u32,
+    pub key_count: usize,
+    pub timing_points: Vec<OsuTimingPoint>,
+    pub hit_objects: Vec<OsuHitObject>,
+}
+
+impl OsuBeatmap {
+    pub fn parse(contents: &str) -> Result<OsuBeatmap, ChartError> {
+        let mut beatmap = OsuBeatmap {
+            title: "".to_owned(),
+            title_unicode: "".to_owned(),
+            artist: "".to_owned(),
+            creator: "".to_owned(),
+            source: "".to_owned(),
+            tags: vec![],
+            preview_time: None,
+            version: "".to_owned(),
+            audio_filename: "".to_owned(),
+            background: None,
+            mode: 0,
+            key_count: 0,
+            timing_points: vec![],
+            hit_objects: vec![],
+        };
+        let mut section = "";
+
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                section = &line[1..line.len() - 1];
+                continue;
+            }
+
+            let syntax_error = |message: &str| ChartError::Syntax { line: i + 1, column: 1, message: message.to_owned() };
+
+            match section {
+                "General" | "Metadata" | "Difficulty" => {
+                    let Some((key, value)) = line.split_once(':') else { continue };
+                    let value = value.trim();
+                    match key.trim() {
+                        "AudioFilename" => beatmap.audio_filename = value.to_owned(),
+                        "Mode" => beatmap.mode = value.parse().map_err(|_| syntax_error("Mode is not a number"))?,
+                        "Title" => beatmap.title = value.to_owned(),
+                        "TitleUnicode" => beatmap.title_unicode = value.to_owned(),
+                        "Artist" => beatmap.artist = value.to_owned(),
+                        "Creator" => beatmap.creator = value.to_owned(),
+                        "Source" => beatmap.source = value.to_owned(),
+                        "Tags" => beatmap.tags = value.split_whitespace().map(|tag| tag.to_owned()).collect(),
+                        // -1 means the beatmap doesn't have a preview point
+                        "PreviewTime" => beatmap.preview_time = value.parse::<f64>().ok().filter(|time| *time >= 0.0),
+                        "Version" => beatmap.version = value.to_owned(),
+                        // on mania the circle size is the amount of keys
+                        "CircleSize" => beatmap.key_count = value.parse::<f64>().map_err(|_| syntax_error("CircleSize is not a number"))? as usize,
+                        _ => {},
+                    }
+                },
+                "Events" => {
+                    // the background is saved as: 0,0,"file.jpg",0,0
+                    let values: Vec<&str> = line.split(',').collect();
+                    if values.len() >= 3 && (values[0] == "0" || values[0] == "Background") && beatmap.background.is_none() {
+                        beatmap.background = Some(values[2].trim_matches('"').to_owned());
+                    }
+                },
+                "TimingPoints" => {
+                    let values: Vec<&str> = line.split(',').collect();
+                    if values.len() < 2 {
+                        return Err(syntax_error("a timing point needs at least a time and a beat length"));
+                    }
+                    let time: f64 = values[0].trim().parse().map_err(|_| syntax_error("the timing point time is not a number"))?;
+                    let beat_length: f64 = values[1].trim().parse().map_err(|_| syntax_error("the beat length is not a number"))?;
+                    // old beatmaps don't have the uninherited value, there the inherited points have a negative beat length
+                    let uninherited = match values.get(6) {
+                        Some(value) => value.trim() == "1",
+                        None => beat_length > 0.0,
+                    };
+                    beatmap.timing_points.push(OsuTimingPoint { time, beat_length, uninherited });
+                },
+                "HitObjects" => {
+                    let values: Vec<&str> = line.split(',').collect();
+                    if values.len() < 5 {
+                        return Err(syntax_error("a hit object needs at least x, y, time, type and hit sound"));
+                    }
+                    let x: f64 = values[0].trim().parse().map_err(|_| syntax_error("the hit object x is not a number"))?;
+                    let time: f64 = values[2].trim().parse().map_err(|_| syntax_error("the hit object time is not a number"))?;
+                    let object_type: u32 = values[3].trim().parse().map_err(|_| syntax_error("the hit object type is not a number"))?;
+
+                    let mut end_time = None;
+                    if object_type & HOLD_TYPE != 0 {
+                        // holds save the end as the first value of the last field: endTime:hitSample
+                        let end = values.get(5).and_then(|params| params.split(':').next()).ok_or_else(|| syntax_error("the hold doesn't have an end time"))?;
+                        end_time = Some(end.trim().parse().map_err(|_| syntax_error("the hold end time is not a number"))?);
+                    }
+
+                    let key_count = beatmap.key_count.max(1);
+                    let column = ((x * key_count as f64 / PLAYFIELD_WIDTH).floor().max(0.0) as usize).min(key_count - 1);
+                    beatmap.hit_objects.push(OsuHitObject { column, time, end_time });
+                },
+                _ => {},
+            }
+        }
+
+        Ok(beatmap)
+    }
+
+    pub fn to_song(&self) -> Result<Song, ChartError> {
+        if self.mode != MANIA_MODE {
+            return Err(ChartError::invalid("Mode", "the beatmap is not an osu!mania beatmap"));
+        }
+        if self.key_count == 0 || self.key_count > MAX_LANES {
+            return Err(ChartError::invalid("CircleSize", &format!("the beatmap has {} keys, only 1 to {} keys are supported", self.key_count, MAX_LANES)));
+        }
+
+        let mut lanes: Vec<Vec<Note>> = vec![vec![]; self.key_count];
+        let mut end: f64 = 0.0;
+
+        // the osu! times start with the audio, here the audio starts after the lead in
+        for object in &self.hit_objects {
+            let holding = object.end_time.map_or(0.0, |end_time| (end_time - object.time).max(0.0));
+            lanes[object.column].push(Note::new(object.time + LEAD_IN_MS, holding));
+            end = end.max(object.time + holding + LEAD_IN_MS);
+        }
+
+        for lane in lanes.iter_mut() {
+            lane.sort_by(|a, b| a.time.total_cmp(&b.time));
+        }
+
+        let bpm: Vec<Bpm> = self.timing_points.iter()
+            .filter(|point| point.uninherited && point.beat_length > 0.0)
+            .map(|point| Bpm { bpm: 60000.0 / point.beat_length, starting_at: point.time.max(0.0) + LEAD_IN_MS })
+            .collect();
+
+        // the inherited timing points change the scroll speed (a beat length of -50 is 2x), every new bpm goes back to 1x
+        let scroll_velocity: Vec<ScrollVelocity> = if self.timing_points.iter().any(|point| !point.uninherited && point.beat_length < 0.0) {
+            self.timing_points.iter()
+                .filter(|point| point.uninherited || point.beat_length < 0.0)
+                .map(|point| ScrollVelocity {
+                    starting_at: point.time.max(0.0) + LEAD_IN_MS,
+                    // osu! itself keeps the speed between 0.1x and 10x
+                    multiplier: if point.uninherited { 1.0 } else { (-100.0 / point.beat_length).clamp(0.1, 10.0) },
+                    ease_ms: 0.0,
+                })
+                .collect()
+        } else {
+            vec![]
+        };
+
+        let name = if self.version.is_empty() { self.title.clone() } else { format!("{} [{}]", self.title, self.version) };
+
+        Ok(Song {
+            name,
+            lanes,
+            end: end + 1000.0,
+            bpm: if bpm.is_empty() { None } else { Some(bpm) },
+            scroll_velocity,
+            metadata: SongMetadata {
+                // osu! saves the title in latin letters and the original one apart
+                title: if self.title_unicode.is_empty() { self.title.clone() } else { self.title_unicode.clone() },
+                title_translit: if self.title_unicode.is_empty() { "".to_owned() } else { self.title.clone() },
+                artist: self.artist.clone(),
+                charter: self.creator.clone(),
+                source: self.source.clone(),
+                tags: self.tags.clone(),
+                preview_start: self.preview_time,
+                difficulty: if self.version.is_empty() { None } else { Some(self.version.clone()) },
+                ..SongMetadata::default()
+            },
+            ..Song::default()
+        })
+    }
+}
+
+// reads a .osu file and writes it inside songs as a new folder with its audio and cover, returns the folder name
+pub fn import(osu_file: &Path) -> Result<String, ChartError> {
+    let path = osu_file.to_string_lossy().to_string();
+    let contents = fs::read_to_string(osu_file).map_err(|err| ChartError::from_io(&path, err))?;
+    let beatmap = OsuBeatmap::parse(&contents)?;
+    let mut song = beatmap.to_song()?;
+
+    let beatmap_folder = osu_file.parent().unwrap_or(Path::new("."));
+    let folder_name = if beatmap.artist.is_empty() { song.name.clone() } else { format!("{} - {}", beatmap.artist, song.name) };
+    let (folder, folder_path) = song_folder::create_song_folder(&folder_name).map_err(|err| ChartError::from_io(&folder_name, err))?;
+
+    if let Err(err) = fill_folder(&beatmap, beatmap_folder, &mut song, &folder_path) {
+        // half imported songs would show up broken on the song list
+        let _ = fs::remove_dir_all(&folder_path);
+        return Err(err);
+    }
+    Ok(folder)
+}
+
+// copies the audio and the cover of the beatmap to the new song folder and writes the chart
+fn fill_folder(beatmap: &OsuBeatmap, beatmap_folder: &Path, song: &mut Song, folder_path: &Path) -> Result<(), ChartError> {
+    if !beatmap.audio_filename.is_empty() {
+        let audio = beatmap_folder.join(&beatmap.audio_filename);
+        let copied = song_folder::copy_asset(&audio, folder_path, "audio").map_err(|err| ChartError::from_io(&audio.to_string_lossy(), err))?;
+        song.metadata.audio_file = song_folder::file_name(&copied);
+    }
+
+    if let Some(background) = &beatmap.background {
+        let cover = beatmap_folder.join(background);
+        // a missing background is not a reason to stop the import, the song just won't have a cover
+        match song_folder::copy_asset(&cover, folder_path, "cover") {
+            Ok(copied) => song.metadata.cover_file = song_folder::file_name(&copied),
+            Err(err) => eprintln!("The background {} wasn't copied: {}", cover.to_string_lossy(), err),
+        }
+    }
+
+    song_folder::write_chart(folder_path, DEFAULT_CHART, song).map_err(|err| ChartError::from_io(&folder_path.to_string_lossy(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEATMAP: &str = "osu file format v14
+
+[General]
+AudioFilename: audio.mp3
+PreviewTime: -1
+Mode: 3
+
+[Metadata]
+Title:Song
+TitleUnicode:Canción
+Artist:Someone
+Creator:Mapper
+Version:Hard
+Tags:one two
+
+[Difficulty]
+CircleSize:4
+
+[Events]
+0,0,\"bg.jpg\",0,0
+
+[TimingPoints]
+1000,500,4,2,0,100,1,0
+3000,-50,4,2,0,100,0,0
+
+[HitObjects]
+64,192,1000,1,0,0:0:0:0:
+448,192,1500,128,0,2500:0:0:0:0:
+";
+
+    #[test]
+    fn parses_the_hit_objects_and_timing() {
+        let beatmap = OsuBeatmap::parse(BEATMAP).unwrap();
+
+        assert_eq!(beatmap.key_count, 4);
+        assert_eq!(beatmap.preview_time, None);
+        assert_eq!(beatmap.background.as_deref(), Some("bg.jpg"));
+        assert_eq!(beatmap.hit_objects.len(), 2);
+        assert_eq!((beatmap.hit_objects[0].column, beatmap.hit_objects[0].end_time), (0, None));
+        assert_eq!((beatmap.hit_objects[1].column, beatmap.hit_objects[1].end_time), (3, Some(2500.0)));
+        assert!(beatmap.timing_points[0].uninherited);
+        assert!(!beatmap.timing_points[1].uninherited);
+    }
+
+    #[test]
+    fn converts_to_a_song_after_the_lead_in() {
+        let song = OsuBeatmap::parse(BEATMAP).unwrap().to_song().unwrap();
+
+        assert_eq!(song.name, "Song [Hard]");
+        assert_eq!(song.metadata.title, "Canción");
+        assert_eq!(song.metadata.title_translit, "Song");
+        assert_eq!(song.lanes.len(), 4);
+        assert_eq!(song.lanes[0][0].time, 1000.0 + LEAD_IN_MS);
+        assert_eq!((song.lanes[3][0].time, song.lanes[3][0].holding), (1500.0 + LEAD_IN_MS, 1000.0));
+        assert_eq!(song.bpm.as_ref().unwrap()[0].bpm, 120.0);
+        // the inherited point of -50 is 2x
+        assert_eq!(song.scroll_velocity.len(), 2);
+        assert_eq!(song.scroll_velocity[1].multiplier, 2.0);
+    }
+
+    #[test]
+    fn only_mania_beatmaps_are_converted() {
+        let standard = BEATMAP.replace("Mode: 3", "Mode: 0");
+        assert!(OsuBeatmap::parse(&standard).unwrap().to_song().is_err());
+        assert!(OsuBeatmap::parse("[HitObjects]\n64,192,1000").is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::{fs::{self, File}, io::{Read, Write}, path::{Path, PathBuf}};
+
+use serde::{Deserialize, Serialize};
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{chart::{error::ChartError, migration::CURRENT_FORMAT_VERSION, song_folder}, load_song::{Song, CHARTS_FOLDER, DEFAULT_CHART}};
+
+pub const PACKAGE_EXTENSION: &str = "arrow";
+pub const EXPORTS_FOLDER: &str = "exports";
+const MANIFEST_FILE: &str = "manifest.json";
+// the version of the package itself, the chart inside has its own format_version
+const PACKAGE_VERSION: u64 = 1;
+
+// saved as manifest.json inside every package, so a zip that is not a song can be told apart
+#[derive(Serialize, Deserialize)]
+pub struct PackageManifest {
+    pub package_version: u64,
+    pub chart_format_version: u64,
+    pub name: String,
+    pub files: Vec<String>,
+}
+
+// packs every file of a song folder (chart, audio, cover...) in a single .arrow file inside exports
+pub fn export(folder: &str) -> Result<PathBuf, ChartError> {
+    // a broken chart is not exported, the error is the same the game would show when playing it
+    let charts = Song::charts(folder);
+    if charts.is_empty() {
+        return Err(ChartError::invalid("charts", "the song doesn't have any chart"));
+    }
+    let mut chart_format_version = 0;
+    for chart in &charts {
+        chart_format_version = chart_format_version.max(Song::new(folder, chart)?.format_version);
+    }
+    let folder_path = Path::new(song_folder::SONGS_FOLDER).join(folder);
+
+    let mut files = vec![];
+    collect_files(&folder_path, &folder_path, &mut files).map_err(|err| ChartError::from_io(&folder_path.to_string_lossy(), err))?;
+
+    fs::create_dir_all(EXPORTS_FOLDER).map_err(|err| ChartError::from_io(EXPORTS_FOLDER, err))?;
+    let package_path = Path::new(EXPORTS_FOLDER).join(format!("{}.{}", song_folder::sanitize_name(folder), PACKAGE_EXTENSION));
+    // the package is written next to it first, so a failed export never leaves a broken .arrow or replaces a good one
+    let temp_path = package_path.with_extension(format!("{}.tmp", PACKAGE_EXTENSION));
+    let manifest = PackageManifest { package_version: PACKAGE_VERSION, chart_format_version, name: folder.to_owned(), files };
+    if let Err(err) = write_package(&temp_path, &manifest, &folder_path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+    fs::rename(&temp_path, &package_path).map_err(|err| ChartError::from_io(&package_path.to_string_lossy(), err))?;
+
+    Ok(package_path)
+}
+
+fn write_package(path: &Path, manifest: &PackageManifest, folder_path: &Path) -> Result<(), ChartError> {
+    let package_name = path.to_string_lossy().to_string();
+    let io_error = |err: std::io::Error| ChartError::from_io(&package_name, err);
+
+    let mut writer = ZipWriter::new(File::create(path).map_err(io_error)?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    writer.start_file(MANIFEST_FILE, options).map_err(zip_error)?;
+    writer.write_all(serde_json::to_string_pretty(manifest)?.as_bytes()).map_err(io_error)?;
+
+    for file in &manifest.files {
+        writer.start_file(file.as_str(), options).map_err(zip_error)?;
+        writer.write_all(&fs::read(folder_path.join(file)).map_err(io_error)?).map_err(io_error)?;
+    }
+    writer.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+// unpacks a .arrow file as a new folder inside songs, if the name is used the folder gets a number at the end
+pub fn import(package: &Path) -> Result<String, ChartError> {
+    import_into(package, Path::new(song_folder::SONGS_FOLDER))
+}
+
+fn import_into(package: &Path, songs: &Path) -> Result<String, ChartError> {
+    let package_name = package.to_string_lossy().to_string();
+    let file = File::open(package).map_err(|err| ChartError::from_io(&package_name, err))?;
+    let mut archive = ZipArchive::new(file).map_err(zip_error)?;
+
+    let manifest: PackageManifest = {
+        let mut contents = String::new();
+        let mut entry = archive.by_name(MANIFEST_FILE).map_err(|_| ChartError::invalid(MANIFEST_FILE, "the package doesn't have a manifest, it is not an .arrow file"))?;
+        entry.read_to_string(&mut contents).map_err(|err| ChartError::from_io(&package_name, err))?;
+        serde_json::from_str(&contents)?
+    };
+
+    if manifest.package_version > PACKAGE_VERSION {
+        return Err(ChartError::invalid("package_version", &format!("the package was made with a newer version of the game ({})", manifest.package_version)));
+    }
+    if manifest.chart_format_version > CURRENT_FORMAT_VERSION {
+        return Err(ChartError::invalid("chart_format_version", &format!("the chart was made with a newer version of the game ({})", manifest.chart_format_version)));
+    }
+
+    // the charts are checked before anything is written (the same way the game opens them), older charts are fine because they are upgraded when opened
+    let charts: Vec<String> = archive.file_names()
+        .filter(|name| *name == DEFAULT_CHART || (name.starts_with(&(CHARTS_FOLDER.to_owned() + "/")) && name.to_lowercase().ends_with(".json")))
+        .map(|name| name.to_owned())
+        .collect();
+    if charts.is_empty() {
+        return Err(ChartError::invalid("charts", "the package doesn't have a chart"));
+    }
+    for chart in &charts {
+        let mut contents = String::new();
+        let mut entry = archive.by_name(chart).map_err(zip_error)?;
+        entry.read_to_string(&mut contents).map_err(|err| ChartError::from_io(&package_name, err))?;
+        Song::from_json(&contents)?;
+    }
+
+    // every path is checked first, a file like ../../something can't be written outside the song folder
+    let mut entries = vec![];
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i).map_err(zip_error)?;
+        if entry.is_dir() || entry.name() == MANIFEST_FILE {
+            continue;
+        }
+        match entry.enclosed_name() {
+            Some(path) => entries.push((i, path.to_path_buf())),
+            None => return Err(ChartError::invalid("package", &format!("the file {} is outside of the song folder", entry.name()))),
+        }
+    }
+
+    let (folder, folder_path) = song_folder::create_folder_in(songs, &manifest.name).map_err(|err| ChartError::from_io(&manifest.name, err))?;
+    if let Err(err) = unpack(&mut archive, &entries, &folder_path) {
+        // half unpacked songs would show up broken on the song list
+        let _ = fs::remove_dir_all(&folder_path);
+        return Err(err);
+    }
+
+    Ok(folder)
+}
+
+// unpacks every .arrow file found inside songs and removes the ones that were imported, returns the messages to show
+pub fn import_dropped_packages() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(song_folder::SONGS_FOLDER) else { return vec![] };
+    let mut messages = vec![];
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|extension| extension.to_string_lossy().to_lowercase() != PACKAGE_EXTENSION) {
+            continue;
+        }
+
+        match import(&path) {
+            Ok(folder) => {
+                let _ = fs::remove_file(&path);
+                messages.push(format!("{} was imported as {}", path.file_name().unwrap_or_default().to_string_lossy(), folder));
+            },
+            Err(err) => messages.push(format!("{} couldn't be imported: {}", path.file_name().unwrap_or_default().to_string_lossy(), err)),
+        }
+    }
+    messages
+}
+
+fn unpack(archive: &mut ZipArchive<File>, entries: &[(usize, PathBuf)], folder_path: &Path) -> Result<(), ChartError> {
+    for (index, path) in entries {
+        let destination = folder_path.join(path);
+        let destination_name = destination.to_string_lossy().to_string();
+        let io_error = |err: std::io::Error| ChartError::from_io(&destination_name, err);
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        let mut entry = archive.by_index(*index).map_err(zip_error)?;
+        let mut output = File::create(&destination).map_err(io_error)?;
+        std::io::copy(&mut entry, &mut output).map_err(io_error)?;
+    }
+    Ok(())
+}
+
+// the paths inside the package always use / so they are the same on every system
+fn collect_files(root: &Path, folder: &Path, files: &mut Vec<String>) -> std::io::Result<()> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            let parts: Vec<String> = relative.components().map(|part| part.as_os_str().to_string_lossy().to_string()).collect();
+            files.push(parts.join("/"));
+        }
+    }
+    files.sort();
+    Ok(())
+}
+
+fn zip_error(err: zip::result::ZipError) -> ChartError {
+    match err {
+        zip::result::ZipError::Io(err) => ChartError::Io { path: "".to_owned(), source: err },
+        other => ChartError::invalid("package", &other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a folder of its own inside the temp folder of the system, it is emptied first
+    fn test_folder(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("arrownier-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(path.join("songs")).unwrap();
+        path
+    }
+
+    fn write_zip(path: &Path, files: &[(&str, String)]) {
+        let mut writer = ZipWriter::new(File::create(path).unwrap());
+        for (name, contents) in files {
+            writer.start_file(*name, FileOptions::default()).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn manifest(name: &str) -> String {
+        let manifest = PackageManifest { package_version: PACKAGE_VERSION, chart_format_version: CURRENT_FORMAT_VERSION, name: name.to_owned(), files: vec![DEFAULT_CHART.to_owned()] };
+        serde_json::to_string(&manifest).unwrap()
+    }
+
+    fn chart() -> String {
+        serde_json::to_string(&Song::default()).unwrap()
+    }
+
+    #[test]
+    fn a_zip_without_manifest_is_not_imported() {
+        let folder = test_folder("no-manifest");
+        let package = folder.join("song.arrow");
+        write_zip(&package, &[(DEFAULT_CHART, chart())]);
+
+        assert!(import_into(&package, &folder.join("songs")).is_err());
+        assert_eq!(fs::read_dir(folder.join("songs")).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn files_outside_of_the_song_folder_are_refused() {
+        let folder = test_folder("zip-slip");
+        let package = folder.join("song.arrow");
+        write_zip(&package, &[(MANIFEST_FILE, manifest("Song")), (DEFAULT_CHART, chart()), ("../../outside.txt", "nope".to_owned())]);
+
+        assert!(import_into(&package, &folder.join("songs")).is_err());
+        // nothing is written, not even the folder of the song
+        assert_eq!(fs::read_dir(folder.join("songs")).unwrap().count(), 0);
+        assert!(!folder.join("outside.txt").exists());
+        let _ = fs::remove_dir_all(&folder);
+    }
+
+    #[test]
+    fn a_name_that_is_taken_gets_a_number() {
+        let folder = test_folder("taken-name");
+        let songs = folder.join("songs");
+        fs::create_dir_all(songs.join("Song")).unwrap();
+        let package = folder.join("song.arrow");
+        write_zip(&package, &[(MANIFEST_FILE, manifest("Song")), (DEFAULT_CHART, chart())]);
+
+        assert_eq!(import_into(&package, &songs).unwrap(), "Song (2)");
+        assert_eq!(import_into(&package, &songs).unwrap(), "Song (3)");
+        assert!(songs.join("Song (2)").join(DEFAULT_CHART).is_file());
+        // the folder that was already there is left alone
+        assert_eq!(fs::read_dir(songs.join("Song")).unwrap().count(), 0);
+        let _ = fs::remove_dir_all(&folder);
+    }
+}
@@ -0,0 +1,65 @@
+use std::{fs, io, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use super::modifiers::Modifiers;
+
+// every finished play is added here, the newest at the end
+const SCORES_FILE: &str = "scores.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Score {
+    // the id of the chart without the modifiers, the same chart on other folder or with other name has the same id
+    pub chart_id: String,
+    pub folder: String,
+    pub chart: String,
+    pub points: u64,
+    pub max_combo: u32,
+    #[serde(default, skip_serializing_if = "Modifiers::is_empty")]
+    pub modifiers: Modifiers,
+    // only for random and shuffle, with it the same notes can be made again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    // seconds since 1970
+    #[serde(default)]
+    pub date: u64,
+}
+
+impl Score {
+    pub fn new(chart_id: String, folder: &str, chart: &str, points: u128, max_combo: u32, modifiers: &Modifiers, seed: u64) -> Score {
+        Score {
+            chart_id,
+            folder: folder.to_owned(),
+            chart: chart.to_owned(),
+            points: points.min(u64::MAX as u128) as u64,
+            max_combo,
+            modifiers: modifiers.clone(),
+            seed: modifiers.uses_seed().then_some(seed),
+            date: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+        }
+    }
+}
+
+// no file is the same as no scores, a broken one is an error so saving never writes over it
+pub fn load() -> Result<Vec<Score>, String> {
+    match fs::read_to_string(SCORES_FILE) {
+        Ok(json_string) => serde_json::from_str(&json_string).map_err(|err| format!("{} is broken: {}", SCORES_FILE, err)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        Err(err) => Err(format!("{} couldn't be read: {}", SCORES_FILE, err)),
+    }
+}
+
+pub fn save(score: Score) -> Result<(), String> {
+    let mut scores = load()?;
+    scores.push(score);
+    let json_string = serde_json::to_string_pretty(&scores).map_err(|err| err.to_string())?;
+    fs::write(SCORES_FILE, json_string).map_err(|err| err.to_string())
+}
+
+// the best points of the chart, only counting the plays with the same modifiers
+pub fn best(scores: &[Score], chart_id: &str, modifiers: &Modifiers) -> Option<u64> {
+    scores.iter()
+        .filter(|score| score.chart_id == chart_id && score.modifiers == *modifiers)
+        .map(|score| score.points)
+        .max()
+}
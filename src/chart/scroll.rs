@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chart::error::ChartError;
+
+// from `starting_at` the notes move `multiplier` times as fast, the hit times don't change,
+// with `ease_ms` the speed goes from the one before to the new one during that amount of milliseconds
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScrollVelocity {
+    pub starting_at: f64,
+    pub multiplier: f64,
+    #[serde(default)]
+    pub ease_ms: f64,
+}
+
+// the scroll speed as a line between points, a change without ease is two points at the same time
+pub struct ScrollTimeline {
+    // (time, multiplier) sorted by time
+    points: Vec<(f64, f64)>,
+    // the position (in milliseconds at normal speed) of every point
+    positions: Vec<f64>,
+}
+
+impl ScrollTimeline {
+    pub fn new(changes: &[ScrollVelocity]) -> ScrollTimeline {
+        let mut changes = changes.to_vec();
+        changes.sort_by(|a, b| a.starting_at.total_cmp(&b.starting_at));
+
+        // before the first change everything moves at the normal speed
+        let mut points: Vec<(f64, f64)> = vec![];
+        for change in changes {
+            let (last_time, last_multiplier) = points.last().copied().unwrap_or((change.starting_at, 1.0));
+            // a change that starts while the one before is still easing starts where that one ends
+            let start = change.starting_at.max(last_time);
+            points.push((start, last_multiplier));
+            points.push((start + change.ease_ms.max(0.0), change.multiplier));
+        }
+
+        let mut positions = vec![];
+        for (i, (time, _)) in points.iter().enumerate() {
+            positions.push(if i == 0 { *time } else { positions[i - 1] + Self::area(points[i - 1], points[i], *time) });
+        }
+
+        ScrollTimeline { points, positions }
+    }
+
+    // where the chart is at the given time, two notes are as far from each other on the screen as their positions are
+    pub fn position(&self, time: f64) -> f64 {
+        // the last point at or before the time, with two points at the same time the second one is used
+        let index = self.points.partition_point(|(point_time, _)| *point_time <= time);
+        if index == 0 {
+            let first_time = self.points.first().map_or(0.0, |(first_time, _)| *first_time);
+            return self.positions.first().copied().unwrap_or(0.0) + (time - first_time);
+        }
+
+        let start = self.points[index - 1];
+        let position = self.positions[index - 1];
+        match self.points.get(index) {
+            Some(end) => position + Self::area(start, *end, time),
+            None => position + (time - start.0) * start.1,
+        }
+    }
+
+    // what the notes moved between the `start` point and `time` when the speed goes in a line up to the `end` point
+    fn area(start: (f64, f64), end: (f64, f64), time: f64) -> f64 {
+        let length = end.0 - start.0;
+        if length <= 0.0 {
+            return 0.0;
+        }
+        let multiplier = start.1 + (end.1 - start.1) * ((time - start.0) / length);
+        (time - start.0) * (start.1 + multiplier) / 2.0
+    }
+}
+
+// negative speeds would make the notes go back up and the hidden notes would never show up
+pub fn check(changes: &[ScrollVelocity]) -> Result<(), ChartError> {
+    for (i, change) in changes.iter().enumerate() {
+        if !change.multiplier.is_finite() || change.multiplier < 0.0 {
+            return Err(ChartError::invalid(&format!("scroll_velocity[{}].multiplier", i), "the multiplier can't be negative"));
+        }
+        if !change.ease_ms.is_finite() || change.ease_ms < 0.0 {
+            return Err(ChartError::invalid(&format!("scroll_velocity[{}].ease_ms", i), "the ease can't be negative"));
+        }
+        if !change.starting_at.is_finite() {
+            return Err(ChartError::invalid(&format!("scroll_velocity[{}].starting_at", i), "the time is not a number"));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chart::error::ChartError;
+
+// the names the editor can give to a new section, a name that is already used gets a number ("Verse 2")
+pub const SECTION_NAMES: [&str; 9] = ["Intro", "Verse", "Pre-Chorus", "Chorus", "Bridge", "Drop", "Breakdown", "Solo", "Outro"];
+// going back to the section before only happens this close to the start of the current one, after it the current one starts again
+// (like the previous button of a music player)
+const RESTART_SECTION_MS: f64 = 2000.0;
+
+// a named part of the song from `starting_at` until the next section, like "Chorus"
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Section {
+    pub starting_at: f64,
+    pub name: String,
+}
+
+// the sections in time order, the other functions need them like that
+pub fn sorted(sections: &[Section]) -> Vec<Section> {
+    let mut sections = sections.to_vec();
+    sections.sort_by(|a, b| a.starting_at.total_cmp(&b.starting_at));
+    sections
+}
+
+// the section being played at this time
+pub fn current(sections: &[Section], time: f64) -> Option<usize> {
+    sections.iter().rposition(|section| section.starting_at <= time)
+}
+
+// the first section that starts after this time
+pub fn next(sections: &[Section], time: f64) -> Option<usize> {
+    sections.iter().position(|section| section.starting_at > time)
+}
+
+// the start of the current section, or the one before when the current one just started
+pub fn previous(sections: &[Section], time: f64) -> Option<usize> {
+    let current = current(sections, time)?;
+    if time - sections[current].starting_at < RESTART_SECTION_MS {
+        return current.checked_sub(1).or(Some(current));
+    }
+    Some(current)
+}
+
+// adds a section keeping the list sorted, `name` gets the number after the highest one already used with it
+// (the section without a number is the 1), so removing a section never gives two with the same name
+pub fn add(sections: &mut Vec<Section>, starting_at: f64, name: &str) {
+    let highest = sections.iter().filter_map(|section| number_of(&section.name, name)).max();
+    let name = match highest {
+        Some(highest) => format!("{} {}", name, highest + 1),
+        None => name.to_owned(),
+    };
+    let index = sections.partition_point(|section| section.starting_at <= starting_at);
+    sections.insert(index, Section { starting_at, name });
+}
+
+// the number of a section named like `name`, "Verse" is 1 and "Verse 3" is 3, but "Drop the bass" is not a "Drop"
+fn number_of(section: &str, name: &str) -> Option<u32> {
+    if section == name {
+        return Some(1);
+    }
+    section.strip_prefix(name)?.strip_prefix(' ')?.parse().ok()
+}
+
+// removes the section that starts this close to the time, returns false when there is none
+pub fn remove_near(sections: &mut Vec<Section>, time: f64, distance: f64) -> bool {
+    match sections.iter().position(|section| (section.starting_at - time).abs() <= distance) {
+        Some(index) => {
+            sections.remove(index);
+            true
+        },
+        None => false,
+    }
+}
+
+pub fn check(sections: &[Section]) -> Result<(), ChartError> {
+    for (i, section) in sections.iter().enumerate() {
+        if !section.starting_at.is_finite() {
+            return Err(ChartError::invalid(&format!("sections[{}].starting_at", i), "the time is not a number"));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn section(starting_at: f64, name: &str) -> Section {
+        Section { starting_at, name: name.to_owned() }
+    }
+
+    #[test]
+    fn add_numbers_after_the_highest_one() {
+        let mut list = vec![section(0.0, "Verse"), section(1000.0, "Verse 3")];
+        add(&mut list, 500.0, "Verse");
+
+        assert_eq!(list[1], section(500.0, "Verse 4"));
+    }
+
+    #[test]
+    fn add_skips_names_that_only_start_the_same() {
+        let mut list = vec![section(0.0, "Drop the bass"), section(1000.0, "Dropkick")];
+        add(&mut list, 2000.0, "Drop");
+        add(&mut list, 3000.0, "Drop");
+
+        assert_eq!(list[2].name, "Drop");
+        assert_eq!(list[3].name, "Drop 2");
+    }
+
+    #[test]
+    fn previous_goes_back_only_near_the_start() {
+        let list = vec![section(0.0, "Intro"), section(10000.0, "Verse")];
+
+        assert_eq!(current(&list, 11000.0), Some(1));
+        assert_eq!(previous(&list, 11000.0), Some(0));
+        assert_eq!(previous(&list, 15000.0), Some(1));
+        assert_eq!(previous(&list, 500.0), Some(0));
+        assert_eq!(next(&list, 500.0), Some(1));
+        assert_eq!(next(&list, 10000.0), None);
+    }
+
+    #[test]
+    fn remove_near_takes_only_a_close_section() {
+        let mut list = vec![section(0.0, "Intro"), section(10000.0, "Verse")];
+
+        assert!(!remove_near(&mut list, 9000.0, 500.0));
+        assert!(remove_near(&mut list, 9800.0, 500.0));
+        assert_eq!(list, vec![section(0.0, "Intro")]);
+    }
+}
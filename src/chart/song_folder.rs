@@ -0,0 +1,89 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use crate::load_song::Song;
+
+pub const SONGS_FOLDER: &str = "songs";
+
+// names coming from other games can have characters that windows doesn't accept in a folder name
+pub fn sanitize_name(name: &str) -> String {
+    let clean: String = name.chars()
+        .map(|c| if "<>:\"/\\|?*".contains(c) || c.is_control() { '_' } else { c })
+        .collect();
+    let clean = clean.trim().trim_end_matches('.').to_owned();
+
+    if clean.is_empty() {
+        return "Imported song".to_owned();
+    }
+    clean
+}
+
+// creates a new folder inside songs, if the name is already used a number is added at the end (Song (2), Song (3)...)
+pub fn create_song_folder(name: &str) -> io::Result<(String, PathBuf)> {
+    create_folder_in(Path::new(SONGS_FOLDER), name)
+}
+
+// the same as create_song_folder on any folder
+pub fn create_folder_in(songs: &Path, name: &str) -> io::Result<(String, PathBuf)> {
+    let base = sanitize_name(name);
+    let mut folder = base.clone();
+    let mut count = 2;
+
+    while songs.join(&folder).exists() {
+        folder = format!("{} ({})", base, count);
+        count += 1;
+    }
+
+    let path = songs.join(&folder);
+    fs::create_dir_all(&path)?;
+    Ok((folder, path))
+}
+
+// the audio formats SDL_mixer can play, audio.mp3 is first because it is the name every song used before
+pub const AUDIO_EXTENSIONS: [&str; 5] = ["mp3", "ogg", "flac", "wav", "opus"];
+
+// the audio of a song folder: the file named on the chart metadata, then audio.<extension>,
+// and if there is none of those any audio file of the folder (the first one by name)
+pub fn find_audio(folder: &str, audio_file: Option<&String>) -> Option<PathBuf> {
+    let folder_path = Path::new(SONGS_FOLDER).join(folder);
+
+    if let Some(path) = audio_file.map(|file| folder_path.join(file)).filter(|path| path.is_file()) {
+        return Some(path);
+    }
+    if let Some(path) = AUDIO_EXTENSIONS.iter().map(|extension| folder_path.join("audio.".to_owned() + extension)).find(|path| path.is_file()) {
+        return Some(path);
+    }
+
+    let mut audio_files: Vec<PathBuf> = fs::read_dir(&folder_path).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| AUDIO_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str())))
+        .collect();
+    audio_files.sort();
+    audio_files.into_iter().next()
+}
+
+// copies a file next to the chart keeping its extension, for example "audio" + song.ogg gives audio.ogg
+pub fn copy_asset(source: &Path, folder: &Path, name: &str) -> io::Result<PathBuf> {
+    let file_name = match source.extension() {
+        Some(extension) => format!("{}.{}", name, extension.to_string_lossy().to_lowercase()),
+        None => name.to_owned(),
+    };
+    let destination = folder.join(file_name);
+    fs::copy(source, &destination)?;
+    Ok(destination)
+}
+
+// the name of a copied asset as it is saved on the metadata of the chart
+pub fn file_name(path: &Path) -> Option<String> {
+    path.file_name().map(|name| name.to_string_lossy().to_string())
+}
+
+// `chart` is the path of the chart inside the folder, like DEFAULT_CHART or charts/hard.json
+pub fn write_chart(folder: &Path, chart: &str, song: &Song) -> io::Result<()> {
+    let json_string = serde_json::to_string(song)?;
+    let path = folder.join(chart);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, json_string)
+}
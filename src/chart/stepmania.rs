@@ -0,0 +1,491 @@
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
+
+use crate::{chart::{error::ChartError, song_folder, timing::{TimingMap, DEFAULT_BPM}}, input::keybutton::{Note, NoteKind}, load_song::{Bpm, Song, SongMetadata, Stop, TimeSignature, CHARTS_FOLDER, DEFAULT_CHART, LEAD_IN_MS}};
+
+// the types of chart that are read and written, with their amount of columns (each column is a lane)
+const STEPS_TYPES: [(&str, usize); 6] = [("dance-single", 4), ("pump-single", 5), ("dance-solo", 6), ("kb7-single", 7), ("dance-double", 8), ("pump-double", 10)];
+// the smallest note stepmania saves is a 192nd, so a measure of 4 beats has 192 rows
+const ROWS_PER_MEASURE: usize = 192;
+const ROWS_PER_BEAT: f64 = 48.0;
+const MEASURE_RESOLUTIONS: [usize; 10] = [4, 8, 12, 16, 24, 32, 48, 64, 96, 192];
+const SM_DIFFICULTIES: [&str; 6] = ["Beginner", "Easy", "Medium", "Hard", "Challenge", "Edit"];
+
+// dance-single columns are Left, Down, Up, Right, so each one goes to the lane with the same arrow
+// (our 4K lanes are left, up, bottom, right). the other types keep their columns in order
+const COLUMN_TO_LANE: [usize; 4] = [0, 2, 1, 3];
+
+pub struct SmChart {
+    pub steps_type: String,
+    pub author: String,
+    pub difficulty: String,
+    pub meter: String,
+    pub notes: String,
+    pub offset: Option<f64>,
+    pub bpms: Option<Vec<(f64, f64)>>,
+    pub stops: Option<Vec<(f64, f64)>>,
+    pub delays: Option<Vec<(f64, f64)>>,
+    pub time_signatures: Option<Vec<(f64, u32, u32)>>,
+}
+
+pub struct SmFile {
+    pub title: String,
+    pub title_translit: String,
+    pub artist: String,
+    pub credit: String,
+    pub genre: String,
+    pub sample_start: Option<f64>,
+    pub music: String,
+    pub background: String,
+    pub offset: f64,
+    pub bpms: Vec<(f64, f64)>,
+    // (beat, seconds) the beats stop
+    pub stops: Vec<(f64, f64)>,
+    pub delays: Vec<(f64, f64)>,
+    // (beat, beats of the measure, note of the beat)
+    pub time_signatures: Vec<(f64, u32, u32)>,
+    pub charts: Vec<SmChart>,
+}
+
+impl SmFile {
+    // reads both .sm and .ssc files, on .ssc every #NOTEDATA starts a new chart with its own tags
+    pub fn parse(contents: &str) -> Result<SmFile, ChartError> {
+        let contents: String = contents.lines()
+            .map(|line| match line.find("//") { Some(index) => &line[..index], None => line })
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        let mut file = SmFile { title: "".to_owned(), title_translit: "".to_owned(), artist: "".to_owned(), credit: "".to_owned(), genre: "".to_owned(), sample_start: None, music: "".to_owned(), background: "".to_owned(), offset: 0.0, bpms: vec![], stops: vec![], delays: vec![], time_signatures: vec![], charts: vec![] };
+        let mut ssc_chart: Option<SmChart> = None;
+
+        for (tag, value) in tags(&contents) {
+            let value = value.trim();
+            match (tag.to_uppercase().as_str(), &mut ssc_chart) {
+                ("NOTEDATA", _) => {
+                    if let Some(chart) = ssc_chart.take() {
+                        file.charts.push(chart);
+                    }
+                    ssc_chart = Some(SmChart { steps_type: "".to_owned(), author: "".to_owned(), difficulty: "".to_owned(), meter: "".to_owned(), notes: "".to_owned(), offset: None, bpms: None, stops: None, delays: None, time_signatures: None });
+                },
+                ("STEPSTYPE", Some(chart)) => chart.steps_type = value.to_owned(),
+                ("CREDIT", Some(chart)) => chart.author = value.to_owned(),
+                ("DIFFICULTY", Some(chart)) => chart.difficulty = value.to_owned(),
+                ("METER", Some(chart)) => chart.meter = value.to_owned(),
+                ("NOTES", Some(chart)) => chart.notes = value.to_owned(),
+                ("OFFSET", Some(chart)) => chart.offset = Some(parse_number("OFFSET", value)?),
+                ("BPMS", Some(chart)) => chart.bpms = Some(parse_beat_values("BPMS", value)?),
+                ("STOPS" | "FREEZES", Some(chart)) => chart.stops = Some(parse_beat_values("STOPS", value)?),
+                ("DELAYS", Some(chart)) => chart.delays = Some(parse_beat_values("DELAYS", value)?),
+                ("TIMESIGNATURES", Some(chart)) => chart.time_signatures = Some(parse_time_signatures(value)?),
+                ("NOTES", None) => {
+                    // on .sm the chart data is: type:author:difficulty:meter:radar values:notes
+                    let fields: Vec<&str> = value.splitn(6, ':').collect();
+                    if fields.len() < 6 {
+                        return Err(ChartError::invalid("NOTES", "a #NOTES tag needs 6 values separated by ':'"));
+                    }
+                    file.charts.push(SmChart { steps_type: fields[0].trim().to_owned(), author: fields[1].trim().to_owned(), difficulty: fields[2].trim().to_owned(), meter: fields[3].trim().to_owned(), notes: fields[5].to_owned(), offset: None, bpms: None, stops: None, delays: None, time_signatures: None });
+                },
+                ("TITLE", None) => file.title = value.to_owned(),
+                ("TITLETRANSLIT", None) => file.title_translit = value.to_owned(),
+                ("ARTIST", None) => file.artist = value.to_owned(),
+                ("CREDIT", None) => file.credit = value.to_owned(),
+                ("GENRE", None) => file.genre = value.to_owned(),
+                // an empty value is common and means the tag is not set
+                ("SAMPLESTART", None) if !value.trim().is_empty() => file.sample_start = Some(parse_number("SAMPLESTART", value)? * 1000.0),
+                ("MUSIC", None) => file.music = value.to_owned(),
+                ("BACKGROUND", None) => file.background = value.to_owned(),
+                ("OFFSET", None) => file.offset = parse_number("OFFSET", value)?,
+                ("BPMS", None) => file.bpms = parse_beat_values("BPMS", value)?,
+                // very old files call the stops freezes
+                ("STOPS" | "FREEZES", None) => file.stops = parse_beat_values("STOPS", value)?,
+                ("DELAYS", None) => file.delays = parse_beat_values("DELAYS", value)?,
+                ("TIMESIGNATURES", None) => file.time_signatures = parse_time_signatures(value)?,
+                _ => {},
+            }
+        }
+
+        if let Some(chart) = ssc_chart.take() {
+            file.charts.push(chart);
+        }
+        Ok(file)
+    }
+
+    // every chart of a known type of the file with the name of its difficulty
+    pub fn songs(&self) -> Result<Vec<(String, Song)>, ChartError> {
+        let mut songs = vec![];
+
+        for chart in &self.charts {
+            let Some(columns) = columns_of(&chart.steps_type) else { continue };
+            let bpms = chart.bpms.clone().unwrap_or(self.bpms.clone());
+            let offset = chart.offset.unwrap_or(self.offset);
+            let stops = chart.stops.clone().unwrap_or(self.stops.clone());
+            let delays = chart.delays.clone().unwrap_or(self.delays.clone());
+            let pauses: Vec<(f64, f64, bool)> = stops.iter().map(|(beat, seconds)| (*beat, seconds * 1000.0, false))
+                .chain(delays.iter().map(|(beat, seconds)| (*beat, seconds * 1000.0, true)))
+                .collect();
+            // the offset is the negative of the time where the beat 0 is in the audio
+            let timing = TimingMap::from_beats(&bpms, &pauses, LEAD_IN_MS - offset * 1000.0);
+
+            let mut lanes: Vec<Vec<Note>> = vec![vec![]; columns];
+            let mut hold_heads: Vec<Option<(f64, NoteKind)>> = vec![None; columns];
+            let mut end: f64 = 0.0;
+
+            for (measure_index, measure) in chart.notes.split(',').enumerate() {
+                let rows: Vec<&str> = measure.split_whitespace().collect();
+                for (row_index, row) in rows.iter().enumerate() {
+                    let beat = (measure_index as f64 + row_index as f64 / rows.len() as f64) * 4.0;
+                    let time = timing.beat_to_time(beat);
+
+                    for (column, step) in row.chars().take(columns).enumerate() {
+                        let lane = column_lane(column, columns);
+                        match step {
+                            '1' | 'L' => lanes[lane].push(Note::new(time, 0.0)),
+                            'M' => lanes[lane].push(Note { time, holding: 0.0, kind: NoteKind::Mine }),
+                            'F' => lanes[lane].push(Note { time, holding: 0.0, kind: NoteKind::Fake }),
+                            '2' => hold_heads[column] = Some((time, NoteKind::Normal)),
+                            '4' => hold_heads[column] = Some((time, NoteKind::Roll)),
+                            '3' => {
+                                if let Some((head, kind)) = hold_heads[column].take() {
+                                    lanes[lane].push(Note { time: head, holding: time - head, kind });
+                                }
+                            },
+                            _ => {},
+                        }
+                        if step != '0' {
+                            end = end.max(time);
+                        }
+                    }
+                }
+            }
+
+            for lane in lanes.iter_mut() {
+                lane.sort_by(|a, b| a.time.total_cmp(&b.time));
+            }
+
+            let bpm: Vec<Bpm> = bpms.iter()
+                .map(|(beat, bpm)| Bpm { bpm: *bpm, starting_at: timing.beat_to_time(*beat).max(LEAD_IN_MS) })
+                .collect();
+            let stops: Vec<Stop> = timing.stops();
+            let time_signatures: Vec<TimeSignature> = chart.time_signatures.clone().unwrap_or(self.time_signatures.clone()).iter()
+                .map(|(beat, numerator, denominator)| TimeSignature { starting_at: timing.beat_to_time(*beat), numerator: *numerator, denominator: *denominator })
+                .collect();
+
+            let name = if chart.difficulty.is_empty() { self.title.clone() } else { format!("{} [{}]", self.title, chart.difficulty) };
+            songs.push((chart.difficulty.clone(), Song {
+                name,
+                lanes,
+                end: end + 1000.0,
+                bpm: if bpm.is_empty() { None } else { Some(bpm) },
+                stops,
+                time_signatures,
+                metadata: SongMetadata {
+                    title: self.title.clone(),
+                    title_translit: self.title_translit.clone(),
+                    artist: self.artist.clone(),
+                    // the author of the chart is saved on each chart, the credit of the file is used when it is empty
+                    charter: if chart.author.is_empty() { self.credit.clone() } else { chart.author.clone() },
+                    genre: self.genre.clone(),
+                    preview_start: self.sample_start,
+                    difficulty: if chart.difficulty.is_empty() { None } else { Some(chart.difficulty.clone()) },
+                    ..SongMetadata::default()
+                },
+                ..Song::default()
+            }));
+        }
+
+        Ok(songs)
+    }
+}
+
+// writes a Song as a .sm file with a single chart, the audio has to be next to it with the name `music`.
+// the type of the chart comes from the amount of lanes, the key modes stepmania doesn't have can't be exported
+pub fn export(song: &Song, music: &str) -> Result<String, ChartError> {
+    let columns = song.lane_count();
+    let steps_type = STEPS_TYPES.iter().find(|(_, count)| *count == columns).map(|(name, _)| *name)
+        .ok_or_else(|| ChartError::invalid("lanes", &format!("stepmania doesn't have a chart type with {} columns", columns)))?;
+    // the beat 0 is where the first bpm starts, but never before the audio
+    let timing = TimingMap::new(song).unwrap_or_else(|| TimingMap::from_beats(&[(0.0, DEFAULT_BPM)], &[], LEAD_IN_MS));
+    // the sync moves the notes and the beats together, so on the file it is part of the offset
+    let offset = (timing.origin() - LEAD_IN_MS + song.sync.unwrap_or(0.0)) / 1000.0;
+
+    // every row of the chart that has a note, the key is the number of the row counting from the start
+    let mut rows: BTreeMap<usize, Vec<char>> = BTreeMap::new();
+    let mut place = |time: f64, column: usize, step: char| {
+        let beat = timing.time_to_beat(time);
+        if beat < 0.0 {
+            eprintln!("The note at {} ms is before the audio starts and can't be exported", time);
+            return;
+        }
+        let row = (beat * ROWS_PER_BEAT).round() as usize;
+        rows.entry(row).or_insert(vec!['0'; columns])[column] = step;
+    };
+
+    for column in 0..columns {
+        for note in &song.lanes[column_lane(column, columns)] {
+            match note.kind {
+                NoteKind::Mine => place(note.time, column, 'M'),
+                // stepmania doesn't have fake holds, only the head is kept
+                NoteKind::Fake => place(note.time, column, 'F'),
+                NoteKind::Normal | NoteKind::Roll if note.holding > 0.0 => {
+                    place(note.time, column, if note.kind == NoteKind::Roll { '4' } else { '2' });
+                    place(note.time + note.holding, column, '3');
+                },
+                _ => place(note.time, column, '1'),
+            }
+        }
+    }
+
+    let measures = rows.keys().last().map_or(1, |row| row / ROWS_PER_MEASURE + 1);
+    let mut notes: Vec<String> = vec![];
+    for measure in 0..measures {
+        let first_row = measure * ROWS_PER_MEASURE;
+        let used: Vec<usize> = rows.range(first_row..first_row + ROWS_PER_MEASURE).map(|(row, _)| row - first_row).collect();
+        // every measure is saved with the smallest amount of rows that keeps all its notes in place
+        let resolution = MEASURE_RESOLUTIONS.iter()
+            .find(|resolution| used.iter().all(|row| row % (ROWS_PER_MEASURE / **resolution) == 0))
+            .copied()
+            .unwrap_or(ROWS_PER_MEASURE);
+        let step = ROWS_PER_MEASURE / resolution;
+
+        let lines: Vec<String> = (0..resolution)
+            .map(|line| rows.get(&(first_row + line * step)).map_or("0".repeat(columns), |row| row.iter().collect()))
+            .collect();
+        notes.push(lines.join("\n"));
+    }
+
+    let bpms_text: Vec<String> = timing.bpm_changes().iter().map(|(beat, bpm)| format!("{:.3}={:.3}", beat, bpm)).collect();
+    let pauses_text = |delay: bool| -> String {
+        timing.pauses().iter().filter(|pause| pause.delay == delay).map(|pause| format!("{:.3}={:.3}", pause.beat, pause.duration / 1000.0)).collect::<Vec<String>>().join(",")
+    };
+    let signatures_text: Vec<String> = song.time_signatures.iter()
+        .map(|signature| format!("{:.3}={}={}", timing.time_to_beat(signature.starting_at).max(0.0), signature.numerator, signature.denominator))
+        .collect();
+    let metadata = &song.metadata;
+    let title = if metadata.title.is_empty() { &song.name } else { &metadata.title };
+    let charter = if metadata.charter.is_empty() { "Arrownier" } else { &metadata.charter };
+    // stepmania only knows these difficulties, everything else is saved as an Edit
+    let difficulty = metadata.difficulty.as_ref()
+        .and_then(|difficulty| SM_DIFFICULTIES.iter().find(|known| known.eq_ignore_ascii_case(difficulty)))
+        .unwrap_or(&"Edit");
+    let sample_start = metadata.preview_start.map_or("".to_owned(), |start| format!("{:.3}", start / 1000.0));
+
+    Ok(format!(
+        "#TITLE:{};\n#TITLETRANSLIT:{};\n#ARTIST:{};\n#GENRE:{};\n#CREDIT:{};\n#MUSIC:{};\n#OFFSET:{:.3};\n#SAMPLESTART:{};\n#BPMS:{};\n#STOPS:{};\n#DELAYS:{};\n#TIMESIGNATURES:{};\n\n//---------------{} - {}----------------\n#NOTES:\n     {}:\n     {}:\n     {}:\n     1:\n     0,0,0,0,0:\n{}\n;\n",
+        title, metadata.title_translit, metadata.artist, metadata.genre, charter, music, if offset == 0.0 { 0.0 } else { -offset }, sample_start, bpms_text.join(","), pauses_text(false), pauses_text(true), signatures_text.join(","),
+        steps_type, charter, steps_type, charter, difficulty, notes.join("\n,\n")
+    ))
+}
+
+// reads a .sm or .ssc file and writes it inside songs as a new folder, returns the folder name
+// a file with a single chart is saved as data.json, with more every difficulty goes to charts/<difficulty>.json
+pub fn import(sm_file: &Path) -> Result<String, ChartError> {
+    let path = sm_file.to_string_lossy().to_string();
+    let contents = fs::read_to_string(sm_file).map_err(|err| ChartError::from_io(&path, err))?;
+    let file = SmFile::parse(&contents)?;
+    let songs = file.songs()?;
+
+    if songs.is_empty() {
+        return Err(ChartError::invalid("NOTES", "the file doesn't have any chart of a type the game can play"));
+    }
+
+    let sm_folder = sm_file.parent().unwrap_or(Path::new("."));
+    let folder_name = if file.artist.is_empty() { file.title.clone() } else { format!("{} - {}", file.artist, file.title) };
+    let (folder, folder_path) = song_folder::create_song_folder(&folder_name).map_err(|err| ChartError::from_io(&folder_name, err))?;
+
+    if let Err(err) = fill_folder(&file, songs, sm_folder, &folder_path) {
+        // half imported songs would show up broken on the song list
+        let _ = fs::remove_dir_all(&folder_path);
+        return Err(err);
+    }
+    Ok(folder)
+}
+
+// copies the audio and the background of the file to the new song folder and writes its charts
+fn fill_folder(file: &SmFile, songs: Vec<(String, Song)>, sm_folder: &Path, folder_path: &Path) -> Result<(), ChartError> {
+    let mut audio_file = None;
+    let mut cover_file = None;
+    if !file.music.is_empty() {
+        let audio = sm_folder.join(&file.music);
+        let copied = song_folder::copy_asset(&audio, folder_path, "audio").map_err(|err| ChartError::from_io(&audio.to_string_lossy(), err))?;
+        audio_file = song_folder::file_name(&copied);
+    }
+    if !file.background.is_empty() {
+        let cover = sm_folder.join(&file.background);
+        match song_folder::copy_asset(&cover, folder_path, "cover") {
+            Ok(copied) => cover_file = song_folder::file_name(&copied),
+            Err(err) => eprintln!("The background {} wasn't copied: {}", cover.to_string_lossy(), err),
+        }
+    }
+
+    let single = songs.len() == 1;
+    let mut used_names: Vec<String> = vec![];
+    for (difficulty, mut song) in songs {
+        song.metadata.audio_file = audio_file.clone();
+        song.metadata.cover_file = cover_file.clone();
+        let chart = if single {
+            DEFAULT_CHART.to_owned()
+        } else {
+            // files can have more than one chart with the same difficulty (mostly Edit), those get a number
+            let base = song_folder::sanitize_name(&difficulty.to_lowercase());
+            let mut name = base.clone();
+            let mut count = 2;
+            while used_names.contains(&name) {
+                name = format!("{} ({})", base, count);
+                count += 1;
+            }
+            used_names.push(name.clone());
+            format!("{}/{}.json", CHARTS_FOLDER, name)
+        };
+        song_folder::write_chart(folder_path, &chart, &song).map_err(|err| ChartError::from_io(&folder_path.to_string_lossy(), err))?;
+    }
+    Ok(())
+}
+
+// writes a chart of a song folder as <folder>/<folder>.sm, so the folder can be copied as it is to stepmania
+pub fn export_folder(folder: &str, chart: &str) -> Result<PathBuf, ChartError> {
+    let song = Song::new(folder, chart)?;
+    let file_name = if chart == DEFAULT_CHART { folder.to_owned() } else { format!("{} [{}]", folder, Song::chart_label(chart)) };
+    let path = Path::new(song_folder::SONGS_FOLDER).join(folder).join(song_folder::sanitize_name(&file_name) + ".sm");
+    // the .sm is written next to the audio, so only the file name is needed
+    let music = song_folder::find_audio(folder, song.metadata.audio_file.as_ref())
+        .and_then(|path| song_folder::file_name(&path))
+        .unwrap_or("audio.mp3".to_owned());
+    fs::write(&path, export(&song, &music)?).map_err(|err| ChartError::from_io(&path.to_string_lossy(), err))?;
+    Ok(path)
+}
+
+// the amount of columns of a type of chart, None for the types the game can't play
+fn columns_of(steps_type: &str) -> Option<usize> {
+    STEPS_TYPES.iter().find(|(name, _)| name.eq_ignore_ascii_case(steps_type.trim())).map(|(_, columns)| *columns)
+}
+
+// the lane of a column of the chart
+fn column_lane(column: usize, columns: usize) -> usize {
+    if columns == 4 { COLUMN_TO_LANE[column] } else { column }
+}
+
+// splits the text of the file in #TAG:value; pairs
+fn tags(contents: &str) -> Vec<(&str, &str)> {
+    let mut tags = vec![];
+    let mut rest = contents;
+
+    while let Some(start) = rest.find('#') {
+        rest = &rest[start + 1..];
+        let Some(colon) = rest.find(':') else { break };
+        let end = rest.find(';').unwrap_or(rest.len());
+        if colon > end {
+            // a tag without value, like #NOTEDATA;
+            tags.push((&rest[..end], ""));
+            rest = &rest[end..];
+            continue;
+        }
+        tags.push((&rest[..colon], &rest[colon + 1..end]));
+        rest = &rest[end..];
+    }
+    tags
+}
+
+fn parse_number(tag: &str, value: &str) -> Result<f64, ChartError> {
+    value.trim().parse().map_err(|_| ChartError::invalid(tag, &format!("{} is not a number", value)))
+}
+
+// #BPMS:0.000=120.000,64.000=140.000 (and the same for #STOPS and #DELAYS, with seconds instead of the bpm)
+fn parse_beat_values(tag: &str, value: &str) -> Result<Vec<(f64, f64)>, ChartError> {
+    let mut values = vec![];
+    for change in value.split(',').filter(|change| !change.trim().is_empty()) {
+        let Some((beat, number)) = change.split_once('=') else {
+            return Err(ChartError::invalid(tag, &format!("{} is not a beat=value pair", change.trim())));
+        };
+        values.push((parse_number(tag, beat)?, parse_number(tag, number)?));
+    }
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(values)
+}
+
+// #TIMESIGNATURES:0.000=4=4,32.000=7=8
+fn parse_time_signatures(value: &str) -> Result<Vec<(f64, u32, u32)>, ChartError> {
+    let mut signatures = vec![];
+    for change in value.split(',').filter(|change| !change.trim().is_empty()) {
+        let parts: Vec<&str> = change.split('=').map(|part| part.trim()).collect();
+        let signature = match parts.as_slice() {
+            [beat, numerator, denominator] => numerator.parse().ok().zip(denominator.parse().ok()).map(|(numerator, denominator)| (beat, numerator, denominator)),
+            _ => None,
+        };
+        let Some((beat, numerator, denominator)) = signature.filter(|(_, numerator, denominator)| *numerator > 0 && *denominator > 0) else {
+            return Err(ChartError::invalid("TIMESIGNATURES", &format!("{} is not a beat=numerator=denominator value", change.trim())));
+        };
+        signatures.push((parse_number("TIMESIGNATURES", beat)?, numerator, denominator));
+    }
+    signatures.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(signatures)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIMFILE: &str = "#TITLE:Song;
+#ARTIST:Someone;
+#OFFSET:0.000;
+#SAMPLESTART:;
+#BPMS:0.000=120.000;
+#STOPS:6.000=0.500;
+#NOTES:
+     dance-single:
+     Stepper:
+     Hard:
+     9:
+     0,0,0,0,0:
+1000
+0100 // a comment
+0010
+000M
+,
+2000
+0000
+3000
+0001
+;
+";
+
+    #[test]
+    fn parses_the_tags_and_the_measures() {
+        let file = SmFile::parse(SIMFILE).unwrap();
+        assert_eq!(file.title, "Song");
+        assert_eq!(file.sample_start, None);
+        assert_eq!(file.stops, vec![(6.0, 0.5)]);
+        assert_eq!(file.charts.len(), 1);
+
+        let songs = file.songs().unwrap();
+        let (difficulty, song) = &songs[0];
+        assert_eq!(difficulty, "Hard");
+        assert_eq!(song.metadata.charter, "Stepper");
+        // the second column is down, so it goes to the lane 2 (bottom), and the third is up (lane 1)
+        assert_eq!(song.lanes[0][0].time, LEAD_IN_MS);
+        assert_eq!(song.lanes[2][0].time, LEAD_IN_MS + 500.0);
+        assert_eq!(song.lanes[1][0].time, LEAD_IN_MS + 1000.0);
+        assert_eq!(song.lanes[3][0].kind, NoteKind::Mine);
+        // the hold goes from the beat 4 to the 6, the stop on the beat 6 is after it
+        assert_eq!((song.lanes[0][1].time, song.lanes[0][1].holding), (LEAD_IN_MS + 2000.0, 1000.0));
+        assert_eq!(song.lanes[3][1].time, LEAD_IN_MS + 3500.0 + 500.0);
+    }
+
+    #[test]
+    fn export_and_import_keep_the_notes() {
+        let (_, song) = SmFile::parse(SIMFILE).unwrap().songs().unwrap().remove(0);
+        let exported = export(&song, "audio.ogg").unwrap();
+        let (_, imported) = SmFile::parse(&exported).unwrap().songs().unwrap().remove(0);
+
+        for lane in 0..4 {
+            let times = |song: &Song| song.lanes[lane].iter().map(|note| (note.time, note.holding, note.kind)).collect::<Vec<_>>();
+            assert_eq!(times(&imported), times(&song));
+        }
+        assert_eq!(imported.stops.len(), 1);
+    }
+
+    #[test]
+    fn unknown_chart_types_are_skipped() {
+        let file = SmFile::parse("#NOTES:lights-cabinet:::1:0:\n00000000\n;").unwrap();
+        assert!(file.songs().unwrap().is_empty());
+        assert!(SmFile::parse("#NOTES:dance-single:only:three;").is_err());
+    }
+}
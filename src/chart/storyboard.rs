@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+
+use crate::chart::error::ChartError;
+
+// something that changes how the level looks from `starting_at`, the notes and the points are never changed by them
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct ChartEvent {
+    pub starting_at: f64,
+    #[serde(flatten)]
+    pub action: EventAction,
+}
+
+// on the json the kind of event is the "type", like {"starting_at": 5000, "type": "flash", "color": [255, 255, 255], "duration": 300}
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventAction {
+    // an image of the song folder drawn behind the lanes, without a file the default background comes back
+    Background {
+        #[serde(default)]
+        file: Option<String>,
+    },
+    // the screen gets covered with the color and it goes away during `duration`
+    Flash {
+        color: [u8; 3],
+        duration: f64,
+    },
+    // the background gets dark, `amount` goes from 0 (as it is) to 1 (black) and it is reached after `duration`
+    Dim {
+        amount: f64,
+        #[serde(default)]
+        duration: f64,
+    },
+    // turns the visualizers on or off, the one that is not written stays as it was.
+    // a visualizer the player turned off on the settings is never shown
+    Visualizer {
+        #[serde(default)]
+        bars: Option<bool>,
+        #[serde(default)]
+        circle: Option<bool>,
+    },
+    // the keys and notes of the lanes go to `opacity` (0 is hidden, 1 is normal) during `duration`, without lanes it is every lane
+    LaneFade {
+        #[serde(default)]
+        lanes: Vec<usize>,
+        opacity: f64,
+        #[serde(default)]
+        duration: f64,
+    },
+}
+
+impl EventAction {
+    // a short description for the editor, like "flash" or "dim 50%"
+    pub fn label(&self) -> String {
+        match self {
+            EventAction::Background { file: Some(file) } => format!("background {}", file),
+            EventAction::Background { file: None } => "default background".to_owned(),
+            EventAction::Flash { .. } => "flash".to_owned(),
+            EventAction::Dim { amount, .. } => format!("dim {:.0}%", amount * 100.0),
+            EventAction::Visualizer { bars, circle } => {
+                let state = |name: &str, on: &Option<bool>| on.map(|on| format!("{} {}", name, if on { "on" } else { "off" }));
+                [state("bars", bars), state("circle", circle)].into_iter().flatten().collect::<Vec<String>>().join(", ")
+            },
+            EventAction::LaneFade { lanes, opacity, .. } if lanes.is_empty() => format!("lanes to {:.0}%", opacity * 100.0),
+            EventAction::LaneFade { lanes, opacity, .. } => format!("lanes {:?} to {:.0}%", lanes, opacity * 100.0),
+        }
+    }
+}
+
+// how the level looks at a moment of the song
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoryboardState {
+    // None is the default background of the game
+    pub background: Option<String>,
+    pub dim: f64,
+    // the color of the flash and how much it covers (from 0 to 1)
+    pub flash: Option<([u8; 3], f64)>,
+    // None when no event changed them, so the settings of the player are used
+    pub bars: Option<bool>,
+    pub circle: Option<bool>,
+    pub lane_opacity: Vec<f64>,
+}
+
+// a value that goes in a line from `from` to `to` during `duration` since `start`
+#[derive(Clone, Copy)]
+struct Fade {
+    from: f64,
+    to: f64,
+    start: f64,
+    duration: f64,
+}
+
+impl Fade {
+    fn still(value: f64) -> Fade {
+        Fade { from: value, to: value, start: 0.0, duration: 0.0 }
+    }
+
+    fn value(&self, time: f64) -> f64 {
+        if self.duration <= 0.0 || time >= self.start + self.duration {
+            return self.to;
+        }
+        self.from + (self.to - self.from) * ((time - self.start) / self.duration).max(0.0)
+    }
+
+    // a fade that starts while the one before is still going starts from where that one is
+    fn to(&self, to: f64, start: f64, duration: f64) -> Fade {
+        Fade { from: self.value(start), to, start, duration }
+    }
+}
+
+// the events of a chart in time order. the state is found again from the first event every time it is asked,
+// so it is the same when the song is played from the start or from any point of the editor
+pub struct Storyboard {
+    events: Vec<ChartEvent>,
+}
+
+impl Storyboard {
+    pub fn new(events: &[ChartEvent]) -> Storyboard {
+        let mut events = events.to_vec();
+        events.sort_by(|a, b| a.starting_at.total_cmp(&b.starting_at));
+        Storyboard { events }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn events(&self) -> &[ChartEvent] {
+        &self.events
+    }
+
+    // the image files the events use, to load them before the song starts
+    pub fn backgrounds(&self) -> Vec<String> {
+        let mut files: Vec<String> = self.events.iter()
+            .filter_map(|event| match &event.action {
+                EventAction::Background { file } => file.clone(),
+                _ => None,
+            })
+            .collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    pub fn state_at(&self, time: f64, lane_count: usize) -> StoryboardState {
+        let mut background = None;
+        let mut dim = Fade::still(0.0);
+        let mut flash = None;
+        let (mut bars, mut circle) = (None, None);
+        let mut lanes = vec![Fade::still(1.0); lane_count];
+
+        for event in self.events.iter().take_while(|event| event.starting_at <= time) {
+            let start = event.starting_at;
+            match &event.action {
+                EventAction::Background { file } => background = file.clone(),
+                EventAction::Flash { color, duration } => {
+                    let left = if *duration > 0.0 { 1.0 - (time - start) / duration } else { 0.0 };
+                    flash = if left > 0.0 { Some((*color, left.min(1.0))) } else { None };
+                },
+                EventAction::Dim { amount, duration } => dim = dim.to(amount.clamp(0.0, 1.0), start, *duration),
+                EventAction::Visualizer { bars: new_bars, circle: new_circle } => {
+                    bars = new_bars.or(bars);
+                    circle = new_circle.or(circle);
+                },
+                EventAction::LaneFade { lanes: fade_lanes, opacity, duration } => {
+                    // a lane the chart doesn't have (like after the lanes modifier) is skipped
+                    let chosen: Vec<usize> = if fade_lanes.is_empty() { (0..lane_count).collect() } else { fade_lanes.iter().copied().filter(|lane| *lane < lane_count).collect() };
+                    for lane in chosen {
+                        lanes[lane] = lanes[lane].to(opacity.clamp(0.0, 1.0), start, *duration);
+                    }
+                },
+            }
+        }
+
+        StoryboardState {
+            background,
+            dim: dim.value(time),
+            flash,
+            bars,
+            circle,
+            lane_opacity: lanes.iter().map(|lane| lane.value(time)).collect(),
+        }
+    }
+}
+
+// the values that can't be drawn, the lanes that the chart doesn't have are only a warning of the validation
+pub fn check(events: &[ChartEvent]) -> Result<(), ChartError> {
+    for (i, event) in events.iter().enumerate() {
+        if !event.starting_at.is_finite() {
+            return Err(ChartError::invalid(&format!("events[{}].starting_at", i), "the time is not a number"));
+        }
+        let duration = match &event.action {
+            EventAction::Flash { duration, .. } | EventAction::Dim { duration, .. } | EventAction::LaneFade { duration, .. } => *duration,
+            _ => 0.0,
+        };
+        if !duration.is_finite() || duration < 0.0 {
+            return Err(ChartError::invalid(&format!("events[{}].duration", i), "the duration can't be negative"));
+        }
+        match &event.action {
+            EventAction::Dim { amount, .. } if !(0.0..=1.0).contains(amount) => {
+                return Err(ChartError::invalid(&format!("events[{}].amount", i), "the dim goes from 0 to 1"));
+            },
+            EventAction::LaneFade { opacity, .. } if !(0.0..=1.0).contains(opacity) => {
+                return Err(ChartError::invalid(&format!("events[{}].opacity", i), "the opacity goes from 0 to 1"));
+            },
+            _ => {},
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use crate::{chart::{audio::{self, Audio, OnsetEnvelope}, error::ChartError, song_folder::{self, SONGS_FOLDER}}, load_song::{Bpm, Song, LEAD_IN_MS}};
+
+// the tempos that are searched, a song outside of them gets the half or the double
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 240.0;
+// when two tempos fit the same, the one closer to this wins (the faster or slower "feel" of the same song)
+const PREFERRED_BPM: f64 = 120.0;
+// the tempo is measured on windows of the song this long, every WINDOW_STEP_MS, to find the changes
+const WINDOW_MS: f64 = 8000.0;
+const WINDOW_STEP_MS: f64 = 4000.0;
+// windows whose tempo is closer than this part are the same tempo
+const SAME_TEMPO: f64 = 0.04;
+// a shorter part with other tempo is more often a break or a fill than a tempo change
+const MIN_SECTION_MS: f64 = 16000.0;
+// a window with one of these times the tempo of the song is the same tempo felt in other way (like a triplet feel or double time),
+// it is not a tempo change
+const RELATED_TEMPOS: [f64; 7] = [0.5, 2.0 / 3.0, 0.75, 1.0, 4.0 / 3.0, 1.5, 2.0];
+// the exact tempo is searched this part above and below the one of the windows, first with big steps and then small ones
+const SEARCH_RANGE: f64 = 0.04;
+const COARSE_STEP: f64 = 0.05;
+const FINE_STEP: f64 = 0.005;
+// a whole bpm is used when it fits at least this part as well as the exact one (most songs are made with a whole bpm)
+const WHOLE_BPM_FIT: f64 = 0.95;
+// frames to each side a beat still gets some of the sound (about 23 ms)
+const SPREAD_FRAMES: usize = 2;
+// parts of a beat tried for the first beat
+const PHASE_STEPS: usize = 64;
+
+// a part of the song with the same tempo, from frame `start` to `end`
+struct Section {
+    start: usize,
+    end: usize,
+    bpm: f64,
+}
+
+// the bpm list of the song, each one starting on a beat: the first on the first beat after the song starts playing
+// and the others on the first beat of their tempo. the times are the ones of a chart with `sync`, like the notes.
+// it is empty when the song has no beat that can be found (silence or sounds without rhythm)
+pub fn detect(audio: &Audio, sync: f64) -> Vec<Bpm> {
+    let envelope = OnsetEnvelope::new(audio);
+    let novelty = novelty(&envelope.flux);
+    let frame_bpm = |frames: f64| 60000.0 / (frames * envelope.frame_ms);
+    let min_lag = (60000.0 / MAX_BPM / envelope.frame_ms).floor().max(1.0) as usize;
+    let max_lag = (60000.0 / MIN_BPM / envelope.frame_ms).ceil() as usize;
+    if novelty.len() < max_lag * 4 {
+        return vec![];
+    }
+
+    let Some(global_lag) = best_lag(&novelty, min_lag, max_lag, &frame_bpm) else { return vec![] };
+    let global_bpm = frame_bpm(global_lag as f64);
+
+    // the tempo of every window, moved to the half or double that is closest to the tempo of the whole song
+    let same_tempo = |a: f64, b: f64| (a / b - 1.0).abs() <= SAME_TEMPO;
+    let window = (WINDOW_MS / envelope.frame_ms) as usize;
+    let step = (WINDOW_STEP_MS / envelope.frame_ms) as usize;
+    let mut windows: Vec<(usize, f64)> = vec![];
+    let mut start = 0;
+    while start + window <= novelty.len() {
+        let bpm = best_lag(&novelty[start..start + window], min_lag, max_lag, &frame_bpm).map_or(global_bpm, |lag| frame_bpm(lag as f64));
+        let bpm = match RELATED_TEMPOS.iter().any(|ratio| same_tempo(bpm, global_bpm * ratio)) {
+            true => global_bpm,
+            false => [bpm / 2.0, bpm, bpm * 2.0].into_iter().min_by(|a, b| (a / global_bpm).ln().abs().total_cmp(&(b / global_bpm).ln().abs())).unwrap_or(bpm),
+        };
+        windows.push((start, bpm));
+        start += step;
+    }
+
+    // a tempo of a single window is noise, it needs two in a row (a median of three) to start a new section
+    let smooth: Vec<f64> = (0..windows.len()).map(|i| {
+        let mut around: Vec<f64> = windows[i.saturating_sub(1)..(i + 2).min(windows.len())].iter().map(|(_, bpm)| *bpm).collect();
+        around.sort_by(|a, b| a.total_cmp(b));
+        around[around.len() / 2]
+    }).collect();
+
+    let mut sections: Vec<Section> = vec![Section { start: 0, end: novelty.len(), bpm: smooth.first().copied().unwrap_or(global_bpm) }];
+    for (i, bpm) in smooth.iter().enumerate().skip(1) {
+        let section = sections.last_mut().unwrap();
+        if !same_tempo(*bpm, section.bpm) {
+            // the change is placed at the middle of the window where it was seen
+            let change = windows[i].0 + window / 2;
+            section.end = change;
+            sections.push(Section { start: change, end: novelty.len(), bpm: *bpm });
+        }
+    }
+
+    let min_frames = (MIN_SECTION_MS / envelope.frame_ms) as usize;
+    let mut i = 1;
+    while i < sections.len() {
+        if sections[i].end - sections[i].start < min_frames {
+            sections[i - 1].end = sections[i].end;
+            sections.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+
+    // the exact tempo and first beat of every section, the sections that end up with almost the same tempo are joined and fitted again
+    let mut fitted: Vec<(Section, f64)> = vec![];
+    for section in sections {
+        let (bpm, first_beat) = fit_beats(&novelty[section.start..section.end], section.bpm, envelope.frame_ms);
+        match fitted.last_mut() {
+            Some((last, last_beat)) if (bpm / last.bpm - 1.0).abs() < SAME_TEMPO / 2.0 => {
+                last.end = section.end;
+                let (bpm, first_beat) = fit_beats(&novelty[last.start..last.end], last.bpm, envelope.frame_ms);
+                last.bpm = bpm;
+                *last_beat = last.start as f64 + first_beat;
+            },
+            _ => fitted.push((Section { start: section.start, end: section.end, bpm }, section.start as f64 + first_beat)),
+        }
+    }
+    let mut beats: Vec<(f64, f64)> = fitted.iter().map(|(section, first_beat)| (section.bpm, *first_beat)).collect();
+
+    // the song starts with the first beat that has sound, not with the silence before it
+    if let Some((bpm, first_beat)) = beats.first_mut() {
+        let beat_frames = 60000.0 / *bpm / envelope.frame_ms;
+        let average = envelope.flux.iter().sum::<f64>() / envelope.flux.len() as f64;
+        let first_sound = envelope.flux.iter().position(|flux| *flux > average).unwrap_or(0) as f64;
+        while *first_beat - beat_frames >= first_sound - beat_frames / 4.0 {
+            *first_beat -= beat_frames;
+        }
+        while *first_beat < first_sound - beat_frames / 4.0 {
+            *first_beat += beat_frames;
+        }
+    }
+
+    beats.iter()
+        .map(|(bpm, first_beat)| Bpm { bpm: *bpm, starting_at: (envelope.frame_time(*first_beat) + LEAD_IN_MS - sync).round() })
+        .collect()
+}
+
+// the flux over the average of the frames around it, the slow changes of loudness don't count as beats
+fn novelty(flux: &[f64]) -> Vec<f64> {
+    const AROUND: usize = 8;
+    (0..flux.len()).map(|frame| {
+        let range = frame.saturating_sub(AROUND)..(frame + AROUND + 1).min(flux.len());
+        let average = flux[range.clone()].iter().sum::<f64>() / range.len() as f64;
+        (flux[frame] - average).max(0.0)
+    }).collect()
+}
+
+// the beat length (in frames) that repeats the most in the novelty, the autocorrelation with the double beat added
+// so a tempo that also fits the measures wins, and leaning to PREFERRED_BPM
+fn best_lag(novelty: &[f64], min_lag: usize, max_lag: usize, frame_bpm: &dyn Fn(f64) -> f64) -> Option<usize> {
+    let correlation = |lag: usize| -> f64 {
+        if lag >= novelty.len() {
+            return 0.0;
+        }
+        novelty.iter().zip(&novelty[lag..]).map(|(a, b)| a * b).sum::<f64>() / (novelty.len() - lag) as f64
+    };
+    (min_lag..=max_lag)
+        .map(|lag| {
+            let octaves = (frame_bpm(lag as f64) / PREFERRED_BPM).log2();
+            let weight = (-0.5 * octaves * octaves).exp();
+            (lag, (correlation(lag) + 0.5 * correlation(lag * 2) + 0.5 * correlation(lag / 2)) * weight)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(lag, _)| lag)
+}
+
+// the bpm and first beat (frames from the start of the part) whose beats land on the most sound
+fn fit_beats(novelty: &[f64], around_bpm: f64, frame_ms: f64) -> (f64, f64) {
+    // the peaks are spread a bit, so a beat a few milliseconds away from the sound still counts (a person never plays exactly on time)
+    let novelty: Vec<f64> = (0..novelty.len()).map(|frame| {
+        (frame.saturating_sub(SPREAD_FRAMES)..(frame + SPREAD_FRAMES + 1).min(novelty.len()))
+            .map(|other| novelty[other] * (SPREAD_FRAMES + 1 - frame.abs_diff(other)) as f64)
+            .sum::<f64>() / (SPREAD_FRAMES + 1) as f64
+    }).collect();
+    let novelty = novelty.as_slice();
+
+    let search = |from: f64, to: f64, step: f64| -> (f64, f64, f64) {
+        let mut best = (around_bpm, 0.0, f64::NEG_INFINITY);
+        let mut bpm = from;
+        while bpm <= to {
+            let (phase, score) = best_phase(novelty, 60000.0 / bpm / frame_ms);
+            if score > best.2 {
+                best = (bpm, phase, score);
+            }
+            bpm += step;
+        }
+        best
+    };
+
+    let (coarse, _, _) = search(around_bpm * (1.0 - SEARCH_RANGE), around_bpm * (1.0 + SEARCH_RANGE), COARSE_STEP);
+    let (bpm, phase, score) = search(coarse - COARSE_STEP, coarse + COARSE_STEP, FINE_STEP);
+
+    let whole = bpm.round();
+    let (whole_phase, whole_score) = best_phase(novelty, 60000.0 / whole / frame_ms);
+    if whole_score >= score * WHOLE_BPM_FIT {
+        return (whole, whole_phase);
+    }
+    ((bpm * 100.0).round() / 100.0, phase)
+}
+
+// the first beat (from 0 to the length of a beat) whose beats have the most sound, and how much sound they have on average
+fn best_phase(novelty: &[f64], beat_frames: f64) -> (f64, f64) {
+    let value_at = |frame: f64| -> f64 {
+        let index = frame as usize;
+        let part = frame - index as f64;
+        match (novelty.get(index), novelty.get(index + 1)) {
+            (Some(a), Some(b)) => a + (b - a) * part,
+            (Some(a), None) => *a,
+            _ => 0.0,
+        }
+    };
+
+    (0..PHASE_STEPS)
+        .map(|step| {
+            let phase = step as f64 * beat_frames / PHASE_STEPS as f64;
+            let (mut score, mut count) = (0.0, 0);
+            let mut beat = phase;
+            while beat < novelty.len() as f64 {
+                score += value_at(beat);
+                count += 1;
+                beat += beat_frames;
+            }
+            (phase, score / count.max(1) as f64)
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap_or((0.0, 0.0))
+}
+
+// the bpm list for a chart of a song folder, from the audio of the folder
+pub fn detect_for_chart(folder: &str, song: &Song) -> Result<Vec<Bpm>, ChartError> {
+    let audio_path = song_folder::find_audio(folder, song.metadata.audio_file.as_ref()).ok_or(ChartError::Missing { path: format!("{}/{}/audio.mp3", SONGS_FOLDER, folder) })?;
+    let audio = audio::decode(&audio_path)?;
+    Ok(detect(&audio, song.sync.unwrap_or(0.0)))
+}
@@ -0,0 +1,273 @@
+use crate::load_song::{Song, Stop, LEAD_IN_MS};
+
+// the bpm used when there is nothing else to count the beats with
+pub const DEFAULT_BPM: f64 = 120.0;
+// beats closer than this are the same beat, the rows of a chart are divisions that don't give exact numbers
+const SAME_BEAT: f64 = 0.0005;
+// times closer than this are the same moment
+const SAME_TIME_MS: f64 = 0.001;
+
+// a bpm from `beat`, `time` is where it starts without counting the pauses before it
+#[derive(Clone, Copy)]
+struct Segment {
+    beat: f64,
+    time: f64,
+    bpm: f64,
+}
+
+// a stop holds its beat after the notes on it, a delay before them
+#[derive(Clone, Copy)]
+pub struct Pause {
+    pub beat: f64,
+    pub duration: f64,
+    pub delay: bool,
+}
+
+enum TimingChange {
+    Bpm(f64),
+    // milliseconds and if it is a delay
+    Stop(f64, bool),
+}
+
+// converts between the times of a chart (the same ones the notes use) and beats, the beat 0 is at `origin`.
+// the sync of the song is not used here, play moves its clock by the sync and not the chart, so the notes,
+// the bpm and the lines all stay on the times of the chart and the sync moves them together
+// the beats count quarter notes like the bpm does, on 6/8 a line of the grid is half a beat
+pub struct TimingMap {
+    origin: f64,
+    segments: Vec<Segment>,
+    pauses: Vec<Pause>,
+    // (beat, numerator, denominator) of every time signature
+    signatures: Vec<(f64, u32, u32)>,
+    // the beats where the grid starts again, a bpm or time signature change that is not on a line starts a new one
+    restarts: Vec<f64>,
+}
+
+impl TimingMap {
+    // the beats of a song start with its first bpm (never before the audio), a song without bpm has no beats
+    pub fn new(song: &Song) -> Option<TimingMap> {
+        let mut bpm_list: Vec<(f64, f64)> = song.bpm.iter().flatten()
+            .filter(|change| change.bpm > 0.0)
+            .map(|change| (change.starting_at, change.bpm))
+            .collect();
+        bpm_list.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (first_time, first_bpm) = *bpm_list.first()?;
+
+        let mut timing = TimingMap {
+            origin: first_time.max(LEAD_IN_MS),
+            segments: vec![Segment { beat: 0.0, time: 0.0, bpm: first_bpm }],
+            pauses: vec![],
+            signatures: vec![],
+            restarts: vec![],
+        };
+
+        // every change gets its beat with the ones before it, so they have to go in the order they happen
+        let mut changes: Vec<(f64, TimingChange)> = bpm_list.iter().skip(1)
+            .map(|(time, bpm)| (*time, TimingChange::Bpm(*bpm)))
+            .chain(song.stops.iter().map(|stop| (stop.starting_at, TimingChange::Stop(stop.duration, stop.delay))))
+            .collect();
+        changes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (time, change) in changes {
+            let beat = timing.time_to_beat(time);
+            match change {
+                TimingChange::Bpm(bpm) => timing.segments.push(Segment { beat, time: timing.unpaused_time(beat), bpm }),
+                TimingChange::Stop(duration, delay) if duration > 0.0 => timing.pauses.push(Pause { beat, duration, delay }),
+                TimingChange::Stop(..) => {},
+            }
+        }
+
+        let mut signatures: Vec<_> = song.time_signatures.iter().collect();
+        signatures.sort_by(|a, b| a.starting_at.total_cmp(&b.starting_at));
+        timing.signatures = signatures.iter()
+            .map(|signature| (timing.time_to_beat(signature.starting_at), signature.numerator.max(1), signature.denominator.max(1)))
+            .collect();
+        timing.find_restarts();
+        Some(timing)
+    }
+
+    // for formats that save the timing in beats, like stepmania. `bpms` are (beat, bpm) and `pauses` are (beat, milliseconds, delay)
+    pub fn from_beats(bpms: &[(f64, f64)], pauses: &[(f64, f64, bool)], origin: f64) -> TimingMap {
+        let mut bpms: Vec<(f64, f64)> = bpms.iter().copied().filter(|(_, bpm)| *bpm > 0.0).collect();
+        bpms.sort_by(|a, b| a.0.total_cmp(&b.0));
+        if bpms.is_empty() {
+            bpms.push((0.0, DEFAULT_BPM));
+        }
+
+        let mut segments: Vec<Segment> = vec![];
+        for (beat, bpm) in bpms {
+            let time = match segments.last() {
+                Some(last) => last.time + (beat - last.beat) * 60000.0 / last.bpm,
+                None => beat * 60000.0 / bpm,
+            };
+            segments.push(Segment { beat, time, bpm });
+        }
+
+        let mut pauses: Vec<Pause> = pauses.iter()
+            .filter(|(_, duration, _)| *duration > 0.0)
+            .map(|(beat, duration, delay)| Pause { beat: *beat, duration: *duration, delay: *delay })
+            .collect();
+        // on the same beat the delay goes first, it is before the notes and the stop after them
+        pauses.sort_by(|a, b| a.beat.total_cmp(&b.beat).then(b.delay.cmp(&a.delay)));
+
+        let mut timing = TimingMap { origin, segments, pauses, signatures: vec![], restarts: vec![] };
+        timing.find_restarts();
+        timing
+    }
+
+    fn find_restarts(&mut self) {
+        self.restarts = self.segments.iter().skip(1).map(|segment| segment.beat)
+            .chain(self.signatures.iter().map(|signature| signature.0))
+            .collect();
+        self.restarts.sort_by(|a, b| a.total_cmp(b));
+    }
+
+    // where the beat 0 is
+    pub fn origin(&self) -> f64 {
+        self.origin
+    }
+
+    pub fn beat_to_time(&self, beat: f64) -> f64 {
+        let paused: f64 = self.pauses.iter()
+            .filter(|pause| pause.beat < beat - SAME_BEAT || (pause.delay && pause.beat <= beat + SAME_BEAT))
+            .map(|pause| pause.duration)
+            .sum();
+        self.origin + self.unpaused_time(beat) + paused
+    }
+
+    // the time inside a pause is still the beat of the pause
+    pub fn time_to_beat(&self, time: f64) -> f64 {
+        let time = time - self.origin;
+        let mut paused = 0.0;
+        for pause in &self.pauses {
+            let start = self.unpaused_time(pause.beat) + paused;
+            if time < start {
+                break;
+            }
+            if time < start + pause.duration {
+                return pause.beat;
+            }
+            paused += pause.duration;
+        }
+        self.unpaused_beat(time - paused)
+    }
+
+    // the closest 1/`division` of a beat, counting from the last place where the grid starts again
+    pub fn snap(&self, time: f64, division: u32) -> f64 {
+        let beat = self.time_to_beat(time);
+        let start = self.restarts.iter().copied().filter(|restart| *restart <= beat + SAME_BEAT).fold(0.0, f64::max);
+        let end = self.restarts.iter().copied().find(|restart| *restart > beat + SAME_BEAT).unwrap_or(f64::INFINITY);
+        let step = 1.0 / division.max(1) as f64;
+        let snapped = start + ((beat - start) / step).round() * step;
+        self.beat_to_time(snapped.min(end))
+    }
+
+    // the number of the measure at that time, the first one is 0
+    pub fn measure_at(&self, time: f64) -> usize {
+        self.lines(time + SAME_TIME_MS).iter().filter(|(_, measure)| *measure).count().saturating_sub(1)
+    }
+
+    // the time of every line of the grid before `until` and if it is the first line of a measure.
+    // there is a line every beat (every 1/`denominator` note with a time signature)
+    pub fn lines(&self, until: f64) -> Vec<(f64, bool)> {
+        let mut lines = vec![];
+        let mut beat = 0.0;
+        let mut signature = None;
+        let mut beat_in_measure = 0;
+
+        loop {
+            let time = self.beat_to_time(beat);
+            if time >= until {
+                break;
+            }
+
+            let current = self.signatures.iter().rposition(|change| change.0 <= beat + SAME_BEAT);
+            if current != signature {
+                signature = current;
+                beat_in_measure = 0;
+            }
+            let (numerator, denominator) = current.map_or((4, 4), |index| (self.signatures[index].1, self.signatures[index].2));
+            lines.push((time, beat_in_measure == 0));
+            beat_in_measure = (beat_in_measure + 1) % numerator;
+
+            let next = beat + 4.0 / denominator as f64;
+            beat = self.restarts.iter().copied()
+                .find(|restart| *restart > beat + SAME_BEAT && *restart < next - SAME_BEAT)
+                .unwrap_or(next);
+        }
+        lines
+    }
+
+    // the (beat, bpm) of every bpm change
+    pub fn bpm_changes(&self) -> Vec<(f64, f64)> {
+        self.segments.iter().map(|segment| (segment.beat, segment.bpm)).collect()
+    }
+
+    pub fn pauses(&self) -> &[Pause] {
+        &self.pauses
+    }
+
+    // the pauses as stops of a chart, each one starts at the moment the beats stop
+    pub fn stops(&self) -> Vec<Stop> {
+        let mut paused = 0.0;
+        let mut stops = vec![];
+        for pause in &self.pauses {
+            stops.push(Stop { starting_at: self.origin + self.unpaused_time(pause.beat) + paused, duration: pause.duration, delay: pause.delay });
+            paused += pause.duration;
+        }
+        stops
+    }
+
+    fn unpaused_time(&self, beat: f64) -> f64 {
+        let segment = self.segments.iter().rev().find(|segment| segment.beat <= beat).unwrap_or(&self.segments[0]);
+        segment.time + (beat - segment.beat) * 60000.0 / segment.bpm
+    }
+
+    fn unpaused_beat(&self, time: f64) -> f64 {
+        let segment = self.segments.iter().rev().find(|segment| segment.time <= time).unwrap_or(&self.segments[0]);
+        segment.beat + (time - segment.time) * segment.bpm / 60000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_song::Bpm;
+
+    #[test]
+    fn beat_to_time_counts_the_stops_before_the_beat() {
+        // 120 bpm is 500 ms a beat, the stop of 1 second is on the beat 2
+        let song = Song {
+            bpm: Some(vec![Bpm { bpm: 120.0, starting_at: 3000.0 }]),
+            stops: vec![Stop { starting_at: 4000.0, duration: 1000.0, delay: false }],
+            ..Song::default()
+        };
+        let timing = TimingMap::new(&song).unwrap();
+
+        assert_eq!(timing.beat_to_time(2.0), 4000.0);
+        assert_eq!(timing.beat_to_time(3.0), 5500.0);
+        assert_eq!(timing.time_to_beat(4500.0), 2.0);
+        assert_eq!(timing.time_to_beat(5250.0), 2.5);
+        assert_eq!(timing.snap(5400.0, 1), 5500.0);
+    }
+
+    #[test]
+    fn a_delay_goes_before_the_notes_of_its_beat() {
+        let stop = TimingMap::from_beats(&[(0.0, 120.0)], &[(4.0, 1000.0, false)], 0.0);
+        let delay = TimingMap::from_beats(&[(0.0, 120.0)], &[(4.0, 1000.0, true)], 0.0);
+
+        assert_eq!(stop.beat_to_time(4.0), 2000.0);
+        assert_eq!(delay.beat_to_time(4.0), 3000.0);
+        assert_eq!(stop.beat_to_time(5.0), delay.beat_to_time(5.0));
+    }
+
+    #[test]
+    fn bpm_changes_keep_the_time_of_the_beats_before_them() {
+        // 4 beats at 120 bpm and then 60 bpm (a second a beat)
+        let timing = TimingMap::from_beats(&[(0.0, 120.0), (4.0, 60.0)], &[], 3000.0);
+
+        assert_eq!(timing.beat_to_time(4.0), 5000.0);
+        assert_eq!(timing.beat_to_time(6.0), 7000.0);
+        assert_eq!(timing.time_to_beat(6000.0), 5.0);
+        assert_eq!(timing.bpm_changes().len(), 2);
+    }
+}
@@ -0,0 +1,205 @@
+use std::fmt;
+
+use crate::{chart::storyboard::EventAction, input::keybutton::Note, load_song::{Song, MIN_EDITOR_HOLD_MS}};
+
+// a hold this long is almost always a mistake of the charter or of an import
+const MAX_HOLD_MS: f64 = 60000.0;
+// notes closer than this are at the same time
+const SAME_TIME_MS: f64 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    // the chart plays, but something looks wrong
+    Warning,
+    // part of the chart can't be played the way it was written
+    Error,
+}
+
+// a problem found on a chart, `location` is the value of the json (like "lanes[0][3]") and `time` where it is on the song
+#[derive(Debug, Clone)]
+pub struct Issue {
+    pub severity: Severity,
+    pub location: String,
+    pub time: Option<f64>,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(severity: Severity, location: String, time: Option<f64>, message: &str) -> Issue {
+        Issue { severity, location, time, message: message.to_owned() }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.time {
+            Some(time) => write!(f, "{}: {} at {:.0} ms: {}", severity, self.location, time, self.message),
+            None => write!(f, "{}: {}: {}", severity, self.location, self.message),
+        }
+    }
+}
+
+// every problem of the chart, the errors first and then by time
+pub fn validate(song: &Song) -> Vec<Issue> {
+    let mut issues = vec![];
+    for (lane, notes) in song.lanes.iter().enumerate() {
+        check_lane(&format!("lanes[{}]", lane), notes, song.end, &mut issues);
+    }
+
+    for (i, change) in song.bpm.iter().flatten().enumerate() {
+        let location = format!("bpm[{}]", i);
+        if change.bpm <= 0.0 || !change.bpm.is_finite() {
+            // after the end it is never used, so it doesn't break anything
+            let severity = if change.starting_at < song.end { Severity::Error } else { Severity::Warning };
+            issues.push(Issue::new(severity, location.clone(), Some(change.starting_at), "the bpm has to be more than 0, the beat lines stop here"));
+        }
+        if i > 0 && song.bpm.as_ref().is_some_and(|list| change.starting_at < list[i - 1].starting_at) {
+            issues.push(Issue::new(Severity::Warning, location, Some(change.starting_at), "the bpm changes are not sorted by time"));
+        }
+    }
+
+    for (i, event) in song.events.iter().enumerate() {
+        if let EventAction::LaneFade { lanes, .. } = &event.action {
+            if let Some(lane) = lanes.iter().find(|lane| **lane >= song.lane_count()) {
+                issues.push(Issue::new(Severity::Warning, format!("events[{}]", i), Some(event.starting_at), &format!("the chart has no lane {}, the fade skips it", lane)));
+            }
+        }
+    }
+
+    issues.sort_by(|a, b| b.severity.cmp(&a.severity).then(a.time.unwrap_or(0.0).total_cmp(&b.time.unwrap_or(0.0))));
+    issues
+}
+
+fn check_lane(name: &str, notes: &[Note], end: f64, issues: &mut Vec<Issue>) {
+    // (index, end of the hold) of the hold that covers the most time until now
+    let mut open_hold: Option<(usize, f64)> = None;
+    let mut order: Vec<usize> = (0..notes.len()).collect();
+    order.sort_by(|a, b| notes[*a].time.total_cmp(&notes[*b].time));
+
+    for (i, note) in notes.iter().enumerate() {
+        let location = format!("{}[{}]", name, i);
+        if !note.time.is_finite() || !note.holding.is_finite() {
+            issues.push(Issue::new(Severity::Error, location, None, "the time is not a number"));
+            continue;
+        }
+        if i > 0 && note.time < notes[i - 1].time {
+            issues.push(Issue::new(Severity::Warning, location.clone(), Some(note.time), "the notes are not sorted by time"));
+        }
+        if note.time < 0.0 {
+            issues.push(Issue::new(Severity::Error, location.clone(), Some(note.time), "the note is before the level starts"));
+        }
+        if note.time + note.holding.max(0.0) > end {
+            issues.push(Issue::new(Severity::Error, location.clone(), Some(note.time), "the note is after the end of the song, the level finishes before it"));
+        }
+        if note.holding < 0.0 {
+            issues.push(Issue::new(Severity::Error, location.clone(), Some(note.time), "the hold has a negative length"));
+        } else if note.holding > MAX_HOLD_MS {
+            issues.push(Issue::new(Severity::Warning, location.clone(), Some(note.time), &format!("the hold lasts {:.0} seconds", note.holding / 1000.0)));
+        } else if note.holding > 0.0 && note.holding < MIN_EDITOR_HOLD_MS {
+            issues.push(Issue::new(Severity::Warning, location, Some(note.time), &format!("the hold is shorter than {:.0} ms, the editor shows it as a single note", MIN_EDITOR_HOLD_MS)));
+        }
+    }
+
+    // the overlaps are found in time order, so an unsorted lane doesn't hide them
+    for (position, index) in order.iter().enumerate() {
+        let note = &notes[*index];
+        if !note.time.is_finite() || !note.holding.is_finite() {
+            continue;
+        }
+        let location = format!("{}[{}]", name, index);
+
+        if let Some(previous) = position.checked_sub(1).map(|previous| order[previous]) {
+            if (note.time - notes[previous].time).abs() < SAME_TIME_MS {
+                issues.push(Issue::new(Severity::Error, location, Some(note.time), &format!("the note is at the same time as {}[{}]", name, previous)));
+                continue;
+            }
+        }
+        match open_hold {
+            Some((hold, hold_end)) if note.time < hold_end - SAME_TIME_MS => {
+                let message = if note.holding > 0.0 { "the hold overlaps" } else { "the tap is inside" };
+                issues.push(Issue::new(Severity::Error, location, Some(note.time), &format!("{} the hold {}[{}]", message, name, hold)));
+            },
+            _ => {},
+        }
+
+        let note_end = note.time + note.holding.max(0.0);
+        if note.holding > 0.0 && open_hold.is_none_or(|(_, hold_end)| note_end > hold_end) {
+            open_hold = Some((*index, note_end));
+        }
+    }
+}
+
+// how many errors and warnings there are
+pub fn count(issues: &[Issue]) -> (usize, usize) {
+    let errors = issues.iter().filter(|issue| issue.severity == Severity::Error).count();
+    (errors, issues.len() - errors)
+}
+
+// the amount of problems in words, like "2 errors, 1 warning"
+pub fn summary(errors: usize, warnings: usize) -> String {
+    let amount = |amount: usize, name: &str| format!("{} {}{}", amount, name, if amount == 1 { "" } else { "s" });
+    match (errors, warnings) {
+        (0, 0) => "no problems".to_owned(),
+        (0, _) => amount(warnings, "warning"),
+        (_, 0) => amount(errors, "error"),
+        _ => format!("{}, {}", amount(errors, "error"), amount(warnings, "warning")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_song::Bpm;
+
+    fn song_with(lane: Vec<Note>) -> Song {
+        Song { lanes: vec![lane], end: 10000.0, bpm: Some(vec![Bpm { bpm: 120.0, starting_at: 0.0 }]), ..Song::default() }
+    }
+
+    #[test]
+    fn a_tap_inside_a_hold_is_an_error() {
+        let song = song_with(vec![Note::new(1000.0, 2000.0), Note::new(2000.0, 0.0)]);
+        let issues = validate(&song);
+
+        assert_eq!(count(&issues), (1, 0));
+        assert_eq!(issues[0].location, "lanes[0][1]");
+        assert!(issues[0].message.contains("lanes[0][0]"));
+    }
+
+    #[test]
+    fn unsorted_notes_still_find_the_overlap() {
+        // the hold is written after the tap, so only the time order sees the tap inside it
+        let song = song_with(vec![Note::new(2000.0, 0.0), Note::new(1000.0, 2000.0)]);
+        let issues = validate(&song);
+
+        assert_eq!(count(&issues), (1, 1));
+        assert_eq!(issues[0].location, "lanes[0][0]");
+        assert_eq!(issues[1].message, "the notes are not sorted by time");
+    }
+
+    #[test]
+    fn a_bpm_of_zero_is_an_error_only_before_the_end() {
+        let song = Song {
+            bpm: Some(vec![Bpm { bpm: 0.0, starting_at: 0.0 }, Bpm { bpm: 0.0, starting_at: 20000.0 }]),
+            ..song_with(vec![])
+        };
+        let issues = validate(&song);
+
+        assert_eq!(count(&issues), (1, 1));
+        assert_eq!(issues[0].location, "bpm[0]");
+        assert_eq!(issues[1].location, "bpm[1]");
+    }
+
+    #[test]
+    fn notes_outside_the_song_and_odd_holds() {
+        let song = song_with(vec![Note::new(-10.0, 0.0), Note::new(1000.0, 100.0), Note::new(9000.0, 2000.0)]);
+        let issues = validate(&song);
+
+        assert_eq!(count(&issues), (2, 1));
+        assert_eq!(summary(2, 1), "2 errors, 1 warning");
+        assert!(issues.iter().any(|issue| issue.location == "lanes[0][1]" && issue.severity == Severity::Warning));
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use crate::{chart::{analysis, autochart::{self, AutoChartOptions}, library::{self, Library}, midi::{self, LaneMapping}, osu, package, song_folder::{self, SONGS_FOLDER}, stepmania, tempo, validate}, load_song::{Song, DEFAULT_LANES}};
+
+// the game can also be started with a command to convert charts without opening the window, for example:
+// arrownier import-osu "path/to/map.osu"
+pub fn run(args: &[String]) -> Result<(), String> {
+    match args {
+        [command, file] if command == "import-osu" => {
+            let folder = osu::import(Path::new(file)).map_err(|err| err.to_string())?;
+            println!("The beatmap was imported in songs/{}", folder);
+            Ok(())
+        },
+        [command, file] if command == "import-sm" => {
+            let folder = stepmania::import(Path::new(file)).map_err(|err| err.to_string())?;
+            println!("The charts were imported in songs/{}", folder);
+            Ok(())
+        },
+        [command, folder, chart @ ..] if command == "export-sm" && chart.len() <= 1 => {
+            let chart = match chart.first() {
+                Some(chart) => chart.clone(),
+                None => Song::charts(folder).first().cloned().ok_or(format!("{} doesn't have any chart", folder))?,
+            };
+            let path = stepmania::export_folder(folder, &chart).map_err(|err| err.to_string())?;
+            println!("The chart was exported to {}", path.to_string_lossy());
+            Ok(())
+        },
+        [command, folder, chart @ ..] if command == "analyze" && chart.len() <= 1 => {
+            let charts = match chart.first() {
+                Some(chart) => vec![chart.clone()],
+                None => Song::charts(folder),
+            };
+            for chart in charts {
+                let song = Song::new(folder, &chart).map_err(|err| format!("{}: {}", chart, err))?;
+                let analysis = analysis::analyze(&song);
+                let patterns = &analysis.patterns;
+                println!("{}: {}K, difficulty {:.1}, id {}", chart, song.lane_count(), analysis.rating, song.chart_id());
+                println!("  {} notes in {:.0} s, {:.1} notes per second, {:.0} at the hardest second", analysis.note_count, analysis.length / 1000.0, analysis.average_nps, analysis.peak_nps);
+                println!("  jacks {}, streams {}, jumps {}, chords {}, holds {} ({:.0} ms held per second)", patterns.jacks, patterns.streams, patterns.jumps, patterns.chords, patterns.holds, analysis.hold_density);
+                let curve: Vec<String> = analysis.nps_curve.iter().map(|nps| format!("{:.0}", nps)).collect();
+                println!("  notes per second: {}", curve.join(" "));
+            }
+            Ok(())
+        },
+        [command, folder, chart @ ..] if command == "validate" && chart.len() <= 1 => {
+            let charts = match chart.first() {
+                Some(chart) => vec![chart.clone()],
+                None => Song::charts(folder),
+            };
+            for chart in charts {
+                let song = Song::new(folder, &chart).map_err(|err| format!("{}: {}", chart, err))?;
+                let issues = song.validate();
+                let (errors, warnings) = validate::count(&issues);
+                println!("{}: {}", chart, validate::summary(errors, warnings));
+                for issue in issues {
+                    println!("  {}", issue);
+                }
+            }
+            Ok(())
+        },
+        [command, file] if command == "import-midi" => {
+            for track in midi::describe_tracks(Path::new(file)).map_err(|err| err.to_string())? {
+                println!("{}", track);
+            }
+            Ok(())
+        },
+        [command, file, track, mapping @ ..] if command == "import-midi" && mapping.len() <= 1 => {
+            let track: usize = track.parse().map_err(|_| format!("{} is not a track number", track))?;
+            let mapping = LaneMapping::parse(mapping.first().map_or("auto", |mapping| mapping.as_str())).map_err(|err| err.to_string())?;
+            let folder = midi::import(Path::new(file), track, &mapping).map_err(|err| err.to_string())?;
+            println!("The track was imported in songs/{}, copy the song audio there as audio.mp3 (or .ogg, .flac, .wav, .opus)", folder);
+            Ok(())
+        },
+        [command, folder, options @ ..] if command == "autochart" && options.len() <= 2 => {
+            let density = match options.first() {
+                Some(density) => density.parse().map_err(|_| format!("{} is not a density, use {} to {}", density, autochart::MIN_DENSITY, autochart::MAX_DENSITY))?,
+                None => 5,
+            };
+            let lanes = match options.get(1) {
+                Some(lanes) => lanes.parse().map_err(|_| format!("{} is not a key mode", lanes))?,
+                None => DEFAULT_LANES,
+            };
+            let chart = autochart::generate_for_folder(folder, &AutoChartOptions { lanes, density }).map_err(|err| err.to_string())?;
+            let song = Song::new(folder, &chart).map_err(|err| err.to_string())?;
+            let analysis = analysis::analyze(&song);
+            println!("The draft was written to songs/{}/{} ({} notes, difficulty {:.1}), open it on the editor to fix it", folder, chart, analysis.note_count, analysis.rating);
+            Ok(())
+        },
+        [command, folder, options @ ..] if command == "detect-bpm" && options.len() <= 2 => {
+            let save = options.last().is_some_and(|option| option == "save");
+            let chart = match options.first().filter(|option| *option != "save") {
+                Some(chart) => chart.clone(),
+                None => Song::charts(folder).first().cloned().ok_or(format!("{} doesn't have any chart", folder))?,
+            };
+            let mut song = Song::new(folder, &chart).map_err(|err| format!("{}: {}", chart, err))?;
+            let bpm_list = tempo::detect_for_chart(folder, &song).map_err(|err| err.to_string())?;
+            if bpm_list.is_empty() {
+                return Err(format!("no beat was found on the audio of {}", folder));
+            }
+            for change in &bpm_list {
+                println!("{} bpm from {:.0} ms", library::bpm_text(change.bpm), change.starting_at);
+            }
+
+            if save {
+                song.bpm = Some(bpm_list);
+                let folder_path = Path::new(SONGS_FOLDER).join(folder);
+                song_folder::write_chart(&folder_path, &chart, &song).map_err(|err| err.to_string())?;
+                println!("The bpm was saved on {}", chart);
+            }
+            Ok(())
+        },
+        [command, file] if command == "import-package" => {
+            let folder = package::import(Path::new(file)).map_err(|err| err.to_string())?;
+            println!("The package was imported in songs/{}", folder);
+            Ok(())
+        },
+        [command, folder] if command == "export-package" => {
+            let path = package::export(folder).map_err(|err| err.to_string())?;
+            println!("The song was exported to {}", path.to_string_lossy());
+            Ok(())
+        },
+        [command, query @ ..] if command == "library" && query.len() <= 1 => {
+            let library = Library::scan();
+            let songs = library.search(query.first().map_or("", |query| query.as_str()));
+            for song in &songs {
+                let Some(chart) = song.main_chart() else { continue };
+                let bpm = chart.max_bpm.map_or("no bpm".to_owned(), |bpm| format!("{} bpm", library::bpm_text(bpm)));
+                let audio = if song.has_audio { "" } else { ", no audio" };
+                println!("{} ({} charts, {}K, {}, {:.0} s, {} notes, difficulty {:.1}{})", song.title, song.charts.len(), chart.lanes, bpm, chart.length / 1000.0, chart.note_count, chart.rating, audio);
+            }
+            println!("{} songs", songs.len());
+            Ok(())
+        },
+        _ => Err(usage()),
+    }
+}
+
+fn usage() -> String {
+    ["usage:",
+     "  arrownier                        open the game",
+     "  arrownier import-osu <file.osu>  import an osu!mania beatmap (1K to 10K) into songs",
+     "  arrownier import-sm <file.sm>    import the dance-single charts of a .sm or .ssc file into songs",
+     "  arrownier export-sm <folder> [charts/name.json]",
+     "                                   write a chart of a song folder as a .sm file inside that folder",
+     "  arrownier import-midi <file.mid> list the tracks of a midi file",
+     "  arrownier import-midi <file.mid> <track> [auto[N] | pitch=lane,...]",
+     "                                   import a midi track, lanes are left, up, bottom and right or 1 to 10",
+     "  arrownier autochart <folder> [density 1-10] [lanes]",
+     "                                   make a draft chart from the audio of a song folder as charts/auto-<density>.json",
+     "  arrownier detect-bpm <folder> [charts/name.json] [save]",
+     "                                   find the bpm and first beat of the audio of a song folder, with save it is written on the chart",
+     "  arrownier import-package <file.arrow>  unpack a song package into songs",
+     "  arrownier export-package <folder>      pack a song folder as exports/<folder>.arrow",
+     "  arrownier library [text]         list the songs, or the ones with the text on the title, charter or tags",
+     "  arrownier analyze <folder> [charts/name.json]",
+     "                                   show the difficulty, notes per second and patterns of the charts of a song folder",
+     "  arrownier validate <folder> [charts/name.json]",
+     "                                   list the problems of the charts of a song folder"].join("\n")
+}
@@ -1,6 +1,6 @@
 use std::time::{Duration, Instant};
 use sdl2::{pixels::Color, ttf::Font, event::Event, keyboard::Keycode};
-use crate::{app::{App, AppState, GameState}, game_object::GameObject, input::{button_module::{Button, TextAlign}, keybutton::KeyButton}, key::GameKey};
+use crate::{app::{App, AppState, GameState}, game_object::GameObject, input::{button_module::{Button, TextAlign}, keybutton::KeyButton}, key::{GameKey, LaneColor}};
 
  pub struct GameLogic { // here we define the data we use on our script
     last_frame: Instant,
@@ -17,7 +17,7 @@ use crate::{app::{App, AppState, GameState}, game_object::GameObject, input::{bu
 impl GameLogic {
     // this is called once
     pub fn new(app: &mut App) -> Self {
-        let calibration_note = GameKey::new(GameObject {active: true, x: ((app.width/2) - 75) as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, 0 as i128, None, None, false);
+        let calibration_note = GameKey::new(GameObject {active: true, x: ((app.width/2) - 75) as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, 0.0, None, None, false);
 
         // UI ELEMENT
         let timer = Button::new(GameObject {active: true, x:(app.width - 40) as f32, y: 10.0, width: 0.0, height: 0.0},Some(String::from("Timer")),Color::RGB(100, 100, 100),Color::WHITE,Color::RGB(0, 200, 0),Color::RGB(0, 0, 0),None,TextAlign::Center);
@@ -46,8 +46,8 @@ impl GameLogic {
 
         // timer
         let elapsed_time = self.start_time.elapsed();
-        let milliseconds = elapsed_time.as_millis() / 10;
-        self.timer.text = Some(format!("{}", milliseconds));
+        let milliseconds = elapsed_time.as_secs_f64() * 1000.0;
+        self.timer.text = Some(format!("{:.0}", milliseconds));
         self.timer.render(&mut app.canvas, &texture_creator, &_font); 
         self.enter_timer.render(&mut app.canvas, &texture_creator, &_font); 
         self.out_timer.render(&mut app.canvas, &texture_creator, &_font); 
@@ -55,10 +55,10 @@ impl GameLogic {
         // buttons 
         let mut key_buttons = [&self.key_up];
         for button_key in key_buttons.iter_mut() {
-            button_key.render(app, 0);
+            button_key.render(app, LaneColor::Red);
         }
 
-        Self::handle_notes(&mut self.started, &mut self.enter_timer, &mut self.out_timer, &mut self.calibration_note, milliseconds, delta_time, self.canvas_height, &mut app_state, app);
+        Self::handle_notes(self, milliseconds, delta_time, app_state, app);
         Self::event_handler(&mut app_state,&mut event_pump, app);
     }
 
@@ -75,31 +75,26 @@ impl GameLogic {
         }
     }
 
-    fn handle_notes(started: &mut bool, enter_timer: &mut Button, out_timer: &mut Button, note: &mut GameKey, milliseconds: u128, delta_time: Duration, height: u32, app_state: &mut AppState, app: &mut App) {
-        let inside: bool;
-        if note.mili < milliseconds.try_into().unwrap() {
-            note.render(app);
-            note.update(delta_time, app.coordination_data.key_speed);         
+    fn handle_notes(&mut self, milliseconds: f64, delta_time: Duration, app_state: &mut AppState, app: &mut App) {
+        if self.calibration_note.mili < milliseconds {
+            self.calibration_note.render(app);
+            self.calibration_note.update(delta_time, app.coordination_data.key_speed);         
 
-            if (note.game_object.y > (height - 160) as f32) && (note.game_object.y < (height - 90) as f32) {
-                inside = true;
-            } else {
-                inside = false;
-            }
+            let inside = (self.calibration_note.game_object.y > (self.canvas_height - 160) as f32) && (self.calibration_note.game_object.y < (self.canvas_height - 90) as f32);
 
-            if inside && *started {
-                *started = false;
-                enter_timer.text_color = Color::RED;
-                app.coordination_data.base_time = milliseconds as i128;
-                enter_timer.text = Some(format!("{}", app.coordination_data.base_time));
-            } else if !inside && *started == false {
-                *started = true;
-                out_timer.text_color = Color::RED;
-                app.coordination_data.end_time = milliseconds as i128;
-                out_timer.text = Some(format!("{}", app.coordination_data.end_time));
+            if inside && self.started {
+                self.started = false;
+                self.enter_timer.text_color = Color::RED;
+                app.coordination_data.base_time = milliseconds;
+                self.enter_timer.text = Some(format!("{:.0}", app.coordination_data.base_time));
+            } else if !inside && !self.started {
+                self.started = true;
+                self.out_timer.text_color = Color::RED;
+                app.coordination_data.end_time = milliseconds;
+                self.out_timer.text = Some(format!("{:.0}", app.coordination_data.end_time));
             }
 
-            if app.coordination_data.end_time > 0 && milliseconds >= (app.coordination_data.end_time + 50).try_into().unwrap() {
+            if app.coordination_data.end_time > 0.0 && milliseconds >= app.coordination_data.end_time + 500.0 {
                 app_state.state = GameState::MainMenu;
             }
         }
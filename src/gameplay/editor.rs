@@ -1,6 +1,16 @@
-use std::fs;
-use sdl2::{pixels::Color, ttf::Font, event::Event, keyboard::Keycode};
-use crate::{app::{App, AppState, GameState, Testing}, game_object::GameObject, input::{button_module::{Button, TextAlign}, keybutton::{KeyButton, Note}, slider_module::Slider_input}, key::{GameKey, KeyFlag}, load_song::{Bpm, Song}};
+use std::{fs, sync::mpsc::{self, Receiver, TryRecvError}, thread};
+use sdl2::{pixels::Color, rect::Rect, ttf::Font, event::Event, keyboard::Keycode, mouse::MouseButton};
+use crate::{app::{App, AppState, GameState, Testing}, chart::{error::ChartError, library, lyrics::Lyrics, sections::{self, SECTION_NAMES}, storyboard::Storyboard, tempo, timing::TimingMap, validate::{self, Issue}}, game_object::GameObject, gameplay::play::StoryboardImages, input::{button_module::{Button, TextAlign}, keybutton::{KeyButton, Note, NoteKind}, slider_module::Slider_input}, key::{lane_x, GameKey, KeyFlag, LaneColor}, load_song::{Bpm, Song, EDITOR_STEP_MS, MAX_LANES}};
+
+// the parts of a beat the new keys can snap to, 0 is without snapping (4 parts are 1/16 notes)
+const SNAP_DIVISIONS: [u32; 7] = [0, 1, 2, 3, 4, 6, 8];
+// the lyric markers only show the start of the line, the whole line doesn't fit next to the lanes
+const LYRIC_MARKER_CHARS: usize = 30;
+// the preview of the storyboard on the top left, with the size of a 16:9 screen
+const PREVIEW_WIDTH: u32 = 256;
+const PREVIEW_HEIGHT: u32 = 144;
+// a click this close to a section removes it instead of adding a new one
+const SECTION_CLICK_MS: f64 = 50.0;
 
 pub struct AddHolding {
     pub can_add: bool,
@@ -13,10 +23,9 @@ pub struct Selected {
 }
 
 pub struct GameLogic { // here we define the data we use on our script
-    key_left: KeyButton,
-    key_up: KeyButton,
-    key_bottom: KeyButton,
-    key_right: KeyButton,
+    // the key mode of the chart, `keys` has a list for every lane and the beat lines after them
+    lane_count: usize,
+    lane_buttons: Vec<KeyButton>,
     song_game: Option<Song>,
     keys: Vec<Vec<GameKey>>,
     start_index: u128,
@@ -31,23 +40,31 @@ pub struct GameLogic { // here we define the data we use on our script
     start_point:  f64,
     scroll_slider: Slider_input,
     error: bool,
-    end: u128
+    error_text: Button,
+    end: u128,
+    // the kind the new keys get, the kind button also changes the selected key
+    place_kind: NoteKind,
+    // the beats of the song, a song without bpm can't snap
+    timing: Option<TimingMap>,
+    // the start_index the measure button was counted for, counting the measures goes over every line before it
+    measure_index: Option<u128>,
+    snap: usize,
+    // shown next to the lanes where every line starts, with the times of the chart
+    lyrics: Option<Lyrics>,
+    // the events of the chart, they are shown on the right of the lanes and previewed at the time of the bottom of the screen
+    storyboard: Storyboard,
+    storyboard_images: StoryboardImages,
+    // while it is on a click on the timeline adds a section with the chosen name, or removes the one clicked
+    adding_section: bool,
+    section_name: usize,
+    // the bpm detection decodes the whole audio, so it runs on its own thread and the result comes here when it finishes
+    detecting_bpm: Option<Receiver<Result<Vec<Bpm>, ChartError>>>,
 } 
 
 impl GameLogic {
     // this is called once
     pub fn new(app: &mut App,  app_state: &mut AppState,  _font: &Font,) -> Self {
-        let mut song_game: Song = Song {
-            name: String::from(""),
-            id: Some(0),
-            left_keys: vec![],
-            up_keys: vec![],
-            bottom_keys: vec![],
-            right_keys: vec![],
-            end: 0,
-            sync: Some(0),
-            bpm: Some(vec![Bpm { bpm: 0, starting_at: 0 }])
-        };
+        let mut song_game: Song = Song::default();
 
         let start_index = 0;
         let mut error = false;
@@ -55,21 +72,45 @@ impl GameLogic {
         app.alert_message = String::from("");
         app.paused = false;
         let mut keys = vec![];
+        let mut timing = None;
+        let mut issues = vec![];
+        let mut lyrics = None;
+        let mut storyboard = Storyboard::new(&[]);
+        let mut storyboard_images = StoryboardImages::default();
 
         match &app_state.song_folder {
             Some(folder) => {
-                match Song::new(folder) {
+                // the editor is where charts get saved again, so old charts are rewritten in the current format here
+                match Song::upgrade_file(folder, &app_state.song_chart) {
+                    Ok(true) => println!("{} was upgraded to the current chart format", folder),
+                    Ok(false) => {},
+                    Err(err) => eprintln!("The chart of {} couldn't be upgraded: {}", folder, err),
+                }
+
+                match Song::new(folder, &app_state.song_chart) {
                     Ok(song) => {
                         song_game = song.clone();
                         keys = song_game.clone().get_keys(app, true);
-                        end = song_game.end.clone();
+                        end = Song::editor_spaces(song_game.end) as u128;
+                        timing = TimingMap::new(&song_game);
+                        issues = song_game.validate();
+                        print_issues(&issues);
+                        storyboard = Storyboard::new(&song_game.events);
+                        storyboard_images = StoryboardImages::load(folder, &storyboard, &app.texture_creator);
+                        lyrics = Lyrics::load_for_chart(folder, &song_game).unwrap_or_else(|err| {
+                            eprintln!("The lyrics of {} didn't loaded right: {}", folder, err);
+                            None
+                        });
                     },
-                    Err(_) => {
+                    Err(err) => {
+                        eprintln!("The chart of {} didn't loaded right: {}", folder, err);
+                        app.alert_message = format!("the song didn't loaded right, {}", err);
                         error = true;
                     },
                 }
             },
             None => {
+                app.alert_message = String::from("the song didn't loaded right");
                 error = true;
             },
         }
@@ -89,6 +130,8 @@ impl GameLogic {
 
         //let mut keys = ;
 
+        let error_text = Button::new(GameObject {active: true, x: 0.0, y: 0.0, width: app.width as f32, height: app.height as f32}, Some(app.alert_message.clone()), Color::RGBA(0, 0, 0, 200), Color::WHITE, Color::RGBA(0, 200, 0,0), Color::RGBA(0, 0, 0,0), None, TextAlign::Center);
+
         let save = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 10.0, width: 100.0, height: 40.0}, Some(String::from("save")), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
         let play = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 60.0, width: 100.0, height: 40.0}, Some(String::from("play")), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
         
@@ -96,19 +139,21 @@ impl GameLogic {
         let add_holding_key = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 180.0, width: 100.0, height: 40.0}, Some(String::from("Add Hold")), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
         let time_position = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 300.0, width: 100.0, height: 40.0}, Some(String::from("000")), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
         let add_testing_start = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 230.0, width: 100.0, height: 40.0}, Some(String::from("Add start")), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
+        let note_kind = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 350.0, width: 100.0, height: 40.0}, Some(NoteKind::Normal.name().to_owned()), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
+        let problems = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 450.0, width: 100.0, height: 40.0}, Some(problems_text(&issues)), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
+        let snap = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 400.0, width: 100.0, height: 40.0}, Some(snap_name(SNAP_DIVISIONS[0])), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
+        let key_mode = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 500.0, width: 100.0, height: 40.0}, Some(format!("{}K", song_game.lane_count())), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
+        let add_section = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 600.0, width: 100.0, height: 40.0}, Some(String::from("Add section")), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
+        let section_name = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 650.0, width: 100.0, height: 40.0}, Some(SECTION_NAMES[0].to_owned()), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
+        let detect_bpm = Button::new(GameObject { active: true, x:(app.width - 110) as f32, y: 550.0, width: 100.0, height: 40.0}, Some(String::from("Detect BPM")), Color::RGB(100, 100, 100), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(0, 0, 0),None, TextAlign::Center);
 
         // controlers 
-        let key_left = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) - 195) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0},Color::RGB(200, 50, 100));
-        let key_up = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) - 95) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
-        let key_bottom = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) + 5) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
-        let key_right = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) + 105) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
-
+        let lane_count = song_game.lane_count();
+        let lane_buttons = lane_buttons(app, lane_count);
 
         Self {
-            key_left,
-            key_up,
-            key_bottom,
-            key_right,
+            lane_count,
+            lane_buttons,
             song_game: Some(song_game),
             keys,
             start_index,
@@ -116,14 +161,25 @@ impl GameLogic {
             note_spaces_mod: 5.0,
             index_range: 200,
             selected_object: None,
-            buttons: vec![save, play, add_single_key, add_holding_key, add_testing_start, time_position],
+            buttons: vec![save, play, add_single_key, add_holding_key, add_testing_start, time_position, note_kind, snap, problems, key_mode, detect_bpm, add_section, section_name],
             changing_start: false,
             add_key: false,
             add_holding: AddHolding { can_add: false, add: false },
             start_point: 300.0,
             scroll_slider,
             error,
-            end
+            error_text,
+            end,
+            place_kind: NoteKind::Normal,
+            timing,
+            measure_index: None,
+            snap: 0,
+            lyrics,
+            storyboard,
+            storyboard_images,
+            adding_section: false,
+            section_name: 0,
+            detecting_bpm: None,
         }
     }
 
@@ -132,11 +188,33 @@ impl GameLogic {
         let texture_creator = app.canvas.texture_creator();
 
         self.buttons[4].text = Some(self.start_index.to_string());
+        self.poll_bpm(app);
+        if self.measure_index != Some(self.start_index) {
+            if let Some(timing) = &self.timing {
+                self.buttons[5].text = Some(format!("measure {}", timing.measure_at(self.start_index as f64 * EDITOR_STEP_MS) + 1));
+            }
+            self.measure_index = Some(self.start_index);
+        }
         match app_state.song_folder {
             Some(_) => {
                 if self.error {
-                    app.reseted = false;
-                    app_state.state = GameState::Playing;
+                    // there is no chart to edit, so the only thing to do is going back to the song list
+                    self.error_text.render(&mut app.canvas, &texture_creator, _font);
+                    for event in event_pump.poll_iter() {
+                        match event {
+                            Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                                app_state.state = GameState::SelectingSong;
+                            },
+                            Event::KeyDown { keycode: Some(key_value), .. } if key_value == Keycode::from_i32(app.play_keys[0]).unwrap() || key_value == Keycode::from_i32(app.play_keys[3]).unwrap() => {
+                                app_state.state = GameState::SelectingSong;
+                            },
+                            Event::Quit { .. } => {
+                                app_state.is_running = false;
+                            },
+                            _ => {}
+                        }
+                    }
+                    return;
                 } else {
                     app.canvas.set_draw_color(Color::RGBA(29, 91, 88, 100));
                     app.canvas.clear();
@@ -147,7 +225,8 @@ impl GameLogic {
                     
                     
 
-                    for list in [4,0,1,2,3] {
+                    // the beat lines are the last list, they go first so the keys are drawn over them
+                    for list in std::iter::once(self.lane_count).chain(0..self.lane_count) {
                         let mut note_spaces = 0.0;
                         let temp_list = self.start_index as usize ..(self.start_index as usize + self.index_range as usize);
                         if self.start_index < self.end - self.index_range as u128 {
@@ -170,12 +249,14 @@ impl GameLogic {
                             self.start_index = self.end - self.index_range as u128 - 10
                         }
 
-                        let mut key_buttons = [&self.key_left, &self.key_up, &self.key_right, &self.key_bottom];
-                        for (i, button_key) in key_buttons.iter_mut().enumerate() {
-                            button_key.render(app, i);
+                        for (lane, button_key) in self.lane_buttons.iter().enumerate() {
+                            button_key.render(app, LaneColor::of(lane, self.lane_count));
                         }
                         // self.scroll_slider.render(app, _font);
                     }
+                    self.render_lyrics(app, _font);
+                    self.render_storyboard(app, _font);
+                    self.render_sections(app, _font);
                 }
     
                 Self::event_handler(self, app_state, event_pump, app);
@@ -184,6 +265,144 @@ impl GameLogic {
         }
     }
 
+    // a mark on the left of the lanes at the space where every line of the lyrics starts, with the start of the line
+    fn render_lyrics(&self, app: &mut App, font: &Font) {
+        let Some(lyrics) = &self.lyrics else { return };
+        let texture_creator = app.canvas.texture_creator();
+        let space_height = 1.4 * self.note_spaces_mod;
+        let left = lane_x(app.width, 0, self.lane_count) - 60;
+
+        for line in &lyrics.lines {
+            let space = Song::editor_space(line.time) as u128;
+            if space < self.start_index || space >= self.start_index + self.index_range as u128 {
+                continue;
+            }
+            let y = app.height as f32 - space_height * ((space - self.start_index) as f32 + 0.5);
+            app.canvas.set_draw_color(Color::RGB(250, 189, 47));
+            app.canvas.fill_rect(Rect::new(left - 40, y as i32 - 1, 40, 3)).unwrap();
+
+            let text = line.text();
+            let text = match text.char_indices().nth(LYRIC_MARKER_CHARS) {
+                Some((index, _)) => format!("{}...", &text[..index]),
+                None => text,
+            };
+            let marker = Button::new(GameObject {active: true, x: 10.0, y: y - 20.0, width: 0.0, height: 0.0}, Some(text), Color::RGBA(0, 0, 0, 0), Color::RGB(250, 189, 47), Color::RGBA(0, 0, 0, 0), Color::RGBA(0, 0, 0, 0), None, TextAlign::Left);
+            marker.render(&mut app.canvas, &texture_creator, font);
+        }
+    }
+
+    // a mark on the right of the lanes for every event, and a small screen with how the level looks at the bottom of the timeline.
+    // the play button shows the events as they are while playing
+    fn render_storyboard(&self, app: &mut App, font: &Font) {
+        if self.storyboard.is_empty() {
+            return;
+        }
+        let texture_creator = app.canvas.texture_creator();
+        let space_height = 1.4 * self.note_spaces_mod;
+        let right = lane_x(app.width, self.lane_count - 1, self.lane_count) + 60;
+        let text_button = |x: f32, y: f32, text: String| Button::new(GameObject {active: true, x, y, width: 0.0, height: 0.0}, Some(text), Color::RGBA(0, 0, 0, 0), Color::RGB(131, 165, 152), Color::RGBA(0, 0, 0, 0), Color::RGBA(0, 0, 0, 0), None, TextAlign::Left);
+
+        for event in self.storyboard.events() {
+            let space = Song::editor_space(event.starting_at) as u128;
+            if space < self.start_index || space >= self.start_index + self.index_range as u128 {
+                continue;
+            }
+            let y = app.height as f32 - space_height * ((space - self.start_index) as f32 + 0.5);
+            app.canvas.set_draw_color(Color::RGB(131, 165, 152));
+            app.canvas.fill_rect(Rect::new(right, y as i32 - 1, 40, 3)).unwrap();
+            text_button((right + 45) as f32, y - 20.0, event.action.label()).render(&mut app.canvas, &texture_creator, font);
+        }
+
+        let time = self.start_index as f64 * EDITOR_STEP_MS;
+        let look = self.storyboard.state_at(time, self.lane_count);
+        let screen = Rect::new(10, 10, PREVIEW_WIDTH, PREVIEW_HEIGHT);
+        match self.storyboard_images.get(&look.background).or(app.textures.background.as_ref()) {
+            Some(texture) => app.canvas.copy(texture, None, Some(screen)).expect("Failed to copy texture into canvas"),
+            None => {
+                app.canvas.set_draw_color(Color::RGB(40, 40, 40));
+                app.canvas.fill_rect(screen).unwrap();
+            },
+        }
+        if look.dim > 0.0 {
+            app.canvas.set_draw_color(Color::RGBA(0, 0, 0, (look.dim * 255.0) as u8));
+            app.canvas.fill_rect(screen).unwrap();
+        }
+        // the lanes as bars at the bottom of the preview, as see-through as they are on the level
+        let lane_width = PREVIEW_WIDTH / (self.lane_count as u32 + 2);
+        for (lane, opacity) in look.lane_opacity.iter().enumerate() {
+            app.canvas.set_draw_color(Color::RGBA(200, 50, 100, (opacity * 255.0) as u8));
+            app.canvas.fill_rect(Rect::new(10 + (lane_width * (lane as u32 + 1)) as i32, 10 + PREVIEW_HEIGHT as i32 - 30, lane_width - 4, 20)).unwrap();
+        }
+        if let Some((color, amount)) = look.flash {
+            app.canvas.set_draw_color(Color::RGBA(color[0], color[1], color[2], (amount * 255.0) as u8));
+            app.canvas.fill_rect(screen).unwrap();
+        }
+        app.canvas.set_draw_color(Color::WHITE);
+        app.canvas.draw_rect(screen).unwrap();
+
+        let on_off = |on: Option<bool>| match on { Some(false) => "off", _ => "on" };
+        let text = format!("{:.0} ms, bars {}, circle {}", time, on_off(look.bars), on_off(look.circle));
+        text_button(10.0, (PREVIEW_HEIGHT + 15) as f32, text).render(&mut app.canvas, &texture_creator, font);
+    }
+
+    // a line over the lanes where every section starts, with its name on the left
+    fn render_sections(&self, app: &mut App, font: &Font) {
+        let Some(song) = &self.song_game else { return };
+        let texture_creator = app.canvas.texture_creator();
+        let space_height = 1.4 * self.note_spaces_mod;
+        let left = lane_x(app.width, 0, self.lane_count) - 50;
+        let right = lane_x(app.width, self.lane_count - 1, self.lane_count) + 50;
+
+        for section in &song.sections {
+            let space = Song::editor_space(section.starting_at) as u128;
+            if space < self.start_index || space >= self.start_index + self.index_range as u128 {
+                continue;
+            }
+            let y = app.height as f32 - space_height * ((space - self.start_index) as f32 + 0.5);
+            app.canvas.set_draw_color(Color::RGB(211, 134, 155));
+            app.canvas.fill_rect(Rect::new(left, y as i32 - 1, (right - left) as u32, 2)).unwrap();
+            let name = Button::new(GameObject {active: true, x: (left + 5) as f32, y: y - 24.0, width: 0.0, height: 0.0}, Some(section.name.clone()), Color::RGBA(0, 0, 0, 0), Color::RGB(211, 134, 155), Color::RGBA(0, 0, 0, 0), Color::RGBA(0, 0, 0, 0), None, TextAlign::Left);
+            name.render(&mut app.canvas, &texture_creator, font);
+        }
+    }
+
+    // adds a section where the timeline was clicked, or removes the one that is there.
+    // it is done once for the click with the time of the cursor, every list of keys (and the wide beat lines) is under it
+    fn place_section(&mut self, app: &App, x: i32, y: i32) {
+        let left = lane_x(app.width, 0, self.lane_count) - 50;
+        let right = lane_x(app.width, self.lane_count - 1, self.lane_count) + 50;
+        if x < left || x > right || self.start_index >= self.end - self.index_range as u128 {
+            return;
+        }
+
+        // the space whose line is the closest to the cursor, the same lines render_sections draws
+        let space_height = 1.4 * self.note_spaces_mod as f64;
+        let space = ((app.height as f64 - y as f64) / space_height - 0.5).round().clamp(0.0, self.index_range as f64 - 1.0);
+        let key = self.start_index as f64 + space;
+        let time = match &self.timing {
+            Some(timing) if SNAP_DIVISIONS[self.snap] > 0 => timing.snap(key * EDITOR_STEP_MS, SNAP_DIVISIONS[self.snap]),
+            _ => key * EDITOR_STEP_MS,
+        };
+        if let Some(song) = &mut self.song_game {
+            if !sections::remove_near(&mut song.sections, time, SECTION_CLICK_MS) {
+                sections::add(&mut song.sections, time, SECTION_NAMES[self.section_name]);
+            }
+        }
+    }
+
+    // moves the timeline and the start of the tests to the next section, or to the start of the current one (the one before if it is already there)
+    fn jump_to_section(&mut self, forward: bool) {
+        let Some(song) = &self.song_game else { return };
+        let sorted = sections::sorted(&song.sections);
+        let time = self.start_index as f64 * EDITOR_STEP_MS;
+        let target = if forward { sections::next(&sorted, time) } else { sections::previous(&sorted, time) };
+        if let Some(target) = target {
+            let space = Song::editor_space(sorted[target].starting_at);
+            self.start_index = space as u128;
+            self.start_point = space as f64;
+        }
+    }
+
     fn event_handler(&mut self, app_state: &mut AppState, event_pump: &mut sdl2::EventPump, app: &mut App) {
         for event in event_pump.poll_iter() {
             match event {
@@ -201,23 +420,9 @@ impl GameLogic {
                     match &self.selected_object {
                         Some(selected) => {
                             match selected.flag {
-                                KeyFlag::Left => {
-                                    println!("left");
-                                    let empty_note = GameKey::new(GameObject {active: true, x: ((app.width/2) - 160) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, 0 as i128, None, None, false);
-                                    delete_key(&selected, 0, empty_note, &mut self.keys);
-                                },
-                                KeyFlag::Up => {
-                                    println!("up");
-                                    let empty_note = GameKey::new(GameObject {active: true, x: ((app.width/2) - 60) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, 0 as i128, None, None, false);
-                                    delete_key(&selected, 1, empty_note, &mut self.keys);
-                                },
-                                KeyFlag::Bottom => {
-                                    let empty_note = GameKey::new(GameObject {active: true, x: ((app.width/2) + 40) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, 0 as i128, None, None, false);
-                                    delete_key(&selected, 2, empty_note, &mut self.keys);
-                                },
-                                KeyFlag::Right => {
-                                    let empty_note = GameKey::new(GameObject {active: true, x: ((app.width/2) + 140) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, 0 as i128, None, None, false);
-                                    delete_key(&selected, 3, empty_note, &mut self.keys);
+                                KeyFlag::Lane(lane, _) => {
+                                    let empty_note = GameKey::new(GameObject {active: true, x: (lane_x(app.width, lane, self.lane_count) - 10) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, 0.0, None, None, false);
+                                    delete_key(selected, lane, empty_note, &mut self.keys);
                                 },
                                 KeyFlag::Bpm => {},
                             }
@@ -226,33 +431,25 @@ impl GameLogic {
                         None => {},
                     }
                 }
-                Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
-                    match &self.song_game {
-                        Some(song_game) => {
-                            if self.start_index < song_game.end - 150 {
-                                self.start_index += 100;
-                            }
-                        }
-                        None => {}
-                    }
+                Event::KeyDown { keycode: Some(Keycode::Up), .. } if self.start_index + 150 < self.end => {
+                    self.start_index += 100;
                 }
-                Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
-                    if self.start_index > 100 {
-                        self.start_index -= 100;
-                    }
+                Event::KeyDown { keycode: Some(Keycode::Down), .. } if self.start_index > 100 => {
+                    self.start_index -= 100;
+                }
+                Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => {
+                    self.jump_to_section(true);
+                }
+                Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => {
+                    self.jump_to_section(false);
                 }
                 Event::MouseWheel { y, .. } => {
-                    match &self.song_game {
-                        Some(song_game) => {
-                            if y == 1 && self.start_index < song_game.end - 200 {
-                                self.start_index += 10;
-                            } else if y == -1 && self.start_index > 10 {
-                                self.start_index -= 10;
-                            } else if y == -1 && self.start_index < 10 {
-                                self.start_index = 0;
-                            }
-                        }
-                        None => {}
+                    if y == 1 && self.start_index + 200 < self.end {
+                        self.start_index += 10;
+                    } else if y == -1 && self.start_index > 10 {
+                        self.start_index -= 10;
+                    } else if y == -1 && self.start_index < 10 {
+                        self.start_index = 0;
                     }
                 }
                 Event::Quit { .. } => {
@@ -268,7 +465,7 @@ impl GameLogic {
             if self.buttons[1].on_click(&event) { // play
                 match &self.song_game {
                     Some(_song) => {
-                        app.testing_song = Some(Testing{song: self.generate_array(), start_point: self.start_point - app.coordination_data.base_time as f64});
+                        app.testing_song = Some(Testing{song: self.generate_array(), start_point: self.start_point * EDITOR_STEP_MS - app.coordination_data.base_time});
                     },
                     None => {},
                 }
@@ -297,6 +494,55 @@ impl GameLogic {
             }
 
 
+            if self.buttons[6].on_click(&event) { // note kind
+                self.place_kind = self.place_kind.next();
+                self.buttons[6].text = Some(self.place_kind.name().to_owned());
+
+                if let Some(selected) = &self.selected_object {
+                    let list = selected.flag.lane_index().unwrap_or(self.lane_count);
+                    self.keys[list][selected.key].kind = self.place_kind;
+                    if let Some(con) = self.keys[list][selected.key].connected {
+                        self.keys[list][con as usize].kind = self.place_kind;
+                    }
+                }
+            }
+
+            if self.buttons[8].on_click(&event) { // problems
+                let issues = self.generate_array().validate();
+                print_issues(&issues);
+                self.buttons[8].text = Some(problems_text(&issues));
+            }
+
+            if self.buttons[9].on_click(&event) { // key mode
+                self.change_key_mode(app);
+            }
+
+            if self.buttons[10].on_click(&event) { // detect bpm
+                self.detect_bpm(app_state);
+            }
+
+            if self.buttons[11].on_click(&event) { // add section
+                self.adding_section = !self.adding_section;
+                self.buttons[11].toggle = Some(self.adding_section);
+            }
+
+            if self.buttons[12].on_click(&event) { // section name
+                self.section_name = (self.section_name + 1) % SECTION_NAMES.len();
+                self.buttons[12].text = Some(SECTION_NAMES[self.section_name].to_owned());
+            }
+
+            if self.buttons[7].on_click(&event) { // snap
+                self.snap = (self.snap + 1) % SNAP_DIVISIONS.len();
+                self.buttons[7].text = Some(snap_name(SNAP_DIVISIONS[self.snap]));
+            }
+
+            if let Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } = event {
+                if self.adding_section {
+                    self.place_section(app, x, y);
+                }
+            }
+
+            let lane_count = self.lane_count;
             for (i, list) in self.keys.iter_mut().enumerate() {
                 if self.start_index < self.end - self.index_range as u128 {
                     for key in self.start_index as usize..self.start_index as usize + self.index_range as usize {
@@ -349,15 +595,25 @@ impl GameLogic {
                                 None => {},
                             }
 
+                            // the click only places the section (place_section), it doesn't add or select keys
+                            if self.adding_section {
+                                continue;
+                            }
+
                             if self.add_key {
-                                if i == 0 {
-                                    list[key] = GameKey::new(GameObject {active: true, x: ((app.width/2) - 175) as f32, y: 0.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, 0 as i128, Some(KeyFlag::Left), None, false);
-                                } else if i == 1 {
-                                    list[key] = GameKey::new(GameObject {active: true, x: ((app.width/2) - 75) as f32, y: 0.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, 0 as i128, Some(KeyFlag::Up), None, false);
-                                } else if i == 2 {
-                                    list[key] = GameKey::new(GameObject {active: true, x: ((app.width/2) + 25) as f32, y: 0.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, 0 as i128, Some(KeyFlag::Bottom), None, false);
-                                } else if i == 3 {
-                                    list[key] = GameKey::new(GameObject {active: true, x: ((app.width/2) + 125) as f32, y: 0.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, 0 as i128, Some(KeyFlag::Right), None, false);
+                                // with snapping the key goes to the closest part of the beat instead of the clicked space
+                                let (space, time) = match &self.timing {
+                                    Some(timing) if SNAP_DIVISIONS[self.snap] > 0 => {
+                                        let time = timing.snap(key as f64 * EDITOR_STEP_MS, SNAP_DIVISIONS[self.snap]);
+                                        (Song::editor_space(time).min(list.len() - 1), time)
+                                    },
+                                    _ => (key, key as f64 * EDITOR_STEP_MS),
+                                };
+
+                                // the last list is the beat lines, the keys only go on the lanes
+                                if i < lane_count {
+                                    list[space] = GameKey::new(GameObject {active: true, x: (lane_x(app.width, i, lane_count) - 25) as f32, y: 0.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, time, Some(KeyFlag::lane(i, lane_count)), None, false);
+                                    list[space].kind = self.place_kind;
                                 }
                             }
 
@@ -403,72 +659,115 @@ impl GameLogic {
     fn generate_array(&mut self) -> Song {
         match &self.song_game {
             Some(song) => {
-                let mut left_keys = vec![];
-                let mut up_keys = vec![];
-                let mut bottom_keys = vec![];
-                let mut right_keys = vec![];
+                let mut lanes: Vec<Vec<Note>> = vec![vec![]; self.lane_count];
                 
                 for list in &self.keys {
-                    for key in 0..song.end as usize {
-                        match &list[key].flag {
-                            Some(flag) => {
-                                match list[key].connected {
-                                    Some(con_value) => {
-                                        if key < con_value as usize {
-                                            match flag {
-                                                KeyFlag::Left => {
-                                                    left_keys.push(Note { time: key as u128, holding: con_value as u128 - key as u128});
-                                                },
-                                                KeyFlag::Up => {
-                                                    up_keys.push(Note { time: key as u128, holding: con_value - key as u128});
-                                                },
-                                                KeyFlag::Bottom => {
-                                                    bottom_keys.push(Note { time: key as u128, holding: con_value - key as u128});
-                                                },
-                                                KeyFlag::Right => {
-                                                    right_keys.push(Note { time: key as u128, holding: con_value - key as u128});
-                                                },
-                                                KeyFlag::Bpm => {},
-                                            }
-                                        }
-                                    },
-                                    None => {
-                                        match flag {
-                                            KeyFlag::Left => {
-                                                left_keys.push(Note { time: key as u128, holding: 0});
-                                            },
-                                            KeyFlag::Up => {
-                                                up_keys.push(Note { time: key as u128, holding: 0});
-                                            },
-                                            KeyFlag::Bottom => {
-                                                bottom_keys.push(Note { time: key as u128, holding: 0});
-                                            },
-                                            KeyFlag::Right => {
-                                                right_keys.push(Note { time: key as u128, holding: 0});
-                                            },
-                                            KeyFlag::Bpm => {},
-                                        }
-                                    },
-                                }
-                            },
-                            None => {},
+                    // keys that weren't moved keep their exact time, moved or new keys take the time of their space
+                    let time_of = |key: usize| -> f64 {
+                        if Song::editor_space(list[key].mili) == key { list[key].mili } else { key as f64 * EDITOR_STEP_MS }
+                    };
+
+                    for (key, game_key) in list.iter().enumerate() {
+                        if let Some(lane) = game_key.flag.and_then(|flag| flag.lane_index()) {
+                            match game_key.connected {
+                                Some(con_value) => {
+                                    if key < con_value as usize {
+                                        lanes[lane].push(Note { time: time_of(key), holding: time_of(con_value as usize) - time_of(key), kind: game_key.kind });
+                                    }
+                                },
+                                None => {
+                                    lanes[lane].push(Note { time: time_of(key), holding: 0.0, kind: game_key.kind });
+                                },
+                            }
                         }
                     }
                 }
                 
                 
 
-                let edited_song = Song { name: song.name.clone(), id: song.id.clone(), left_keys, up_keys, bottom_keys, right_keys, end: song.end, sync: song.sync, bpm: song.bpm.clone()};
+                let edited_song = Song { lanes, ..song.clone() };
                 return edited_song;
             },
             None => {
-                return Song { name: " ".to_owned(), id: Some(0), left_keys: vec![], up_keys: vec![], bottom_keys: vec![], right_keys: vec![], end: 0, sync: Some(0), bpm: Some(vec![Bpm { bpm: 0, starting_at: 0 }]) };
+                Song { name: " ".to_owned(), ..Song::default() }
+            },
+        }
+    }
+
+    // goes to the next key mode, the modes that would remove a lane with notes are skipped (after 10K it goes back to the fewest lanes the notes fit in)
+    fn change_key_mode(&mut self, app: &mut App) {
+        let mut edited_song = self.generate_array();
+        let used = edited_song.lanes.iter().rposition(|lane| !lane.is_empty()).map_or(1, |last| last + 1);
+        let lane_count = (self.lane_count % MAX_LANES + 1).max(used);
+
+        edited_song.lanes.resize(lane_count, vec![]);
+        self.lane_count = lane_count;
+        self.lane_buttons = lane_buttons(app, lane_count);
+        self.keys = edited_song.clone().get_keys(app, true);
+        self.song_game = Some(edited_song);
+        self.selected_object = None;
+        self.buttons[9].text = Some(format!("{}K", lane_count));
+    }
+
+    // starts looking for the bpm on the audio, the editor keeps working while it does and poll_bpm uses the result
+    fn detect_bpm(&mut self, app_state: &AppState) {
+        let Some(folder) = app_state.song_folder.clone() else { return };
+        if self.detecting_bpm.is_some() {
+            return;
+        }
+        let song = self.generate_array();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            // the editor could be closed before it finishes, then nobody is waiting for the result
+            let _ = sender.send(tempo::detect_for_chart(&folder, &song));
+        });
+        self.detecting_bpm = Some(receiver);
+        self.buttons[10].text = Some("Detecting...".to_owned());
+    }
+
+    // replaces the bpm of the chart with the one found on the audio, it is only written on the file when the chart is saved
+    fn poll_bpm(&mut self, app: &mut App) {
+        let Some(receiver) = &self.detecting_bpm else { return };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.detecting_bpm = None;
+                self.buttons[10].text = Some("No beat".to_owned());
+                return;
+            },
+        };
+        self.detecting_bpm = None;
+
+        // the chart is taken again, the notes could have changed while the audio was read
+        let mut edited_song = self.generate_array();
+        match result {
+            Ok(bpm_list) if !bpm_list.is_empty() => {
+                for change in &bpm_list {
+                    println!("detected {} bpm from {:.0} ms", library::bpm_text(change.bpm), change.starting_at);
+                }
+                self.buttons[10].text = Some(format!("{} BPM", library::bpm_text(bpm_list[0].bpm)));
+                edited_song.bpm = Some(bpm_list);
+                self.timing = TimingMap::new(&edited_song);
+                self.measure_index = None;
+                self.keys = edited_song.clone().get_keys(app, true);
+                self.song_game = Some(edited_song);
+                self.selected_object = None;
+            },
+            Ok(_) => self.buttons[10].text = Some("No beat".to_owned()),
+            Err(err) => {
+                eprintln!("The bpm couldn't be detected: {}", err);
+                self.buttons[10].text = Some("No audio".to_owned());
             },
         }
     }
 
     fn save(&mut self, app_state: &mut AppState) {
         let edited_song = self.generate_array();
+        // the chart is saved anyway, the problems are only shown so they can be fixed
+        let issues = edited_song.validate();
+        print_issues(&issues);
+        self.buttons[8].text = Some(problems_text(&issues));
         save_json(&edited_song, app_state).err();
     }
 }
@@ -478,13 +777,35 @@ fn save_json(song: &Song, app_state: &mut AppState) -> Result<(), Box<dyn std::e
     match &app_state.song_folder {
         Some(folder) => {
             println!("{}", folder);
-            fs::write("songs/".to_owned() + folder + "/data.json", json_string.to_string())?;
+            fs::write(Song::chart_path(folder, &app_state.song_chart), &json_string)?;
         },
         None => {},
     }
     Ok(())
 }
 
+fn problems_text(issues: &[Issue]) -> String {
+    let (errors, warnings) = validate::count(issues);
+    validate::summary(errors, warnings)
+}
+
+fn lane_buttons(app: &mut App, lane_count: usize) -> Vec<KeyButton> {
+    (0..lane_count)
+        .map(|lane| KeyButton::new(app, GameObject {active: true, x: (lane_x(app.width, lane, lane_count) - 45) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100)))
+        .collect()
+}
+
+fn print_issues(issues: &[Issue]) {
+    for issue in issues {
+        println!("{}", issue);
+    }
+}
+
+// 4 parts of a beat are 1/16 notes
+fn snap_name(division: u32) -> String {
+    if division == 0 { "No snap".to_owned() } else { format!("Snap 1/{}", division * 4) }
+}
+
 fn delete_key(selected: &Selected, index: usize, empty_note: GameKey, keys: &mut Vec<Vec<GameKey>>) {
     match keys[index][selected.key].connected{
         Some(connected) => {
@@ -1,42 +1,24 @@
 use std::{fs::{File, OpenOptions}, io::{Read, Write}, sync::MutexGuard, time::{Duration, Instant}};
 use sdl2::{event::Event, keyboard::Keycode, mixer::{self, Music}, pixels::Color, rect::{Point, Rect}, render::Canvas, sys::KeyCode, ttf::Font, video::Window};
 use serde_json::value;
-use crate::{app::{App, AppState, GameState}, game_object::GameObject, input::{button_module::{Button, TextAlign}, keybutton::KeyButton}, key::{GameKey, KeyFlag}, load_song::{Bpm, Song}};
+use crate::{app::{App, AppState, GameState}, chart::{migration, song_folder}, game_object::GameObject, input::{button_module::{Button, TextAlign}, keybutton::KeyButton}, key::{lane_x, GameKey, LaneColor}, load_song::{Song, DEFAULT_LANES, EDITOR_STEP_MS, LEAD_IN_MS}};
 
 const NUM_BARS: usize = 20;
 
-#[derive(Clone,Debug,Copy)]
-pub struct Note {
-    pub state: bool,
-    pub active: bool
-}
-
-pub struct KeyState {
-    pub left: Note,
-    pub top: Note,
-    pub bottom: Note,
-    pub right: Note
-}
-
 pub struct GameLogic<'a> { // here we define the data we use on our script
     last_frame: Instant,
     pub start_time: Instant,
     canvas_height: u32,
-    key_left: KeyButton,
-    key_up: KeyButton,
-    key_bottom: KeyButton,
-    key_right: KeyButton,
-    key_state: KeyState,
+    // a key for every lane of the chart
+    keys: Vec<KeyButton>,
     song_keys: Option<Vec<Vec<GameKey>>>,
-    song_sync: i128,
-    maked_song: Song,
+    song_sync: f64,
     started_song: bool,
     started_level: bool,
     song: Option<Music<'a>>,
-    points: u128,
     paused_time: Duration,
     error: bool,
-    song_end: u128,
+    song_end: f64,
     end: bool,
     frame_count: u32,
     frame_timer: Duration,
@@ -50,46 +32,32 @@ impl GameLogic<'_> {
     pub fn new(app: &mut App,  app_state: &mut AppState) -> Self {
         let mut song = None;
         let mut song_keys = None;
-        let mut song_sync = 0;
-        let mut song_end = 0;
+        let mut song_sync = 0.0;
+        let mut song_end = 0.0;
+        let mut lane_count = DEFAULT_LANES;
         let mut error = false;
         app.alert_message = String::from("");
         app.paused = false;
 
         match &app_state.song_folder {
             Some(folder) => { 
-                match mixer::Music::from_file("./songs/".to_owned() + folder + "/audio.mp3") {
-                    Ok(song_ok) => song = Some(song_ok),
-                    Err(_) => {
-                        eprintln!("The song didn't loaded right for some reason: {}", folder);
-                        app.alert_message = String::from("the song audio didn't loaded right");
-                        app.paused = true;
-                        error = true;
-                    },
-                }
-
-                match &app.testing_song {
+                let audio_file = match &app.testing_song {
                     Some(testing) => {
-                        song_keys = Some(testing.song.clone().get_keys(app, false));
+                        let testing_song = testing.song.clone();
+                        let audio_file = testing_song.metadata.audio_file.clone();
+                        lane_count = testing_song.lane_count();
+                        song_keys = Some(testing_song.get_keys(app, false));
+                        audio_file
                     },
                     None => {
-                        let mut song_game: Song = Song {
-                            name: String::from(""),
-                            id: Some(0),
-                            left_keys: vec![],
-                            up_keys: vec![],
-                            bottom_keys: vec![],
-                            right_keys: vec![],
-                            end: 0,
-                            sync: Some(0), 
-                            bpm: Some(vec![Bpm { bpm: 0, starting_at: 0 }])
-                        };
-                        match Song::new(folder) {
+                        let mut song_game: Song = Song::default();
+                        match Song::new(folder, &app_state.song_chart) {
                             Ok(song) => {
                                 song_game = song
                             },
-                            Err(_) => {
-                                app.alert_message = String::from("the song didn't loaded right");
+                            Err(err) => {
+                                eprintln!("The chart of {} didn't loaded right: {}", folder, err);
+                                app.alert_message = format!("the song didn't loaded right, {}", err);
                                 app.paused = true;
                                 error = true;
                             },
@@ -103,7 +71,24 @@ impl GameLogic<'_> {
                         }
 
                         song_end = song_game.end;
+                        let audio_file = song_game.metadata.audio_file.clone();
+                        lane_count = song_game.lane_count();
                         song_keys = Some(song_game.get_keys(app, false));
+                        audio_file
+                    },
+                };
+
+                // the audio is loaded after the chart because the chart metadata can say which file it is
+                match song_folder::find_audio(folder, audio_file.as_ref()).map(mixer::Music::from_file) {
+                    Some(Ok(song_ok)) => song = Some(song_ok),
+                    _ => {
+                        eprintln!("The song didn't loaded right for some reason: {}", folder);
+                        // a broken chart is a better reason to show than the audio
+                        if !error {
+                            app.alert_message = String::from("the song audio didn't loaded right");
+                        }
+                        app.paused = true;
+                        error = true;
                     },
                 }
             },
@@ -116,10 +101,9 @@ impl GameLogic<'_> {
 
 
         // controlers 
-        let key_left = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) - 195) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0},Color::RGB(200, 50, 100));
-        let key_up = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) - 95) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
-        let key_bottom = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) + 5) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
-        let key_right = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) + 105) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
+        let keys: Vec<KeyButton> = (0..lane_count)
+            .map(|lane| KeyButton::new(app, GameObject {active: true, x: (lane_x(app.width, lane, lane_count) - 45) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0},Color::RGB(200, 50, 100)))
+            .collect();
 
         // keys
         let mut ctrl_string = "".to_owned();
@@ -145,25 +129,18 @@ impl GameLogic<'_> {
         ctrl_string += &(", ESCAPE-[Back]");
 
         // buttons
-        let key_state = KeyState { left: Note { state: false, active: true }, top: Note { state: false, active: true }, bottom: Note { state: false, active: true }, right: Note { state: false, active: true }};
         let calibration = Button::new(GameObject { active: true, x:0 as f32, y: 10.0, width: app.width as f32, height: 0.0}, Some(String::from("Calibration")), Color::RGB(100, 100, 100),Color::WHITE, Color::RGB(0, 200, 0), Color::RGB(0, 0, 0), None, TextAlign::Center);
         let controller = Button::new(GameObject { active: true, x:0 as f32, y: 40.0, width: app.width as f32, height: 0.0}, Some(String::from(ctrl_string)), Color::RGB(100, 100, 100),Color::WHITE, Color::RGB(0, 200, 0), Color::RGB(0, 0, 0), None, TextAlign::Center);
 
         Self {
             last_frame: Instant::now(),
             start_time: Instant::now(),
-            key_left,
-            key_up,
-            key_bottom,
-            key_right,
-            key_state,
+            keys,
             song_keys,
             canvas_height: app.height,
-            maked_song: Song { name: "Test".to_owned(), id: Some(0), left_keys: vec![], up_keys: vec!(), bottom_keys: vec![], right_keys: vec![], end: 0, sync: Some(0), bpm: Some(vec![Bpm { bpm: 0, starting_at: 0 }])},
             started_song: true,
             started_level: false,
             song,
-            points: 0,
             paused_time: Duration::new(0, 0),
             error,
             song_end,
@@ -181,23 +158,22 @@ impl GameLogic<'_> {
     pub fn update(&mut self, _font: &Font, mut app_state: &mut AppState, mut event_pump: &mut sdl2::EventPump, app: &mut App) {
         let delta_time = self.delta_time();
         let elapsed_time = self.start_time.elapsed() - self.paused_time;
-        let mut milliseconds = 0;
                 
         app.canvas.set_draw_color(Color::RGBA(40, 40, 40, 100));
         app.canvas.clear();
 
-        match &app.testing_song {
+        let milliseconds = match &app.testing_song {
             Some(_song) => {
-                milliseconds = ((elapsed_time.as_millis() / 10) - (if app.paused_time > 0 { app.paused_time } else { 1 }) / 10) + ((_song.start_point) + 300.0) as u128
+                (elapsed_time.as_secs_f64() * 1000.0 - app.paused_time as f64) + _song.start_point + LEAD_IN_MS
             },
             None => {
-                milliseconds = (elapsed_time.as_millis() / 10) - (if app.paused_time > 0 { app.paused_time } else { 1 }) / 10
+                elapsed_time.as_secs_f64() * 1000.0 - app.paused_time as f64
             },
-        }
+        };
 
-        let mut key_buttons = [&self.key_left, &self.key_up, &self.key_right, &self.key_bottom];
-        for (i, button_key) in key_buttons.iter_mut().enumerate() {
-            button_key.render(app, i);
+        let lane_count = self.keys.len();
+        for (lane, button_key) in self.keys.iter().enumerate() {
+            button_key.render(app, LaneColor::of(lane, lane_count));
         }
 
         match self.song_keys {
@@ -215,7 +191,7 @@ impl GameLogic<'_> {
 
         self.controller.render(&mut app.canvas, &app.texture_creator, &_font); 
 
-        if milliseconds >= 300 && self.started_song == true {
+        if milliseconds >= LEAD_IN_MS && self.started_song {
             println!("{}", self.started_song);
             match app_state.state {
                 GameState::SongCalibration => {
@@ -242,28 +218,29 @@ impl GameLogic<'_> {
         for event in event_pump.poll_iter() {
             match event {
                 sdl2::event::Event::KeyDown { keycode: Some(key_value), .. } if key_value == Keycode::from_i32(app.play_keys[1]).unwrap() => {
-                    self.song_sync -= 1;
+                    self.song_sync -= EDITOR_STEP_MS;
                 },
                 sdl2::event::Event::KeyDown { keycode: Some(key_value), .. } if key_value == Keycode::from_i32(app.play_keys[2]).unwrap() => {
-                    self.song_sync += 1;
+                    self.song_sync += EDITOR_STEP_MS;
                 },
                 sdl2::event::Event::KeyDown { keycode: Some(Keycode::Space), .. } => {
-                    self.song_sync = 0;
+                    self.song_sync = 0.0;
                 },
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. }  => {
                     match &app_state.song_folder {
                         Some(folder) => {
-                            let mut song_file = File::open("songs/".to_owned() + &folder + "/data.json").expect("The json didn't loaded correctly");
+                            let mut song_file = File::open(Song::chart_path(folder, &app_state.song_chart)).expect("The json didn't loaded correctly");
                             let mut json_string = String::new();
                             song_file.read_to_string(&mut json_string).expect("the json wasn't correctly read");
         
-                            let mut song: Song = serde_json::from_str(&json_string).expect("The data wasnt read correctly");
+                            // going through the migration means old charts are saved back in the current format
+                            let (mut song, _) = migration::migrate(&json_string).expect("The data wasnt read correctly");
         
                             song.sync = Some(self.song_sync);
         
                             let modified_json_string = serde_json::to_string_pretty(&song).expect("Something went wrong");
         
-                            let mut file = OpenOptions::new().write(true).truncate(true).open("songs/".to_owned() + &folder + "/data.json").expect("Something went wrong");
+                            let mut file = OpenOptions::new().write(true).truncate(true).open(Song::chart_path(folder, &app_state.song_chart)).expect("Something went wrong");
                             file.write_all(modified_json_string.as_bytes()).expect("Something went wrong");
         
                             Self::reset(app, app_state);
@@ -301,27 +278,21 @@ impl GameLogic<'_> {
         app_state.state = GameState::SelectingSong;
     }
 
-    fn handle_notes(&mut self, milliseconds: u128, delta_time: Duration, app: &mut App) {
+    fn handle_notes(&mut self, milliseconds: f64, delta_time: Duration, app: &mut App) {
         
 
         if let Some(song_keys) = &mut self.song_keys {
-            for key_index in 0..4 {
+            for (actual_key, lane_notes) in self.keys.iter_mut().zip(song_keys.iter_mut()) {
                 let mut inside = false;
-                let actual_key = match key_index {
-                    0 => &mut self.key_left,
-                    1 => &mut self.key_up,
-                    2 => &mut self.key_bottom,
-                    _ => &mut self.key_right,
-                };
 
                 let mut remove: Vec<usize> = Vec::new(); // Collect indices of notes to remove
 
-                for (i, note) in song_keys[key_index].iter_mut().enumerate() {
+                for (i, note) in lane_notes.iter_mut().enumerate() {
                     if note.game_object.y > app.width as f32 {
                         remove.push(i);
                     }
 
-                    if note.mili - (app.coordination_data.base_time - self.song_sync) < milliseconds.try_into().unwrap() {
+                    if note.mili - (app.coordination_data.base_time - self.song_sync) < milliseconds {
                         note.render(app);
                         note.update(delta_time, app.coordination_data.key_speed);
 
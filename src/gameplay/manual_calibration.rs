@@ -1,6 +1,6 @@
 use std::time::{Duration, Instant};
 use sdl2::{event::Event, keyboard::Keycode, mixer::{self, Music}, pixels::Color, render::Canvas, ttf::Font, video::Window};
-use crate::{app::{self, App, AppState, CoordinationData, GameState}, game_object::GameObject, input::{button_module::{Button, TextAlign}, keybutton::KeyButton}, key::GameKey};
+use crate::{app::{App, AppState, GameState}, game_object::GameObject, input::{button_module::{Button, TextAlign}, keybutton::KeyButton}, key::{GameKey, LaneColor}};
 
  pub struct GameLogic<'a> { // here we define the data we use on our script
     last_frame: Instant,
@@ -27,12 +27,12 @@ impl GameLogic<'_> {
         }
 
         let mut calibration_notes: Vec<GameKey> = vec![];
-        let mut milis = 300;
+        let mut milis = 3000.0;
 
         for note in 0..100 {
-            let calibration_note = GameKey::new(GameObject {active: true, x: ((app.width/2) - 75) as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, milis as i128, None, None, false);
+            let calibration_note = GameKey::new(GameObject {active: true, x: ((app.width/2) - 75) as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), app.coordination_data.key_speed, milis, None, None, false);
             calibration_notes.push(calibration_note);
-            milis += 400
+            milis += 4000.0
         }
 
         // UI ELEMENT
@@ -78,19 +78,19 @@ impl GameLogic<'_> {
 
         // timer
         let elapsed_time = self.start_time.elapsed();
-        let milliseconds = elapsed_time.as_millis() / 10;
-        self.timer.text = Some(format!("{}", milliseconds));
+        let milliseconds = elapsed_time.as_secs_f64() * 1000.0;
+        self.timer.text = Some(format!("{:.0}", milliseconds));
         self.timer.render(&mut app.canvas, &texture_creator, &_font); 
         self.enter_timer.render(&mut app.canvas, &texture_creator, &_font); 
         self.out_timer.render(&mut app.canvas, &texture_creator, &_font); 
 
-        self.calibration.text = Some(app.coordination_data.base_time.to_string());
+        self.calibration.text = Some(format!("{:.0}", app.coordination_data.base_time));
         self.calibration.render(&mut app.canvas, &texture_creator, &_font); 
 
         // buttons 
         let mut key_buttons = [&self.key_up];
         for button_key in key_buttons.iter_mut() {
-            button_key.render(app, 0);
+            button_key.render(app, LaneColor::Red);
         }
 
         Self::handle_notes(self, delta_time, milliseconds, app);
@@ -101,14 +101,14 @@ impl GameLogic<'_> {
         for event in event_pump.poll_iter() {
             match event { 
                 sdl2::event::Event::KeyDown { keycode: Some(key_value), .. } if key_value == Keycode::from_i32(app.play_keys[1]).unwrap() => {
-                    if app.coordination_data.base_time > 10 {
-                        app.coordination_data.base_time -= 10;
+                    if app.coordination_data.base_time > 100.0 {
+                        app.coordination_data.base_time -= 100.0;
                     } else {
-                        app.coordination_data.base_time = 0;
+                        app.coordination_data.base_time = 0.0;
                     }
                 },
                 sdl2::event::Event::KeyDown { keycode: Some(key_value), .. } if key_value == Keycode::from_i32(app.play_keys[2]).unwrap() => {
-                    app.coordination_data.base_time += 10;
+                    app.coordination_data.base_time += 100.0;
                 },
                 Event::Quit { .. } | Event::KeyDown { keycode: Some(Keycode::Escape), .. }  => {
                     mixer::Music::pause();
@@ -119,10 +119,11 @@ impl GameLogic<'_> {
         }
     }
 
-    pub fn handle_notes(&mut self, delta_time: Duration, milliseconds: u128,  app: &mut App) {
+    pub fn handle_notes(&mut self, delta_time: Duration, milliseconds: f64,  app: &mut App) {
         let inside: bool;
         
-        if milliseconds % 300 == 0 {
+        // the calibration audio is restarted every 3 seconds
+        if (milliseconds as u128) % 3000 < 10 {
             match &self.song {
                 Some(song) => {
                     if self.started == false {
@@ -134,7 +135,7 @@ impl GameLogic<'_> {
         }
 
         for key in self.calibration_notes.iter_mut() {
-            if key.mili - app.coordination_data.base_time < milliseconds.try_into().unwrap() {
+            if key.mili - app.coordination_data.base_time < milliseconds {
                 key.render(app);
                 key.update(delta_time, app.coordination_data.key_speed);         
 
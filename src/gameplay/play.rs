@@ -1,8 +1,18 @@
-use std::{sync::MutexGuard, time::{Duration, Instant}};
-use sdl2::{event::Event, keyboard::Keycode, mixer::{self, Music}, pixels::Color, rect::{Point, Rect}, render::Canvas, ttf::Font, video::Window};
-use crate::{app::{App, AppState, GameState}, game_object::{self, GameObject}, input::{button_module::{Button, TextAlign}, keybutton::KeyButton}, key::GameKey, load_song::{Bpm, Song}};
+use std::{collections::HashMap, path::Path, sync::MutexGuard, time::{Duration, Instant}};
+use sdl2::{event::Event, image::LoadTexture, keyboard::Keycode, mixer::{self, Music}, pixels::Color, rect::{Point, Rect}, render::{Canvas, Texture, TextureCreator, TextureQuery}, ttf::Font, video::{Window, WindowContext}};
+use crate::{app::{App, AppState, GameState}, chart::{lyrics::Lyrics, modifiers::Modifiers, scores::{self, Score}, scroll::ScrollTimeline, sections::{self, Section}, song_folder::{self, SONGS_FOLDER}, storyboard::Storyboard}, game_object::GameObject, input::{button_module::{Button, TextAlign}, keybutton::{KeyButton, NoteKind}}, key::{lane_x, GameKey, LaneColor}, load_song::{Song, DEFAULT_LANES, LEAD_IN_MS}};
 
 const NUM_BARS: usize = 20;
+// points taken when the player hits a mine
+const MINE_PENALTY: u128 = 300;
+// the longest time between taps that keeps a roll going
+const ROLL_TAP_MS: u128 = 250;
+// the first line of the lyrics (or the one after a pause of the singing) shows up this long before it is sung
+const LYRICS_PREVIEW_MS: f64 = 3000.0;
+// space between the current line of the lyrics and the next one
+const LYRICS_LINE_SPACE: f32 = 35.0;
+// a jump to a section goes this long before it, so its first notes can be seen coming
+const SECTION_LEAD_MS: f64 = 2000.0;
 
 #[derive(Clone,Debug,Copy)]
 pub struct Note {
@@ -10,24 +20,17 @@ pub struct Note {
     pub active: bool
 }
 
-pub struct KeyState {
-    pub left: Note,
-    pub top: Note,
-    pub bottom: Note,
-    pub right: Note
-}
 
 pub struct GameLogic<'a> { // here we define the data we use on our script
     last_frame: Instant,
     pub start_time: Instant,
     canvas_height: u32,
-    key_left: KeyButton,
-    key_up: KeyButton,
-    key_bottom: KeyButton,
-    key_right: KeyButton,
-    key_state: KeyState,
+    // a key for every lane of the chart from the left, with the keyboard key that presses it
+    keys: Vec<KeyButton>,
+    lane_keys: Vec<i32>,
+    key_state: Vec<Note>,
     song_keys: Option<Vec<Vec<GameKey>>>,
-    song_sync: i128,
+    song_sync: f64,
     maked_song: Song,
     started_song: bool,
     started_level: bool,
@@ -39,7 +42,7 @@ pub struct GameLogic<'a> { // here we define the data we use on our script
     paused_time: Duration,
     error: bool,
     error_elements: Vec<Button>,
-    song_end: u128,
+    song_end: f64,
     end: bool,
     frame_count: u32,
     frame_timer: Duration,
@@ -49,8 +52,24 @@ pub struct GameLogic<'a> { // here we define the data we use on our script
     max_combo: u32,
     actual_button: usize,
     ui_texts: Vec<Button>,
-    bpm_timer: Instant,
-    bpm_bars: Vec<BeatLine>
+    // the notes are placed with it every frame, so the scroll velocity of the chart changes how they move but not when they are hit
+    scroll: ScrollTimeline,
+    // the id of the chart as it is on the file, None when the score is not saved (the tests of the editor or a broken chart)
+    chart_id: Option<String>,
+    modifiers: Modifiers,
+    seed: u64,
+    // with the times of the chart, None when the song has no lyrics
+    lyrics: Option<Lyrics>,
+    storyboard: Storyboard,
+    storyboard_images: StoryboardImages,
+    // sorted by time, PageUp and PageDown jump between them
+    sections: Vec<Section>,
+    // the notes as they were when the level started, a jump back to a section needs the notes that were already hit
+    all_keys: Option<Vec<Vec<GameKey>>>,
+    // milliseconds added to the clock by the jumps to the sections
+    time_shift: f64,
+    // a play with jumps is a practice, its score is not saved
+    practice: bool,
 } 
 
 impl GameLogic<'_> {
@@ -59,46 +78,45 @@ impl GameLogic<'_> {
         let benchmark = Instant::now();
         let mut song = None;
         let mut song_keys = None;
-        let mut song_sync = 0;
-        let mut song_end = 0;
+        let mut song_sync = 0.0;
+        let mut song_end = 0.0;
+        let mut scroll_velocity = vec![];
+        let mut lane_count = DEFAULT_LANES;
         let mut error = false;
+        let mut chart_id = None;
+        let mut lyrics = None;
+        let mut events = vec![];
+        let mut chart_sections = vec![];
+        let seed = rand::random::<u64>();
         app.alert_message = String::from("");
         app.paused = false;
 
         match &app_state.song_folder {
             Some(folder) => { 
-                match mixer::Music::from_file("./songs/".to_owned() + folder + "/audio.mp3") {
-                    Ok(song_ok) => song = Some(song_ok),
-                    Err(_) => {
-                        eprintln!("The song didn't loaded right for some reason: {}", folder);
-                        app.alert_message = String::from("the song audio didn't loaded right");
-                        app.paused = true;
-                        error = true;
-                    },
-                }
-
-                match &app.testing_song {
+                let audio_file = match &app.testing_song {
                     Some(testing) => {
-                        song_keys = Some(testing.song.clone().get_keys(app, false));
+                        let testing_song = testing.song.clone();
+                        let audio_file = testing_song.metadata.audio_file.clone();
+                        scroll_velocity = testing_song.scroll_velocity.clone();
+                        lane_count = testing_song.lane_count();
+                        lyrics = load_lyrics(folder, &testing_song);
+                        events = testing_song.events.clone();
+                        chart_sections = sections::sorted(&testing_song.sections);
+                        song_end = testing_song.end;
+                        song_keys = Some(testing_song.get_keys(app, false));
+                        audio_file
                     },
                     None => {
-                        let mut song_game: Song = Song {
-                            name: String::from(""),
-                            id: Some(0),
-                            left_keys: vec![],
-                            up_keys: vec![],
-                            bottom_keys: vec![],
-                            right_keys: vec![],
-                            end: 0,
-                            sync: Some(0),
-                            bpm: Some(vec![Bpm { bpm: 0, starting_at: 0 }])
-                        };
-                        match Song::new(folder) {
+                        let mut song_game: Song = Song::default();
+                        match Song::new(folder, &app_state.song_chart) {
                             Ok(song) => {
-                                song_game = song
+                                // the id is taken before the modifiers, they are saved apart with the score
+                                chart_id = Some(song.chart_id());
+                                song_game = app.modifiers.apply(&song, seed);
                             },
-                            Err(_) => {
-                                app.alert_message = String::from("the song didn't loaded right");
+                            Err(err) => {
+                                eprintln!("The chart of {} didn't loaded right: {}", folder, err);
+                                app.alert_message = format!("the song didn't loaded right, {}", err);
                                 app.paused = true;
                                 error = true;
                             },
@@ -112,7 +130,28 @@ impl GameLogic<'_> {
                         }
 
                         song_end = song_game.end;
+                        let audio_file = song_game.metadata.audio_file.clone();
+                        scroll_velocity = song_game.scroll_velocity.clone();
+                        lane_count = song_game.lane_count();
+                        lyrics = load_lyrics(folder, &song_game);
+                        events = song_game.events.clone();
+                        chart_sections = sections::sorted(&song_game.sections);
                         song_keys = Some(song_game.get_keys(app, false));
+                        audio_file
+                    },
+                };
+
+                // the audio is loaded after the chart because the chart metadata can say which file it is
+                match song_folder::find_audio(folder, audio_file.as_ref()).map(mixer::Music::from_file) {
+                    Some(Ok(song_ok)) => song = Some(song_ok),
+                    _ => {
+                        eprintln!("The song didn't loaded right for some reason: {}", folder);
+                        // a broken chart is a better reason to show than the audio
+                        if !error {
+                            app.alert_message = String::from("the song audio didn't loaded right");
+                        }
+                        app.paused = true;
+                        error = true;
                     },
                 }
             },
@@ -123,6 +162,12 @@ impl GameLogic<'_> {
             },
         }
 
+        let storyboard = Storyboard::new(&events);
+        let storyboard_images = match &app_state.song_folder {
+            Some(folder) => StoryboardImages::load(folder, &storyboard, &app.texture_creator),
+            None => StoryboardImages::default(),
+        };
+
         // UI ELEMENT
         let ui_points = Button::new(GameObject { active: true, x:((app.width/2) - 70 ) as f32, y: 10.0, width: 140.0, height: 30.0}, Some(String::from("Points")),Color::RGB(200, 100, 100), Color::WHITE, Color::RGB(200, 10, 0), Color::RGB(200, 0, 0),None, TextAlign::Center);
         let timer = Button::new(GameObject {active: true, x:10 as f32, y: 30.0, width: 0.0, height: 0.0},Some(String::from("Timer")),Color::RGB(100, 100, 100),Color::WHITE,Color::RGB(0, 200, 0),Color::RGB(0, 0, 0),None, TextAlign::Left);
@@ -144,36 +189,36 @@ impl GameLogic<'_> {
         let back_to_menu = Button::new(GameObject {active: true, x:((app.width/2) - (160/2)) as f32, y: (app.height - (app.height / 2) + 100) as f32, width: 160.0, height: 50.0},Some(String::from("Back to menu")),Color::RGBA(0, 0, 0, 200),Color::WHITE,Color::RGBA(0, 200, 0,0),Color::RGBA(0, 0, 0,0),None, TextAlign::Center);
 
         // UI LISTS
-        let ui_elements = vec![ui_points, timer, framerate];
+        let section_name = Button::new(GameObject {active: true, x: 10.0, y: (app.height - 35) as f32, width: 0.0, height: 0.0},None,Color::RGBA(100, 100, 100, 0),Color::WHITE,Color::RGB(0, 200, 0),Color::RGB(0, 0, 0),None, TextAlign::Left);
+        let ui_elements = vec![ui_points, timer, framerate, section_name];
         let pause_elements = vec![resume, exit];
         let end_elements = vec![back_to_menu];
         let error_elements = vec![ok_button];
         let ui_texts = vec![pause_text, end_text, combo_text, error_text];
 
         // controlers 
-        let key_left = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) - 195) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0},Color::RGB(200, 50, 100));
-        let key_up = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) - 95) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
-        let key_bottom = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) + 5) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
-        let key_right = KeyButton::new(app, GameObject {active: true, x: ((app.width/2) + 105) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0}, Color::RGB(200, 50, 100));
+        let keys: Vec<KeyButton> = (0..lane_count)
+            .map(|lane| KeyButton::new(app, GameObject {active: true, x: (lane_x(app.width, lane, lane_count) - 45) as f32, y: app.height as f32 - 170.0, width: 90.0, height: 90.0},Color::RGB(200, 50, 100)))
+            .collect();
+        let lane_keys = app.lane_keys(lane_count);
 
         // buttons
-        let key_state = KeyState { left: Note { state: false, active: true }, top: Note { state: false, active: true }, bottom: Note { state: false, active: true }, right: Note { state: false, active: true }};
+        let key_state = vec![Note { state: false, active: true }; lane_count];
         
-        let mut bpm_bars:Vec<BeatLine> = vec![];
+        // only a chart with sections can jump back, the others don't need a copy of the notes
+        let all_keys = if chart_sections.is_empty() { None } else { song_keys.clone() };
 
         println!("{}", benchmark.elapsed().as_millis());
 
         Self {
             last_frame: Instant::now(),
             start_time: Instant::now(),
-            key_left,
-            key_up,
-            key_bottom,
-            key_right,
+            keys,
+            lane_keys,
             key_state,
             song_keys,
             canvas_height: app.height,
-            maked_song: Song { name: "Test".to_owned(), id: Some(0), left_keys: vec![], up_keys: vec!(), bottom_keys: vec![], right_keys: vec![], end: 0, sync: Some(0), bpm: Some(vec![Bpm { bpm: 0, starting_at: 0 }]) },
+            maked_song: Song { name: "Test".to_owned(), ..Song::default() },
             started_song: true,
             started_level: false,
             song,
@@ -195,8 +240,17 @@ impl GameLogic<'_> {
             actual_button: 0,
             ui_texts,
             song_sync,
-            bpm_timer: Instant::now(),
-            bpm_bars
+            scroll: ScrollTimeline::new(&scroll_velocity),
+            chart_id,
+            modifiers: app.modifiers.clone(),
+            seed,
+            lyrics,
+            storyboard,
+            storyboard_images,
+            sections: chart_sections,
+            all_keys,
+            time_shift: 0.0,
+            practice: false,
         }
     }
 
@@ -207,10 +261,10 @@ impl GameLogic<'_> {
             Some(_) => {
                 let delta_time = self.delta_time();
                 let elapsed_time = self.start_time.elapsed() - self.paused_time;
-                let mut milliseconds = 0;
+                let mut milliseconds = 0.0;
                 
                 if app.paused && !self.end{ // pause state
-                    milliseconds = 0;
+                    milliseconds = 0.0;
                     if self.error == true{
                         self.ui_texts[3].render(&mut app.canvas, &texture_creator, &_font);
 
@@ -240,16 +294,14 @@ impl GameLogic<'_> {
                         app.canvas.set_draw_color(Color::RGBA(29, 91, 88, 100));
                         app.canvas.clear();
 
-                        // i have to make sure so i can load the points of the player in a json
-                            // first i have to check if the song haves an id that is not 0
-                                // if id == 0 
-                                    // 1. create id
-                                    // 2. add it to the song
-                                // then we take the id created and check if the id exists in the json of points
-                                    // if it exists we change teh value of points
-                                    // if not we add the new id and the points
-
-                        self.ui_texts[1].text = Some("Congrats, you got ".to_owned() + &self.points.to_string().to_owned() + " points");
+                        let mut points_text = match self.practice {
+                            true => "Practice done, you got ".to_owned() + &self.points.to_string().to_owned() + " points",
+                            false => "Congrats, you got ".to_owned() + &self.points.to_string().to_owned() + " points",
+                        };
+                        if !self.modifiers.is_empty() {
+                            points_text += &format!(" with {}", self.modifiers.label());
+                        }
+                        self.ui_texts[1].text = Some(points_text);
                         self.ui_texts[2].text = Some("Your max combo is ".to_owned() + &self.max_combo.to_string().to_owned() + " notes");
                         
                         self.ui_texts[1].render(&mut app.canvas, &texture_creator, &_font);
@@ -264,7 +316,18 @@ impl GameLogic<'_> {
                             button.render(&mut app.canvas, &texture_creator, &_font);
                         }
                     } else { // play state
-                        match &app.textures.background {
+                        match &app.testing_song {
+                            Some(_song) => {
+                                milliseconds = (elapsed_time.as_secs_f64() * 1000.0 - app.paused_time as f64) + _song.start_point + LEAD_IN_MS + self.time_shift
+                            },
+                            None => {
+                                milliseconds = elapsed_time.as_secs_f64() * 1000.0 - app.paused_time as f64 + self.time_shift
+                            },
+                        }
+                        // the events of the chart have the times of the notes
+                        let look = self.storyboard.state_at(milliseconds - self.song_sync, self.keys.len());
+
+                        match self.storyboard_images.get(&look.background).or(app.textures.background.as_ref()) {
                             Some(texture) => {
                                     app.canvas.copy(&texture, None, Some(Rect::new(0 as i32, 0 as i32, app.width as u32, app.height as u32)))
                                     .expect("Failed to copy texture into canvas");
@@ -274,27 +337,22 @@ impl GameLogic<'_> {
                                 app.canvas.clear();
                             },
                         }
-                        if app.visualizer_settings.bars {
+                        if look.dim > 0.0 {
+                            app.canvas.set_draw_color(Color::RGBA(0, 0, 0, (look.dim * 255.0) as u8));
+                            app.canvas.fill_rect(Rect::new(0, 0, app.width, app.height)).unwrap();
+                        }
+                        if app.visualizer_settings.bars && look.bars.unwrap_or(true) {
                             visualize_space(app, &fft_data.to_vec());
                         }
 
-                        if app.visualizer_settings.circle {
+                        if app.visualizer_settings.circle && look.circle.unwrap_or(true) {
                             visualize_circle(app, &fft_data.to_vec());
                         }
                         
                         self.display_framerate(delta_time);
-
-                        match &app.testing_song {
-                            Some(_song) => {
-                                milliseconds = ((elapsed_time.as_millis() / 10) - (if app.paused_time > 0 { app.paused_time } else { 1 }) / 10) + ((_song.start_point) + 300.0) as u128
-                            },
-                            None => {
-                                milliseconds = (elapsed_time.as_millis() / 10) - (if app.paused_time > 0 { app.paused_time } else { 1 }) / 10
-                            },
-                        }
                         
                         // audio loading and playing
-                        if milliseconds >= 300 && self.started_song == true {
+                        if milliseconds >= LEAD_IN_MS && self.started_song {
                             match app_state.state {
                                 GameState::Playing => {
                                     self.started_song = false;
@@ -305,19 +363,17 @@ impl GameLogic<'_> {
                                                 },
                                                 Err(_) => {}
                                             }
-                                            match &app.testing_song {
-                                                Some(testing) => {
-                                                    match mixer::Music::set_pos(((elapsed_time.as_millis() / 10) - app.paused_time / 10) as f64 + (testing.start_point) as f64 / 100.0) {
-                                                        Ok(_) => {},
-                                                        Err(_) => {
-                                                            app.alert_message = String::from("the song position wasn't loaded correctly");
-                                                            self.actual_button = 0;
-                                                            app.paused = true;
-                                                            self.error = true;
-                                                        },
-                                                    };
-                                                },
-                                                None => {},
+                                            // the tests of the editor and the jumps to a section don't start the audio from the beginning
+                                            if app.testing_song.is_some() || self.time_shift != 0.0 {
+                                                match mixer::Music::set_pos((milliseconds - LEAD_IN_MS) / 1000.0) {
+                                                    Ok(_) => {},
+                                                    Err(_) => {
+                                                        app.alert_message = String::from("the song position wasn't loaded correctly");
+                                                        self.actual_button = 0;
+                                                        app.paused = true;
+                                                        self.error = true;
+                                                    },
+                                                };
                                             }
                                         },
                                         None => {},
@@ -327,23 +383,35 @@ impl GameLogic<'_> {
                             }
                         }
 
-                        let mut key_buttons = [&self.key_left, &self.key_up, &self.key_right, &self.key_bottom];
-                        for (i, button_key) in key_buttons.iter_mut().enumerate() {
-                            button_key.render(app, i);
+                        let lane_count = self.keys.len();
+                        for (lane, button_key) in self.keys.iter_mut().enumerate() {
+                            let alpha = lane_alpha(&look.lane_opacity, lane);
+                            app.textures.set_lane_alpha(alpha);
+                            button_key.color.a = alpha;
+                            button_key.render(app, LaneColor::of(lane, lane_count));
                         }
+                        app.textures.set_lane_alpha(255);
 
                         self.combo.text = Some(self.combo_val.to_string() + "x combo");
                         self.combo.render(&mut app.canvas, &texture_creator, _font);
 
                         match &self.song_keys {
                             Some(keys) => {
-                                Self::handle_notes(self, milliseconds, delta_time, app)
+                                Self::handle_notes(self, milliseconds, app, &look.lane_opacity)
                             },
                             None => {},
                         }
 
+                        if let Some((color, amount)) = look.flash {
+                            app.canvas.set_draw_color(Color::RGBA(color[0], color[1], color[2], (amount * 255.0) as u8));
+                            app.canvas.fill_rect(Rect::new(0, 0, app.width, app.height)).unwrap();
+                        }
+
+                        // the lyrics go over the notes so they can be read
+                        self.render_lyrics(app, _font, milliseconds - self.song_sync);
+
                         self.ui_elements[0].text = Some(self.points.to_string()); // point text
-                        self.ui_elements[1].text = Some(format!("{}", milliseconds)); // timer
+                        self.ui_elements[1].text = Some(format!("{:.0}", milliseconds)); // timer
 
                         for button in &self.ui_elements {
                             button.render(&mut app.canvas, &texture_creator, &_font);
@@ -358,9 +426,18 @@ impl GameLogic<'_> {
                             self.actual_button = 0;
                             self.end = true;
                             mixer::Music::pause();
+                            self.save_score(app_state);
                         }
                         app.canvas.set_draw_color(Color::RGB(235, 219, 178)); // it must be a Color::RGB() or other
-                        app.canvas.fill_rect(Rect::new(0, (app.height - 5) as i32, ((app.width as f32 / self.song_end as f32) * milliseconds as f32) as u32, 5)).unwrap();
+                        app.canvas.fill_rect(Rect::new(0, (app.height - 5) as i32, ((app.width as f64 / self.song_end) * milliseconds).max(0.0) as u32, 5)).unwrap();
+
+                        // a tick over the bar where every section starts, and the name of the one being played
+                        app.canvas.set_draw_color(Color::RGB(250, 189, 47));
+                        for section in &self.sections {
+                            let x = (app.width as f64 / self.song_end) * (section.starting_at + self.song_sync);
+                            app.canvas.fill_rect(Rect::new(x as i32 - 1, (app.height - 12) as i32, 3, 12)).unwrap();
+                        }
+                        self.ui_elements[3].text = sections::current(&self.sections, milliseconds - self.song_sync).map(|section| self.sections[section].name.clone());
                     }
                 } 
                 Self::event_handler(self, milliseconds, &mut app_state, &mut event_pump, app);
@@ -369,7 +446,42 @@ impl GameLogic<'_> {
         }
     }
 
-    fn event_handler(&mut self, milliseconds: u128,app_state: &mut AppState, event_pump: &mut sdl2::EventPump, app: &mut App) {
+    // adds the play to scores.json once, with the modifiers it was played with
+    fn save_score(&mut self, app_state: &AppState) {
+        let (Some(chart_id), Some(folder)) = (self.chart_id.take(), &app_state.song_folder) else { return };
+        let score = Score::new(chart_id, folder, &app_state.song_chart, self.points, self.max_combo, &self.modifiers, self.seed);
+        if let Err(err) = scores::save(score) {
+            eprintln!("The score couldn't be saved: {}", err);
+        }
+    }
+
+    // the line being sung with the words already sung in other color (the word being sung gets colored from left to right),
+    // and the next line under it
+    fn render_lyrics(&self, app: &mut App, font: &Font, time: f64) {
+        let (Some(lyrics), Some(y)) = (&self.lyrics, app.lyrics_position.y(app.height)) else { return };
+        let current = lyrics.current(time);
+        let next = lyrics.next(time).filter(|next| current.is_some() || lyrics.lines[*next].time - time < LYRICS_PREVIEW_MS);
+
+        if let Some(line) = current.map(|current| &lyrics.lines[current]) {
+            let widths: Vec<u32> = line.words.iter().map(|word| font.size_of(&word.text).map_or(0, |(width, _)| width)).collect();
+            let mut x = (app.width as i32 - widths.iter().sum::<u32>() as i32) / 2;
+            for (word, width) in line.words.iter().zip(widths) {
+                let sung = if word.end > word.time { ((time - word.time) / (word.end - word.time)).clamp(0.0, 1.0) } else if time >= word.time { 1.0 } else { 0.0 };
+                draw_text(app, font, &word.text, Color::WHITE, x, y as i32, None);
+                draw_text(app, font, &word.text, Color::RGB(250, 189, 47), x, y as i32, Some((width as f64 * sung) as u32));
+                x += width as i32;
+            }
+        }
+
+        if let Some(line) = next.map(|next| &lyrics.lines[next]) {
+            let text = line.text();
+            let width = font.size_of(&text).map_or(0, |(width, _)| width);
+            let next_y = if current.is_some() { y + LYRICS_LINE_SPACE } else { y };
+            draw_text(app, font, &text, Color::RGB(146, 131, 116), (app.width as i32 - width as i32) / 2, next_y as i32, None);
+        }
+    }
+
+    fn event_handler(&mut self, milliseconds: f64,app_state: &mut AppState, event_pump: &mut sdl2::EventPump, app: &mut App) {
         for event in event_pump.poll_iter() {
             match event {
                 sdl2::event::Event::KeyDown { keycode: Some(key_value), .. } if key_value == Keycode::from_i32(app.play_keys[0]).unwrap() => {
@@ -418,6 +530,12 @@ impl GameLogic<'_> {
                         Self::reset(app, app_state)
                     }
                 },
+                Event::KeyDown { keycode: Some(Keycode::PageDown), .. } if !app.paused && !self.end => {
+                    self.jump_to_section(app, milliseconds, true);
+                },
+                Event::KeyDown { keycode: Some(Keycode::PageUp), .. } if !app.paused && !self.end => {
+                    self.jump_to_section(app, milliseconds, false);
+                },
                 Event::KeyDown { keycode: Some(Keycode::Escape), .. }  => {
                     match app.testing_song {
                         Some(_) => {
@@ -438,10 +556,49 @@ impl GameLogic<'_> {
                 } 
                 _ => {}
             }
-            self.key_state.left.state = self.key_left.update(&mut self.maked_song, milliseconds ,&event, app.play_keys[0], &mut app.play_keys);
-            self.key_state.top.state = self.key_up.update(&mut self.maked_song, milliseconds,&event, app.play_keys[1], &mut app.play_keys);
-            self.key_state.bottom.state = self.key_bottom.update(&mut self.maked_song,milliseconds,&event, app.play_keys[2], &mut app.play_keys);
-            self.key_state.right.state = self.key_right.update(&mut self.maked_song,milliseconds,&event, app.play_keys[3], &mut app.play_keys);
+            // S saves the recorded notes, but not on the key modes where S is a lane (it would save on every tap)
+            let recording = !self.lane_keys.contains(&(Keycode::S as i32));
+            for (lane, key) in self.keys.iter_mut().enumerate() {
+                self.key_state[lane].state = key.update(&mut self.maked_song, milliseconds, &event, self.lane_keys[lane], lane, recording);
+            }
+        }
+    }
+
+    // moves the level to the next section, or to the start of the current one (the one before if it just started).
+    // the notes before it are skipped and the ones after it come again, so a part can be played as many times as needed
+    fn jump_to_section(&mut self, app: &mut App, milliseconds: f64, forward: bool) {
+        // after a jump the clock is SECTION_LEAD_MS before the section, for the sections it is already on it
+        let now = milliseconds - self.song_sync + SECTION_LEAD_MS;
+        let target = if forward { sections::next(&self.sections, now) } else { sections::previous(&self.sections, now) };
+        let (Some(target), Some(all_keys)) = (target, &self.all_keys) else { return };
+
+        let new_milliseconds = (self.sections[target].starting_at + self.song_sync - SECTION_LEAD_MS).max(0.0);
+        self.time_shift += new_milliseconds - milliseconds;
+        let mut song_keys = all_keys.clone();
+        for note in song_keys.iter_mut().flatten() {
+            if note.mili < new_milliseconds - self.song_sync {
+                note.game_object.active = false;
+            }
+        }
+        self.song_keys = Some(song_keys);
+
+        if new_milliseconds < LEAD_IN_MS {
+            // the audio starts again when the clock gets to it
+            mixer::Music::halt();
+            self.started_song = true;
+        } else if let Some(song) = &self.song {
+            if self.started_song {
+                self.started_song = false;
+                let _ = song.play(1);
+            }
+            if mixer::Music::set_pos((new_milliseconds - LEAD_IN_MS) / 1000.0).is_err() {
+                eprintln!("The song position wasn't loaded correctly for the section {}", self.sections[target].name);
+            }
+        }
+
+        if app.testing_song.is_none() {
+            self.practice = true;
+            self.chart_id = None;
         }
     }
 
@@ -482,35 +639,21 @@ impl GameLogic<'_> {
         app_state.state = GameState::SelectingSong;
     }
 
-    fn handle_notes(&mut self, milliseconds: u128, delta_time: Duration, app: &mut App) {
-        /* 
-        match app.bpm {
-            Some(bpm) => {
-                if (self.bpm_timer.elapsed().as_millis() / 10) > 6000 / bpm as u128 && milliseconds > (300 - (app.coordination_data.base_time - self.song_sync)).try_into().unwrap() {
-                    self.bpm_bars.push(BeatLine::new(app.width, app.height));
-                    self.bpm_timer = Instant::now();
-                }
-            },
-            None => {},
-        }
-
-        for bars in &mut self.bpm_bars {
-            bars.render(app, delta_time);
-            bars.game_object.y += app.coordination_data.key_speed * delta_time.as_secs_f32();
-            bars.rect.y = bars.game_object.y as i32;
-        }
-        */
+    fn handle_notes(&mut self, milliseconds: f64, app: &mut App, lane_opacity: &[f64]) {
+        // the scroll position is only for drawing, the notes are judged on the time so the scroll velocity doesn't change when they are hit
+        let time = milliseconds - self.song_sync;
+        let now = self.scroll.position(time);
+        let (late, early) = hit_window(self.canvas_height, app.coordination_data.base_time, app.coordination_data.key_speed);
 
         if let Some(song_keys) = &mut self.song_keys {
-            for key_index in [4,0,1,2,3] {
+            // the beat lines are the last list, they go first so the notes are drawn over them
+            let lane_count = self.keys.len();
+            for key_index in std::iter::once(lane_count).chain(0..lane_count) {
+                // the beat lines don't fade, they are not a lane
+                let alpha = if key_index < lane_count { lane_alpha(lane_opacity, key_index) } else { 255 };
+                app.textures.set_lane_alpha(alpha);
                 
-                let mut actual_key = match key_index {
-                    0 => Some(&mut self.key_left),
-                    1 => Some(&mut self.key_up),
-                    2 => Some(&mut self.key_bottom),
-                    3 => Some(&mut self.key_right),
-                    _ => None
-                };
+                let mut actual_key = self.keys.get_mut(key_index);
 
                 let mut remove: Vec<usize> = Vec::new(); // Collect indices of notes to remove
 
@@ -519,13 +662,34 @@ impl GameLogic<'_> {
                         remove.push(i);
                     }
 
-                    if note.mili - (app.coordination_data.base_time - self.song_sync) < milliseconds.try_into().unwrap() {
+                    // how far the note is from the hit line, with the scroll velocity a far note can show up later or sooner
+                    let distance = self.scroll.position(note.mili) - now;
+                    if distance < app.coordination_data.base_time {
+                        note.scroll_to(distance, app.coordination_data.base_time, app.coordination_data.key_speed);
+                        note.color.a = alpha;
                         note.render(app);
-                        note.update(delta_time, app.coordination_data.key_speed);
+
+                        let time_distance = note.mili - time;
+                        let in_window = time_distance > late && time_distance < early;
 
                         match actual_key {
+                            // the mine goes off if the key is down while it passes, letting it go is what the player has to do
+                            Some(ref key_actual) if note.kind == NoteKind::Mine && in_window && key_actual.pressed && note.game_object.active => {
+                                self.points = self.points.saturating_sub(MINE_PENALTY);
+                                self.combo_val = 0;
+                                note.game_object.active = false;
+                            },
+                            // fake notes are only decoration and the mines are never hit like a note
+                            Some(_) if matches!(note.kind, NoteKind::Fake | NoteKind::Mine) => {},
                             Some(ref mut key_actual) => {
-                                if (note.game_object.y + 50.0 > (self.canvas_height - 150) as f32) && (note.game_object.y < (self.canvas_height - 80) as f32) && key_actual.pressed && key_actual.timer_hold.elapsed().as_millis() / 10 < 10 {
+                                // the body of a roll only counts if the key was pressed again a moment ago, keeping it down is not enough
+                                let pressed_in_time = if note.kind == NoteKind::Roll && note.holding {
+                                    key_actual.timer.elapsed().as_millis() < ROLL_TAP_MS
+                                } else {
+                                    key_actual.pressed && key_actual.timer_hold.elapsed().as_millis() < 100
+                                };
+
+                                if in_window && pressed_in_time {
                                     if note.game_object.active {
                                         if note.holding {
 
@@ -543,7 +707,7 @@ impl GameLogic<'_> {
                                     
                                             key_actual.state = 2;
                                         
-                                } else if (note.game_object.y > (self.canvas_height - 80) as f32) && !note.muted && note.game_object.active {
+                                } else if time_distance <= late && !note.muted && note.game_object.active {
                                     if !note.muted {
                                         self.combo_val = 0;
                                     }
@@ -559,6 +723,7 @@ impl GameLogic<'_> {
                     song_keys[key_index].remove(*value);
                 }
             }
+            app.textures.set_lane_alpha(255);
         }
     }
 
@@ -570,6 +735,79 @@ impl GameLogic<'_> {
     }
 }
 
+// the images of the background events of a chart, loaded before the song starts so changing them doesn't stop the game
+#[derive(Default)]
+pub struct StoryboardImages {
+    images: HashMap<String, Texture>,
+}
+
+impl StoryboardImages {
+    // an image that can't be loaded is told on the console and the default background is used instead
+    pub fn load(folder: &str, storyboard: &Storyboard, texture_creator: &TextureCreator<WindowContext>) -> StoryboardImages {
+        let mut images = HashMap::new();
+        for file in storyboard.backgrounds() {
+            let path = Path::new(SONGS_FOLDER).join(folder).join(&file);
+            match texture_creator.load_texture(&path) {
+                Ok(texture) => {
+                    images.insert(file, texture);
+                },
+                Err(err) => eprintln!("The background {} didn't loaded right: {}", path.to_string_lossy(), err),
+            }
+        }
+        StoryboardImages { images }
+    }
+
+    pub fn get(&self, file: &Option<String>) -> Option<&Texture> {
+        file.as_ref().and_then(|file| self.images.get(file))
+    }
+}
+
+impl Drop for StoryboardImages {
+    fn drop(&mut self) {
+        for (_, texture) in self.images.drain() {
+            unsafe { texture.destroy(); }
+        }
+    }
+}
+
+// how many milliseconds before the hit line a note stops being hittable (late, negative when it passed it) and starts being hittable (early).
+// it is the part of the screen around the keys (from 200 to 80 pixels over the bottom) where a note is at normal speed
+fn hit_window(canvas_height: u32, base_time: f64, key_speed: f32) -> (f64, f64) {
+    let distance_at = |y: f64| base_time - (y + 100.0) * 1000.0 / key_speed as f64;
+    (distance_at(canvas_height as f64 - 80.0), distance_at(canvas_height as f64 - 200.0))
+}
+
+fn lane_alpha(lane_opacity: &[f64], lane: usize) -> u8 {
+    (lane_opacity.get(lane).copied().unwrap_or(1.0) * 255.0).round() as u8
+}
+
+// a missing lyrics file is normal, a broken one is only told on the console because the song can be played without it
+fn load_lyrics(folder: &String, song: &Song) -> Option<Lyrics> {
+    match Lyrics::load_for_chart(folder, song) {
+        Ok(lyrics) => lyrics,
+        Err(err) => {
+            eprintln!("The lyrics of {} didn't loaded right: {}", folder, err);
+            None
+        },
+    }
+}
+
+// draws a text with its top left corner on x y, `cut` is the width of the text that is drawn from the left (all of it when None)
+fn draw_text(app: &mut App, font: &Font, text: &str, color: Color, x: i32, y: i32, cut: Option<u32>) {
+    if text.is_empty() || cut == Some(0) {
+        return;
+    }
+    let Ok(surface) = font.render(text).blended(color) else { return };
+    let Ok(texture) = app.texture_creator.create_texture_from_surface(&surface) else { return };
+    let TextureQuery { width, height, .. } = texture.query();
+    let width = cut.map_or(width, |cut| cut.min(width));
+    app.canvas.copy(&texture, Rect::new(0, 0, width, height), Rect::new(x, y, width, height)).unwrap();
+
+    unsafe {
+        texture.destroy();
+    }
+}
+
 fn visualize_space(app: &mut App, fft_data: &Vec<f32>) {
     let width = (app.width / NUM_BARS as u32) / 4 as u32;
         let total_width = width * NUM_BARS as u32;
@@ -627,26 +865,3 @@ fn draw_circle(canvas: &mut Canvas<Window>, center_x: i32, center_y: i32, radius
         }
     }
 }
-
-#[derive(Clone, Copy)]
-struct BeatLine {
-    game_object: GameObject,
-    rect: Rect
-}
-
-impl BeatLine {
-    pub fn new(width: u32, _height: u32) -> Self {
-        let game_object = GameObject { active: true, x: (width / 2 - (((width/2) - 200) / 2)) as f32, y: -100.0 - (5 / 2) as f32, width: (width/2) as f32  - 200.0, height: 5 as f32 };
-        let bar_rect = Rect::new(game_object.x as i32, game_object.y as i32, game_object.width as u32, game_object.height as u32);
-
-        BeatLine {
-            rect: bar_rect,
-            game_object
-        }
-    }
-
-    pub fn render(mut self, app: &mut App, deltatime: Duration) {
-        app.canvas.set_draw_color(Color::RGB(60, 56, 54));
-        app.canvas.fill_rect(self.rect).unwrap();
-    }
-}
@@ -1,5 +1,5 @@
 use sdl2::{event::Event, keyboard::Keycode, mixer, pixels::Color, ttf::Font};
-use crate::{ app::{App, AppState, GameState}, game_object::GameObject, input::{button_module::{Button, TextAlign}, slider_module::Slider_input}};
+use crate::{ app::{App, AppState, GameState, LyricsPosition}, game_object::GameObject, input::{button_module::{Button, TextAlign}, slider_module::Slider_input}};
 
 enum MenuSelector {
     Controller,
@@ -11,7 +11,7 @@ pub struct GameLogic<'a> { // here we define the data we use on our script
     opt_list: [&'a MenuSelector; 3],
     actual_opt: &'a MenuSelector,
     actual_setting: usize,
-    btn_list: [Button;6],
+    btn_list: [Button;7],
     slider: Slider_input,
     pub started: bool
 }
@@ -30,9 +30,11 @@ impl GameLogic<'_> {
         let circle = Button::new(GameObject {active: true, x:((app.width/2) - (200/2)) as f32, y: 460.0, width: 200.0, height: 50.0}, Some(String::from("Circle Visualization")), Color::RGB(143, 63, 113), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(100, 100, 100), None, TextAlign::Center);
         let bars = Button::new(GameObject {active: true, x:((app.width/2) - (200/2)) as f32, y: 520.0, width: 200.0, height: 50.0}, Some(String::from("Bars Visualization")), Color::RGB(143, 63, 113), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(100, 100, 100), None, TextAlign::Center);
 
+        let lyrics = Button::new(GameObject {active: true, x:((app.width/2) - (200/2)) as f32, y: 580.0, width: 200.0, height: 50.0}, Some(lyrics_text(app.lyrics_position)), Color::RGB(143, 63, 113), Color::WHITE, Color::RGB(0, 100, 0), Color::RGB(100, 100, 100), None, TextAlign::Center);
+
         let exit = Button::new(GameObject {active: true, x: 10.0 as f32, y: 10.0, width: 70.0, height: 30.0},Some(String::from("Back")),Color::RGB(100, 100, 100),Color::WHITE,Color::RGB(0, 200, 0),Color::RGB(0, 0, 0),None, TextAlign::Center);
 
-        let btn_list = [controller, calibration, manual_calibration, circle, bars, exit];
+        let btn_list = [controller, calibration, manual_calibration, circle, bars, lyrics, exit];
 
         // at the end of our "new we need to return the data" since this is our constructor
         Self {
@@ -61,7 +63,7 @@ impl GameLogic<'_> {
         Self::event_handler(app_state, event_pump, &mut self.btn_list, &mut self.slider, app);
     }
 
-    fn event_handler(app_state: &mut AppState, event_pump: &mut sdl2::EventPump,  btn_list: &mut [Button;6], slider: &mut Slider_input, app: &mut App) {
+    fn event_handler(app_state: &mut AppState, event_pump: &mut sdl2::EventPump,  btn_list: &mut [Button;7], slider: &mut Slider_input, app: &mut App) {
         for event in event_pump.poll_iter() {
             match event { 
                 Event::Quit { .. } => {
@@ -94,6 +96,10 @@ impl GameLogic<'_> {
                     btn_list[4].toggle = Some(app.visualizer_settings.bars);
                     app.visualizer_settings.bars = !app.visualizer_settings.bars
                 }
+                if btn_list[5].on_click(&event) {
+                    app.lyrics_position = app.lyrics_position.next();
+                    btn_list[5].text = Some(lyrics_text(app.lyrics_position));
+                }
                 if btn_list[btn_list.len() - 1].on_click(&event) {
                     app_state.state = GameState::MainMenu;
                 }
@@ -101,3 +107,6 @@ impl GameLogic<'_> {
     }
 }
 
+fn lyrics_text(position: LyricsPosition) -> String {
+    format!("Lyrics: {}", position.name())
+}
@@ -1,33 +1,80 @@
-use std::{fs, path::Path};
-
-use sdl2::{event::Event, image::LoadTexture, keyboard::Keycode, pixels::Color, rect::Rect, render::{Canvas, Texture, TextureCreator}, sys::{KeyCode, SDL_Texture}, ttf::Font, video::{Window, WindowContext}};
-use crate::{ app::{App, AppState, GameState}, game_object::GameObject, input::button_module::{Button, TextAlign}};
+use sdl2::{event::Event, image::LoadTexture, keyboard::Keycode, pixels::Color, rect::Rect, render::{Canvas, Texture, TextureCreator}, ttf::Font, video::{Window, WindowContext}};
+use crate::{ app::{App, AppState, GameState}, chart::{library::{self, ChartEntry, Library}, package, scores::{self, Score}, validate}, game_object::GameObject, input::button_module::{Button, TextAlign}, load_song::DEFAULT_CHART};
 
 pub struct SongFile {
     button: Button,
-    img_texture: Option<Texture>
+    cover_path: Option<String>,
+    folder: String,
+    charts: Vec<String>,
+    // the difficulty name and the credits of every chart, in the same order as charts
+    chart_labels: Vec<String>,
+    chart_info: Vec<String>,
+    chart_ids: Vec<String>,
+    chart_lanes: Vec<usize>,
+    chart_index: usize
+}
+
+impl SongFile {
+    fn selected_chart(&self) -> String {
+        self.charts.get(self.chart_index).cloned().unwrap_or(DEFAULT_CHART.to_owned())
+    }
 }
 
 pub struct GameLogic<> { // here we define the data we use on our script
     btn_list: Vec<SongFile>,
     loading_text: Button,
     song_img: Button,
+    message_text: Button,
+    chart_text: Button,
+    info_text: Button,
+    modifiers_text: Button,
+    // the scores of scores.json, to show the best one of the selected chart
+    scores: Vec<Score>,
+    // only the cover of the selected song is loaded, cover_index says which song it belongs to
+    cover: Option<Texture>,
+    cover_index: Option<usize>,
     actual_button: usize
 }
 
 impl GameLogic<> {
     // this is called once
-    pub fn new(mut app: &mut App) -> Self {
-        // read every file in ./song
-        let songs = Self::load_songs("./songs".to_owned(), &mut app);
+    pub fn new(app: &mut App) -> Self {
+        // the .arrow packages copied to ./songs are unpacked before reading the folders
+        let messages = package::import_dropped_packages();
+        for message in &messages {
+            println!("{}", message);
+        }
+
+        // read every song in ./songs, the charts that didn't change since the last time come from the library index
+        let songs = Self::load_songs(&Library::scan(), app);
         let loading_text = Button::new( GameObject { active: true, x: ((app.width / 2) - ((app.width - 20) / 2)) as f32, y: (app.height as f32 - 60.0), width: (app.width as f32 - 20.0), height: 50.0}, Some("Loading".to_owned()), Color::RGB(28, 29, 37), Color::WHITE, Color::RGB(0, 200, 0), Color::RGB(0, 0, 0), None, TextAlign::Center);
 
         let song_img = Button::new( GameObject { active: true, x: (app.width as f32 - 600.0) / 2 as f32, y: (app.height as f32 / 2.0) - 300.0 / 2.0, width: 300.0, height: 300.0}, Some("No Cover".to_owned()), Color::RGB(28, 29, 37), Color::WHITE, Color::RGB(0, 200, 0), Color::RGB(0, 0, 0), None, TextAlign::Center);
 
+        let message_text = Button::new( GameObject { active: !messages.is_empty(), x: 10.0, y: 10.0, width: (app.width as f32 - 20.0), height: 50.0}, Some(messages.join(", ")), Color::RGB(28, 29, 37), Color::WHITE, Color::RGB(0, 200, 0), Color::RGB(0, 0, 0), None, TextAlign::Center);
+
+        let chart_text = Button::new( GameObject { active: true, x: (app.width as f32 - 600.0) / 2.0, y: (app.height as f32 / 2.0) + 160.0, width: 300.0, height: 40.0}, None, Color::RGB(28, 29, 37), Color::WHITE, Color::RGB(0, 200, 0), Color::RGB(0, 0, 0), None, TextAlign::Center);
+
+        let info_text = Button::new( GameObject { active: true, x: 10.0, y: (app.height as f32 / 2.0) + 210.0, width: app.width as f32 / 2.0 - 20.0, height: 40.0}, None, Color::RGBA(0, 0, 0, 0), Color::WHITE, Color::RGB(0, 200, 0), Color::RGB(0, 0, 0), None, TextAlign::Center);
+
+        let modifiers_text = Button::new( GameObject { active: true, x: 10.0, y: (app.height as f32 / 2.0) + 250.0, width: app.width as f32 / 2.0 - 20.0, height: 40.0}, None, Color::RGBA(0, 0, 0, 0), Color::WHITE, Color::RGB(0, 200, 0), Color::RGB(0, 0, 0), None, TextAlign::Center);
+
+        let scores = scores::load().unwrap_or_else(|err| {
+            eprintln!("The scores didn't loaded right: {}", err);
+            vec![]
+        });
+
         Self {
             btn_list: songs,
             loading_text,
             song_img,
+            message_text,
+            chart_text,
+            info_text,
+            modifiers_text,
+            scores,
+            cover: None,
+            cover_index: None,
             actual_button: 0
         }
     }
@@ -39,19 +86,45 @@ impl GameLogic<> {
             self.btn_list[btn].button.render(&mut app.canvas, &texture_creator, _font);
         }
 
-        match &self.btn_list[self.actual_button].img_texture {
+        if self.message_text.game_object.active {
+            self.message_text.render(&mut app.canvas, &texture_creator, _font);
+        }
+
+        if self.cover_index != Some(self.actual_button) {
+            if let Some(old_cover) = self.cover.take() {
+                unsafe { old_cover.destroy(); }
+            }
+            self.cover = self.btn_list[self.actual_button].cover_path.as_ref().and_then(|path| app.texture_creator.load_texture(path).ok());
+            self.cover_index = Some(self.actual_button);
+        }
+
+        match &self.cover {
             Some(texture) => {
                     app.canvas.copy(&texture, None, Some(Rect::new((app.width as f32 - 600.0) as i32 / 2 as i32, ((app.height as f32 / 2.0) - 150.0) as i32, 300, 300))).unwrap();
-                    unsafe {
-                        let raw_texture_ptr = texture as *const sdl2::render::Texture as *mut SDL_Texture;
-                        sdl2::sys::SDL_DestroyTexture(raw_texture_ptr);
-                    }
             },
             None => {
                 self.song_img.render(&mut app.canvas, &texture_creator, _font);
             },
         }
 
+        // the difficulty picker is only shown when the folder has more than one chart
+        let selected = &self.btn_list[self.actual_button];
+        if selected.charts.len() > 1 {
+            self.chart_text.text = Some(format!("< {} >", selected.chart_labels[selected.chart_index]));
+            self.chart_text.render(&mut app.canvas, &texture_creator, _font);
+        }
+        if let Some(info) = selected.chart_info.get(selected.chart_index).filter(|info| !info.is_empty()) {
+            self.info_text.text = Some(info.clone());
+            self.info_text.render(&mut app.canvas, &texture_creator, _font);
+        }
+
+        let mut modifiers = format!("modifiers: {}", app.modifiers.label());
+        if let Some(best) = selected.chart_ids.get(selected.chart_index).and_then(|chart_id| scores::best(&self.scores, chart_id, &app.modifiers)) {
+            modifiers += &format!(" | best {} points", best);
+        }
+        self.modifiers_text.text = Some(modifiers);
+        self.modifiers_text.render(&mut app.canvas, &texture_creator, _font);
+
         for (i, btn) in self.btn_list.iter_mut().enumerate() {
             // buscamos en la lista los valores mayores y menores a este y en base a eso organizamos los elementos.
             
@@ -111,36 +184,57 @@ impl GameLogic<> {
                         self.actual_button = 0;
                     }
                 },
+                sdl2::event::Event::KeyDown { keycode: Some(Keycode::Left), .. } => {
+                    let song = &mut self.btn_list[self.actual_button];
+                    if song.charts.len() > 1 {
+                        song.chart_index = (song.chart_index + song.charts.len() - 1) % song.charts.len();
+                    }
+                },
+                sdl2::event::Event::KeyDown { keycode: Some(Keycode::Right), .. } => {
+                    let song = &mut self.btn_list[self.actual_button];
+                    if song.charts.len() > 1 {
+                        song.chart_index = (song.chart_index + 1) % song.charts.len();
+                    }
+                },
                 sdl2::event::Event::KeyDown { keycode: Some(Keycode::Delete), .. } => {
                     self.loading(&mut texture_creator, _font, &mut app.canvas);
-                    match &self.btn_list[self.actual_button].button.text {
-                        Some(_text) => {
-                            app_state.song_folder = Some(_text.clone());
-                        },
-                        None => {},
-                    }
+                    app_state.song_folder = Some(self.btn_list[self.actual_button].folder.clone());
+                    app_state.song_chart = self.btn_list[self.actual_button].selected_chart();
                     app.reseted = false;
                     app_state.state = GameState::Editing;
             },
                 sdl2::event::Event::KeyDown { keycode: Some(key_value), .. } if key_value == Keycode::from_i32(app.play_keys[3]).unwrap() => {
                         self.loading(&mut texture_creator, _font, &mut app.canvas);
-                        match &self.btn_list[self.actual_button].button.text {
-                            Some(_text) => {
-                                app_state.song_folder = Some(_text.clone());
-                            },
-                            None => {},
-                        }
+                        app_state.song_folder = Some(self.btn_list[self.actual_button].folder.clone());
+                        app_state.song_chart = self.btn_list[self.actual_button].selected_chart();
                         app.reseted = false;
                         app_state.state = GameState::Playing;
                 },
+                Event::KeyDown { keycode: Some(Keycode::E), .. }  => {
+                    // packs the selected song as exports/<song>.arrow so it can be shared as one file
+                    let folder = &self.btn_list[self.actual_button].folder;
+                    let message = match package::export(folder) {
+                        Ok(path) => format!("{} was exported to {}", folder, path.to_string_lossy()),
+                        Err(err) => format!("{} couldn't be exported: {}", folder, err),
+                    };
+                    println!("{}", message);
+                    self.message_text.text = Some(message);
+                    self.message_text.game_object.active = true;
+                },
+                // the modifiers, they stay on for every song until they are pressed again
+                Event::KeyDown { keycode: Some(Keycode::M), .. }  => app.modifiers.mirror = !app.modifiers.mirror,
+                Event::KeyDown { keycode: Some(Keycode::R), .. }  => app.modifiers.random = !app.modifiers.random,
+                Event::KeyDown { keycode: Some(Keycode::S), .. }  => app.modifiers.shuffle = !app.modifiers.shuffle,
+                Event::KeyDown { keycode: Some(Keycode::H), .. }  => app.modifiers.no_holds = !app.modifiers.no_holds,
+                Event::KeyDown { keycode: Some(Keycode::L), .. }  => {
+                    let song = &self.btn_list[self.actual_button];
+                    let chart_lanes = song.chart_lanes.get(song.chart_index).copied().unwrap_or(0);
+                    app.modifiers.lanes = app.modifiers.next_lanes(chart_lanes);
+                },
                 Event::KeyDown { keycode: Some(Keycode::Space), .. }  => {
                     self.loading(&mut texture_creator, _font, &mut app.canvas);
-                        match &self.btn_list[self.actual_button].button.text {
-                            Some(_text) => {
-                                app_state.song_folder = Some(_text.clone());
-                            },
-                            None => {},
-                        }
+                        app_state.song_folder = Some(self.btn_list[self.actual_button].folder.clone());
+                        app_state.song_chart = self.btn_list[self.actual_button].selected_chart();
                         app.reseted = false;
                         app_state.state = GameState::SongCalibration;
                 },
@@ -182,55 +276,76 @@ impl GameLogic<> {
         canvas.present();
     }
 
-    fn load_songs(songs_folder: String, app: &mut App) -> Vec<SongFile>{
-        let folders = fs::read_dir(&songs_folder).unwrap();
+    fn load_songs(library: &Library, app: &mut App) -> Vec<SongFile>{
         let mut songs_buttons: Vec<SongFile> = vec![];
         let mut position = 50.0;
 
-        for entry in folders {
-            let entry = entry.unwrap();
-                if entry.file_type().unwrap().is_dir() {
-                    println!("{}", entry.file_name().to_string_lossy().to_string() + "/cover.png");
-                    
-                    let mut img_texture = None;
-                    let path = "./songs/".to_owned() + &entry.file_name().to_string_lossy().to_string() + "/cover";
-                    if Path::new(&(path.clone() + ".png")).exists() {
-                        img_texture = app.texture_creator.load_texture(&(path + ".png")).ok();
-                    } else if Path::new(&(path.clone() + ".jpeg")).exists() {
-                        img_texture = app.texture_creator.load_texture(&(path + ".jpeg")).ok();
-                    } else if Path::new(&(path.clone() + ".jpg")).exists() {
-                        img_texture = app.texture_creator.load_texture(&(path + ".jpg")).ok();
-                    } else if Path::new(&(path.clone() + ".svg")).exists() {
-                        img_texture = app.texture_creator.load_texture(&(path + ".svg")).ok();
-                    } else if Path::new(&(path.clone() + ".gif")).exists() {
-                        img_texture = app.texture_creator.load_texture(&(path + ".gif")).ok();
-                    }
-
-                    songs_buttons.push(
-                        SongFile {
-                            button: Button::new(
-                                GameObject {
-                                    active: true,
-                                    x: app.width as f32 - 350.0,
-                                    y: position,
-                                    width: 350.0,
-                                    height: 50.0,
-                                },
-                                Some(entry.file_name().to_string_lossy().to_string()),
-                                Color::RGB(100, 100, 100),
-                                Color::WHITE,
-                                Color::RGB(0, 200, 0),
-                                Color::RGB(0, 0, 0),
-                                None,
-                                TextAlign::Center),
-                                img_texture
-                        }
-                        
-                    );
-                    position += 60.0;
+        for song in &library.songs {
+            songs_buttons.push(
+                SongFile {
+                    button: Button::new(
+                        GameObject {
+                            active: true,
+                            x: app.width as f32 - 350.0,
+                            y: position,
+                            width: 350.0,
+                            height: 50.0,
+                        },
+                        Some(song.title.clone()),
+                        Color::RGB(100, 100, 100),
+                        Color::WHITE,
+                        Color::RGB(0, 200, 0),
+                        Color::RGB(0, 0, 0),
+                        None,
+                        TextAlign::Center),
+                        cover_path: song.cover_path.clone(),
+                        folder: song.folder.clone(),
+                        charts: song.charts.iter().map(|chart| chart.chart.clone()).collect(),
+                        chart_labels: song.charts.iter().map(|chart| chart.label.clone()).collect(),
+                        chart_info: song.charts.iter().map(|chart| Self::song_info(chart, song.has_audio)).collect(),
+                        chart_ids: song.charts.iter().map(|chart| chart.chart_id.clone()).collect(),
+                        chart_lanes: song.charts.iter().map(|chart| chart.lanes).collect(),
+                        chart_index: 0
                 }
-            }
+            );
+            position += 60.0;
+        }
         return songs_buttons
     }
-}
 
+    // the line under the cover with the chart information, like "7K | charted by Khalz | Rock | 180 BPM | 2:31 | 512 notes | difficulty 4.5"
+    fn song_info(chart: &ChartEntry, has_audio: bool) -> String {
+        if let Some(error) = &chart.error {
+            return format!("the chart can't be played: {}", error);
+        }
+
+        let mut parts = vec![format!("{}K", chart.lanes)];
+        if !chart.charter.is_empty() {
+            parts.push(format!("charted by {}", chart.charter));
+        }
+        if !chart.genre.is_empty() {
+            parts.push(chart.genre.clone());
+        }
+        if !chart.source.is_empty() {
+            parts.push(format!("from {}", chart.source));
+        }
+        match (chart.min_bpm, chart.max_bpm) {
+            (Some(min), Some(max)) if min != max => parts.push(format!("{}-{} BPM", library::bpm_text(min), library::bpm_text(max))),
+            (Some(bpm), _) => parts.push(format!("{} BPM", library::bpm_text(bpm))),
+            _ => {},
+        }
+        let seconds = (chart.length / 1000.0) as u64;
+        parts.push(format!("{}:{:02}", seconds / 60, seconds % 60));
+        parts.push(format!("{} notes", chart.note_count));
+        if chart.note_count > 0 {
+            parts.push(format!("difficulty {:.1}", chart.rating));
+        }
+        if chart.error_count + chart.warning_count > 0 {
+            parts.push(validate::summary(chart.error_count, chart.warning_count));
+        }
+        if !has_audio {
+            parts.push("no audio".to_owned());
+        }
+        parts.join(" | ")
+    }
+}
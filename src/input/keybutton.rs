@@ -8,17 +8,64 @@ use serde::{Deserialize, Serialize};
 use serde_json;
 use std::fs;
 use std::time::Instant;
-use rand::Rng;
 
 use crate::app::App;
 use crate::game_object::GameObject;
+use crate::key::LaneColor;
 use crate::load_song::Song;
 
 #[derive(Clone,Debug,Serialize,Deserialize,Copy)]
 
 pub struct Note {
-    pub time: u128,
-    pub holding: u128
+    pub time: f64,
+    pub holding: f64,
+    // charts made before the kinds existed only have normal notes, so it is not saved for them
+    #[serde(default, skip_serializing_if = "NoteKind::is_normal")]
+    pub kind: NoteKind,
+}
+
+// what the player has to do with the note
+#[derive(Clone,Debug,Serialize,Deserialize,Copy,PartialEq,Eq,Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NoteKind {
+    // pressed on time, or held until the end if it has `holding`
+    #[default]
+    Normal,
+    // must not be pressed, pressing the key when it passes takes points and breaks the combo
+    Mine,
+    // like a hold but the key has to be tapped again and again until the end
+    Roll,
+    // only decoration, it is never judged
+    Fake,
+}
+
+impl NoteKind {
+    pub const ALL: [NoteKind; 4] = [NoteKind::Normal, NoteKind::Mine, NoteKind::Roll, NoteKind::Fake];
+
+    pub fn is_normal(&self) -> bool {
+        *self == NoteKind::Normal
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoteKind::Normal => "Note",
+            NoteKind::Mine => "Mine",
+            NoteKind::Roll => "Roll",
+            NoteKind::Fake => "Fake",
+        }
+    }
+
+    // the kind that comes after this one, used by the editor button
+    pub fn next(&self) -> NoteKind {
+        let index = Self::ALL.iter().position(|kind| kind == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+}
+
+impl Note {
+    pub fn new(time: f64, holding: f64) -> Note {
+        Note { time, holding, kind: NoteKind::Normal }
+    }
 }
 
 #[derive(Clone)]
@@ -27,15 +74,13 @@ pub struct KeyButton {
     pub color: Color,
     pub pressed: bool,
     pub repeat: bool,
-    pub left_keys: Vec<Note>,
-    pub right_keys: Vec<Note>,
-    pub up_keys: Vec<Note>,
-    pub down_keys: Vec<Note>,
+    // the notes recorded with this key, they go to its lane of the song when it is saved with S
+    pub notes: Vec<Note>,
     pub image_array: Vec<Rect>,
     pub state: usize,
     pub timer: Instant,
     pub timer_hold: Instant,
-    pub pressed_time: u128,
+    pub pressed_time: f64,
 }
 
 impl KeyButton {
@@ -48,30 +93,22 @@ impl KeyButton {
             color: color,
             pressed: false,
             repeat: true,
-            left_keys: vec![],
-            right_keys: vec![],
-            up_keys: vec![],
-            down_keys: vec![],
+            notes: vec![],
             image_array,
             state: 0,
             timer: Instant::now(),
             timer_hold: Instant::now(),
-            pressed_time: 0,
+            pressed_time: 0.0,
         }
     }
 
-    pub fn render(&self, app: &mut App, flag: usize) {
-        let mut texture = &app.textures.red_key;
-
-        if flag == 0 {
-            texture = &app.textures.red_key
-        } else if flag == 1 {
-            texture = &app.textures.yellow_key
-        } else if flag == 2 {
-            texture = &app.textures.purple_key
-        } else if flag == 3 {
-            texture = &app.textures.blue_key
-        }
+    pub fn render(&self, app: &mut App, color: LaneColor) {
+        let texture = match color {
+            LaneColor::Red => &app.textures.red_key,
+            LaneColor::Yellow => &app.textures.yellow_key,
+            LaneColor::Blue => &app.textures.blue_key,
+            LaneColor::Purple => &app.textures.purple_key,
+        };
 
         match &texture {
             Some(texture) => {
@@ -97,7 +134,7 @@ impl KeyButton {
         }
     }
 
-    pub fn update(&mut self,song: &mut Song, milliseconds: u128, event: &sdl2::event::Event, key: i32, play_keys: &mut [i32; 4]) -> bool {
+    pub fn update(&mut self,song: &mut Song, milliseconds: f64, event: &sdl2::event::Event, key: i32, lane: usize, recording: bool) -> bool {
         if self.game_object.active {
             match event {
                 sdl2::event::Event::KeyDown { keycode: Some(key_value), .. } if *key_value == Keycode::from_i32(key).unwrap() => {
@@ -116,26 +153,13 @@ impl KeyButton {
                     if self.repeat == false {
                         let elapsed_time = self.timer.elapsed();
 
-                        let mut holding_value = elapsed_time.as_millis() / 10;
+                        let mut holding_value = elapsed_time.as_secs_f64() * 1000.0;
 
-                        if holding_value < 20 {
-                            holding_value = 0;
+                        if holding_value < 200.0 {
+                            holding_value = 0.0;
                         }
 
-                        let note = Note { time: self.pressed_time, holding: holding_value};
-
-                        if key == play_keys[0] {
-                            self.left_keys.push(note);
-                        }
-                        if key == play_keys[3] {
-                            self.right_keys.push(note);
-                        }
-                        if key == play_keys[1] {
-                            self.up_keys.push(note);
-                        }
-                        if key == play_keys[2] {
-                            self.down_keys.push(note);
-                        }
+                        self.notes.push(Note::new(self.pressed_time, holding_value));
 
                         self.repeat = true;
                         self.pressed = false;
@@ -144,26 +168,13 @@ impl KeyButton {
                     }
                 },
                 // song generation (saving)
-                sdl2::event::Event::KeyDown { keycode: Some(Keycode::S), .. } => {
+                sdl2::event::Event::KeyDown { keycode: Some(Keycode::S), .. } if recording => {
                     song.end = milliseconds;
 
-                    let mut rng = rand::thread_rng();
-                    let random_number: i128 = rng.gen();
-
-                    song.id = Some(random_number);
-
-                    if key == play_keys[0] {
-                        song.left_keys = self.left_keys.clone();
-                    }
-                    if key == play_keys[3] {
-                        song.right_keys = self.right_keys.clone();
-                    }
-                    if key == play_keys[1] {
-                        song.up_keys = self.up_keys.clone();
-                    }
-                    if key == play_keys[2] {
-                        song.bottom_keys = self.down_keys.clone();
+                    if song.lanes.len() <= lane {
+                        song.lanes.resize(lane + 1, vec![]);
                     }
+                    song.lanes[lane] = self.notes.clone();
 
                     if let Err(err) = save_json(&song) {
                         println!("Error saving JSON: {}", err);
@@ -7,31 +7,77 @@ use sdl2::pixels::Color;
 
 use crate::app::App;
 use crate::game_object::GameObject;
+use crate::input::keybutton::NoteKind;
+
+// the space between the middle of two lanes
+pub const LANE_SPACING: i32 = 100;
+
+// the colors the textures of the notes and keys come in
+#[derive(Clone, Copy, PartialEq)]
+pub enum LaneColor {
+    Red,
+    Yellow,
+    Blue,
+    Purple,
+}
+
+impl LaneColor {
+    // 4K keeps the colors it always had, the other modes repeat the colors from both sides to the middle
+    // so they look the same for each hand, and the lane of the middle (if there is one) is purple
+    pub fn of(lane: usize, lane_count: usize) -> LaneColor {
+        if lane_count == 4 {
+            return [LaneColor::Red, LaneColor::Yellow, LaneColor::Purple, LaneColor::Blue][lane % 4];
+        }
+        if lane_count % 2 == 1 && lane == lane_count / 2 {
+            return LaneColor::Purple;
+        }
+        let from_side = lane.min(lane_count.saturating_sub(lane + 1));
+        [LaneColor::Red, LaneColor::Yellow, LaneColor::Blue][from_side % 3]
+    }
+}
+
+// the x of the middle of a lane, the lanes are centered on the screen
+pub fn lane_x(width: u32, lane: usize, lane_count: usize) -> i32 {
+    (width / 2) as i32 + (2 * lane as i32 - (lane_count as i32 - 1)) * LANE_SPACING / 2
+}
 
 #[derive(Clone, Copy)]
 pub enum KeyFlag {
-    Left,
-    Up,
-    Bottom,
-    Right,
+    // the number of the lane from the left and its color
+    Lane(usize, LaneColor),
     Bpm
 }
 
+impl KeyFlag {
+    pub fn lane(lane: usize, lane_count: usize) -> KeyFlag {
+        KeyFlag::Lane(lane, LaneColor::of(lane, lane_count))
+    }
+
+    // the lane of the key, the beat lines have none
+    pub fn lane_index(&self) -> Option<usize> {
+        match self {
+            KeyFlag::Lane(lane, _) => Some(*lane),
+            KeyFlag::Bpm => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct GameKey {
     pub game_object: GameObject,
     pub color: Color,
     pub speed: f32,
-    pub mili: i128,
+    pub mili: f64,
     pub hover: bool,
     pub holding: bool,
     pub flag: Option<KeyFlag>,
     pub connected: Option<u128>,
     pub muted: bool,
+    pub kind: NoteKind,
 }
 
 impl GameKey {
-    pub fn new(game_object: GameObject, color: Color, speed: f32, mili: i128, flag: Option<KeyFlag>, connected: Option<u128>, holding: bool) -> GameKey {
+    pub fn new(game_object: GameObject, color: Color, speed: f32, mili: f64, flag: Option<KeyFlag>, connected: Option<u128>, holding: bool) -> GameKey {
         GameKey {
             game_object,
             color: color,
@@ -42,6 +88,7 @@ impl GameKey {
             flag,
             connected,
             muted: false,
+            kind: NoteKind::Normal,
         }
     }
 
@@ -54,24 +101,41 @@ impl GameKey {
             match &self.flag {
                 Some(flag) => {
                     match flag {
-                        KeyFlag::Left => {
+                        KeyFlag::Lane(_, LaneColor::Red) => {
                             note_texture = &app.textures.red_note;
                             hold_texture = &app.textures.red_hold;
                         },
-                        KeyFlag::Up => {
+                        KeyFlag::Lane(_, LaneColor::Yellow) => {
                             note_texture = &app.textures.yellow_note;
                             hold_texture = &app.textures.yellow_hold;
                         },
-                        KeyFlag::Bottom => {
+                        KeyFlag::Lane(_, LaneColor::Blue) => {
                             note_texture = &app.textures.blue_note;
                             hold_texture = &app.textures.blue_hold;
                         },
-                        KeyFlag::Right => {
+                        KeyFlag::Lane(_, LaneColor::Purple) => {
                             note_texture = &app.textures.purple_note;
                             hold_texture = &app.textures.purple_hold;
                         },
                         KeyFlag::Bpm => { note_texture = &None },
                     }
+
+                    // the other kinds look the same on every lane, so they can't be mistaken for normal notes
+                    match self.kind {
+                        NoteKind::Normal => {},
+                        NoteKind::Mine => {
+                            note_texture = &app.textures.mine_note;
+                            hold_texture = &app.textures.mine_note;
+                        },
+                        NoteKind::Roll => {
+                            note_texture = &app.textures.roll_note;
+                            hold_texture = &app.textures.roll_hold;
+                        },
+                        NoteKind::Fake => {
+                            note_texture = &app.textures.fake_note;
+                            hold_texture = &app.textures.fake_hold;
+                        },
+                    }
                     
                     if self.muted {
                         note_texture = &app.textures.mute_note;
@@ -111,6 +175,12 @@ impl GameKey {
         self.game_object.y += key_speed * deltatime.as_secs_f32();
     }
 
+    // puts the key where it is when it is `distance` milliseconds (at normal speed) away from the hit line,
+    // the keys show up at -100 and take `base_time` milliseconds to get to the line
+    pub fn scroll_to(&mut self, distance: f64, base_time: f64, key_speed: f32) {
+        self.game_object.y = -100.0 + key_speed * ((base_time - distance) / 1000.0) as f32;
+    }
+
     pub fn is_hover(&mut self, event: &sdl2::event::Event) {
         if self.game_object.active {
             match event { 
@@ -1,105 +1,277 @@
-use std::time::Duration;
-
-use sdl2::{pixels::Color, rect::Rect};
+use sdl2::pixels::Color;
 use serde::{Deserialize, Serialize};
 use serde_json;
 
-use crate::{app::App, game_object::GameObject, input::keybutton::Note, key::{GameKey, KeyFlag}};
+use crate::{app::App, chart::{error::ChartError, migration, scroll::{self, ScrollVelocity}, sections::{self, Section}, storyboard::{self, ChartEvent}, chart_id, timing::TimingMap, validate::{self, Issue}}, game_object::GameObject, input::keybutton::{Note, NoteKind}, key::{lane_x, GameKey, KeyFlag, LANE_SPACING}};
+
+// the main chart of a song folder, the other difficulties go inside CHARTS_FOLDER
+pub const DEFAULT_CHART: &str = "data.json";
+pub const CHARTS_FOLDER: &str = "charts";
+// every time in the chart is in milliseconds since the level started, the song audio starts at LEAD_IN_MS
+pub const LEAD_IN_MS: f64 = 3000.0;
+// the editor shows the chart as a grid, each space of the grid is this amount of milliseconds
+pub const EDITOR_STEP_MS: f64 = 10.0;
+// holds are drawn as a trail of small keys, one every HOLD_STEP_MS
+pub const HOLD_STEP_MS: f64 = 10.0;
+// shorter holds are shown as single notes in the editor
+pub const MIN_EDITOR_HOLD_MS: f64 = 500.0;
+// the charts can have from 1 to this amount of lanes, the charts made before the lanes existed have 4
+pub const MAX_LANES: usize = 10;
+pub const DEFAULT_LANES: usize = 4;
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Bpm {
-    pub bpm: u128,
-    pub starting_at: u128
+    pub bpm: f64,
+    pub starting_at: f64
+}
+
+// the beats stop for `duration` milliseconds from `starting_at`, the notes keep their own times.
+// a delay is the same but a beat that is just at `starting_at` comes after the pause instead of before (like on stepmania)
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Stop {
+    pub starting_at: f64,
+    pub duration: f64,
+    #[serde(default)]
+    pub delay: bool,
+}
+
+// from `starting_at` each measure has `numerator` beats of 1/`denominator` notes, songs without any are 4/4
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TimeSignature {
+    pub starting_at: f64,
+    pub numerator: u32,
+    pub denominator: u32,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Song {
+    pub(crate) format_version: u64,
     pub(crate) name: String,
+    // an id the author can give to the chart, the one that always matches the notes is Song::chart_id
     pub(crate) id: Option<i128>,
-    pub(crate) left_keys:  Vec<Note>,
-    pub(crate) up_keys: Vec<Note>,
-    pub(crate) bottom_keys: Vec<Note>,
-    pub(crate) right_keys: Vec<Note>,
-    pub(crate) end: u128,
-    pub(crate) sync: Option<i128>,
+    // the notes of every lane from left to right, the amount of lanes is the key mode of the chart (4 is 4K)
+    pub(crate) lanes: Vec<Vec<Note>>,
+    pub(crate) end: f64,
+    pub(crate) sync: Option<f64>,
     pub(crate) bpm: Option<Vec<Bpm>>,
+    #[serde(default)]
+    pub(crate) stops: Vec<Stop>,
+    #[serde(default)]
+    pub(crate) time_signatures: Vec<TimeSignature>,
+    // changes of how fast the notes move, without them the notes always move at the speed of the settings
+    #[serde(default)]
+    pub(crate) scroll_velocity: Vec<ScrollVelocity>,
+    // the storyboard of the chart: backgrounds, flashes, dim, visualizers and lane fades at their times
+    #[serde(default)]
+    pub(crate) events: Vec<ChartEvent>,
+    // the named parts of the song (Intro, Verse, Chorus...), the player can jump between them
+    #[serde(default)]
+    pub(crate) sections: Vec<Section>,
+    // charts made before the metadata existed get it empty
+    #[serde(default)]
+    pub(crate) metadata: SongMetadata,
+}
+
+// the information about the song that is not needed to play it, shown on the song list
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+#[serde(default)]
+pub struct SongMetadata {
+    pub(crate) title: String,
+    // the title written with latin letters, for songs with titles in other alphabets
+    pub(crate) title_translit: String,
+    pub(crate) artist: String,
+    pub(crate) charter: String,
+    // the game, album or anime the song comes from
+    pub(crate) source: String,
+    pub(crate) genre: String,
+    pub(crate) tags: Vec<String>,
+    // milliseconds of the audio where the song list preview starts
+    pub(crate) preview_start: Option<f64>,
+    // files inside the song folder, when they are not set the game looks for audio.* (or any audio file) and cover.*
+    pub(crate) audio_file: Option<String>,
+    pub(crate) cover_file: Option<String>,
+    // a .lrc file of the folder, when it is not set the game looks for lyrics.lrc (or any .lrc file)
+    pub(crate) lyrics_file: Option<String>,
+    // the name shown on the difficulty picker, like "Hard", when is not set the chart file name is used
+    pub(crate) difficulty: Option<String>,
+}
+
+impl Default for Song {
+    fn default() -> Self {
+        Song { format_version: migration::CURRENT_FORMAT_VERSION, name: "".to_owned(), id: None, lanes: vec![vec![]; DEFAULT_LANES], end: 0.0, sync: Some(0.0), bpm: Some(vec![Bpm { bpm: 0.0, starting_at: 0.0 }]), stops: vec![], time_signatures: vec![], scroll_velocity: vec![], events: vec![], sections: vec![], metadata: SongMetadata::default() }
+    }
 }
 
 // this struct loads the data from a json so is runned from the play.rs file
 impl Song {
-    pub fn new(folder: &String) -> Result<Song, Box<dyn std::error::Error>> {
-        let mut song: Song = Song { name: "".to_owned(), id: Some(0), left_keys: vec![], up_keys: vec![], bottom_keys: vec![], right_keys: vec![], end: 0, sync: Some(0), bpm: Some(vec![Bpm { bpm: 0, starting_at: 0 }]) };
-        match std::fs::read_to_string("songs/".to_owned() + &folder + "/data.json") {
-            Ok(file_contents) => {
-                let mut new_song: Song = serde_json::from_str(&file_contents)?;
-                match new_song.id {
-                    Some(_) => {},
-                    None => new_song.id = Some(0),
-                }
-                song = Song { name: new_song.name, id: new_song.id, left_keys: new_song.left_keys, up_keys: new_song.up_keys, bottom_keys: new_song.bottom_keys, right_keys: new_song.right_keys, end: new_song.end, sync: new_song.sync, bpm: new_song.bpm};
-            },
-            Err(_) => {
-                eprintln!("The song json didn't loaded right");
-            },
-        }
+    // a folder can have its main chart in data.json and more difficulties in charts/<name>.json,
+    // `chart` is the path of the chart inside the folder, like "data.json" or "charts/hard.json"
+    pub fn new(folder: &str, chart: &str) -> Result<Song, ChartError> {
+        let path = Self::chart_path(folder, chart);
+        let file_contents = std::fs::read_to_string(&path).map_err(|err| ChartError::from_io(&path, err))?;
+        Self::from_json(&file_contents)
+    }
+
+    // the chart inside a json, with the same checks as opening it from a folder
+    pub fn from_json(contents: &str) -> Result<Song, ChartError> {
+        // old charts are upgraded in memory, the file itself is only rewritten by upgrade_file
+        let (song, _) = migration::migrate(contents)?;
+        song.check_values()?;
         Ok(song)
     }
 
-    pub fn get_keys(self, app: &mut App, edit: bool) -> Vec<Vec<GameKey>> {
-        let mut left_keys: Vec<GameKey> = Vec::new();
-        let mut up_keys: Vec<GameKey> = Vec::new();
-        let mut bottom_keys: Vec<GameKey> = Vec::new();
-        let mut right_keys: Vec<GameKey> = Vec::new();
+    // rewrites a chart of a folder in the current format, only when its format_version is an older one (opening a
+    // current chart never touches the file), returns false if it was already up to date
+    pub fn upgrade_file(folder: &str, chart: &str) -> Result<bool, ChartError> {
+        let path = Self::chart_path(folder, chart);
+        let file_contents = std::fs::read_to_string(&path).map_err(|err| ChartError::from_io(&path, err))?;
+
+        let (song, migrated) = migration::migrate(&file_contents)?;
+        if migrated {
+            std::fs::write(&path, serde_json::to_string(&song)?).map_err(|err| ChartError::from_io(&path, err))?;
+        }
+        Ok(migrated)
+    }
+
+    pub fn chart_path(folder: &str, chart: &str) -> String {
+        "songs/".to_owned() + folder + "/" + chart
+    }
+
+    // every chart of a folder, data.json first and then the files inside charts sorted by name
+    pub fn charts(folder: &str) -> Vec<String> {
+        let mut charts = vec![];
+        if std::path::Path::new(&Self::chart_path(folder, DEFAULT_CHART)).exists() {
+            charts.push(DEFAULT_CHART.to_owned());
+        }
+
+        if let Ok(entries) = std::fs::read_dir("songs/".to_owned() + folder + "/" + CHARTS_FOLDER) {
+            let mut extra: Vec<String> = entries.flatten()
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| name.to_lowercase().ends_with(".json"))
+                .map(|name| CHARTS_FOLDER.to_owned() + "/" + &name)
+                .collect();
+            extra.sort();
+            charts.extend(extra);
+        }
+        charts
+    }
+
+    // the title shown on the song list, charts without metadata are shown with the name of their folder
+    // (the name can't be used, most charts were copied from the example and all of them are called "Test")
+    pub fn display_title(&self, folder: &str) -> String {
+        let title = if self.metadata.title.is_empty() { folder } else { &self.metadata.title };
+        let title = if self.metadata.title_translit.is_empty() || self.metadata.title_translit == *title { title.to_owned() } else { format!("{} ({})", self.metadata.title_translit, title) };
+
+        if self.metadata.artist.is_empty() { title } else { format!("{} - {}", self.metadata.artist, title) }
+    }
+
+    // the name shown on the difficulty picker, charts/hard.json is shown as "hard"
+    pub fn chart_label(chart: &str) -> String {
+        if chart == DEFAULT_CHART {
+            return "default".to_owned();
+        }
+        let file = chart.rsplit('/').next().unwrap_or(chart);
+        file.strip_suffix(".json").unwrap_or(file).to_owned()
+    }
+
+    // the same notes and timing always give the same id, so scores can be saved with it
+    pub fn chart_id(&self) -> String {
+        chart_id::chart_id(self)
+    }
 
+    // the problems that don't stop the chart from loading but make it play wrong, for the editor and the song list
+    pub fn validate(&self) -> Vec<Issue> {
+        validate::validate(self)
+    }
+
+    // the key mode of the chart
+    pub fn lane_count(&self) -> usize {
+        self.lanes.len()
+    }
+
+    // values that the json accepts but that would crash the game when building the keys
+    fn check_values(&self) -> Result<(), ChartError> {
+        if self.lanes.is_empty() || self.lanes.len() > MAX_LANES {
+            return Err(ChartError::invalid("lanes", &format!("a chart needs from 1 to {} lanes", MAX_LANES)));
+        }
+        for (i, stop) in self.stops.iter().enumerate() {
+            if stop.duration.is_nan() || stop.duration < 0.0 || !stop.starting_at.is_finite() {
+                return Err(ChartError::invalid(&format!("stops[{}].duration", i), "the stop can't be negative"));
+            }
+        }
+        for (i, signature) in self.time_signatures.iter().enumerate() {
+            if signature.numerator == 0 || signature.denominator == 0 {
+                return Err(ChartError::invalid(&format!("time_signatures[{}]", i), "a measure needs at least one beat"));
+            }
+        }
+        scroll::check(&self.scroll_velocity)?;
+        storyboard::check(&self.events)?;
+        sections::check(&self.sections)?;
+
+        Ok(())
+    }
+
+    // the keys of every lane and after them the beat lines, the lanes are placed from the middle of the screen with lane_x
+    pub fn get_keys(self, app: &mut App, edit: bool) -> Vec<Vec<GameKey>> {
+        let lane_count = self.lane_count();
+        let mut lanes: Vec<Vec<GameKey>> = vec![Vec::new(); lane_count];
         let mut bpm_bars: Vec<GameKey> = vec![];
 
-        
         let width = app.width;
         let key_speed = app.coordination_data.key_speed;
+        let lines = TimingMap::new(&self).map_or(vec![], |timing| timing.lines(self.end));
         
         if edit == true {
-            for spaces in 0..self.end {
-                left_keys.push(GameKey::new(GameObject {active: true, x: ((width/2) - 160) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, spaces as i128, None, None, false));
-                up_keys.push(GameKey::new(GameObject {active: true, x: ((width/2) - 60) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, spaces as i128, None, None, false));
-                bottom_keys.push(GameKey::new(GameObject {active: true, x: ((width/2) + 40) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, spaces as i128, None, None, false));
-                right_keys.push(GameKey::new(GameObject {active: true, x: ((width/2) + 140) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, spaces as i128, None, None, false));
-                bpm_bars.push(GameKey::new(GameObject { active: true, x: 0.0, y: 0.0, width: 0.0, height: 0.0 }, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, spaces as i128, None, None, false));
+            for spaces in 0..Self::editor_spaces(self.end) {
+                let mili = spaces as f64 * EDITOR_STEP_MS;
+                for (lane, keys) in lanes.iter_mut().enumerate() {
+                    keys.push(GameKey::new(GameObject {active: true, x: (lane_x(width, lane, lane_count) - 10) as f32, y: 0.0, width: 20.0, height: 6.0}, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, mili, None, None, false));
+                }
+                bpm_bars.push(GameKey::new(GameObject { active: true, x: 0.0, y: 0.0, width: 0.0, height: 0.0 }, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, mili, None, None, false));
             }
 
-            Self::edit_list(self.left_keys.clone(),(width/2) - 175, (width/2) - 160, &mut left_keys, key_speed, KeyFlag::Left);
-            Self::edit_list(self.up_keys.clone(),(width/2) - 75,(width/2) - 60,  &mut up_keys, key_speed, KeyFlag::Up);
-            Self::edit_list(self.bottom_keys.clone(),(width/2) + 25,(width/2) + 40,  &mut bottom_keys, key_speed, KeyFlag::Bottom);
-            Self::edit_list(self.right_keys.clone(),(width/2) + 125,(width/2) + 140,  &mut right_keys, key_speed, KeyFlag::Right);
-            Self::bpm_list(self.end, self.right_keys.clone(),(width / 2 - (((width/2) - 200) / 2)) as u32,app.width,  &mut bpm_bars, key_speed, KeyFlag::Bpm, app, edit, self.bpm);
+            for (lane, keys) in lanes.iter_mut().enumerate() {
+                let center = lane_x(width, lane, lane_count);
+                Self::edit_list(self.lanes[lane].clone(), (center - 25) as u32, (center - 10) as u32, keys, key_speed, KeyFlag::lane(lane, lane_count));
+            }
         } else {
-            Self::play_list(self.left_keys.clone(),(width/2) - 175, (width/2) - 160, &mut left_keys, key_speed, KeyFlag::Left, app);
-            Self::play_list(self.up_keys.clone(),(width/2) - 75,(width/2) - 60,  &mut up_keys, key_speed, KeyFlag::Up, app);
-            Self::play_list(self.bottom_keys.clone(),(width/2) + 25,(width/2) + 40,  &mut bottom_keys, key_speed, KeyFlag::Bottom, app);
-            Self::play_list(self.right_keys.clone(),(width/2) + 125,(width/2) + 140,  &mut right_keys, key_speed, KeyFlag::Right, app);
-            Self::bpm_list(self.end, self.right_keys.clone(),(width / 2 - (((width/2) - 200) / 2)) as u32,app.width,  &mut bpm_bars, key_speed, KeyFlag::Bpm, app, edit, self.bpm);
+            for (lane, keys) in lanes.iter_mut().enumerate() {
+                let center = lane_x(width, lane, lane_count);
+                Self::play_list(self.lanes[lane].clone(), (center - 25) as u32, (center - 10) as u32, keys, key_speed, KeyFlag::lane(lane, lane_count), app);
+            }
         }
+        Self::bpm_list(lines, lane_count, &mut bpm_bars, KeyFlag::Bpm, app, edit);
+
+        lanes.push(bpm_bars);
+        lanes
+    }
 
-        return vec![left_keys, up_keys, bottom_keys, right_keys, bpm_bars]
+    // the amount of spaces the editor grid needs to show a song that ends at `end` milliseconds
+    pub fn editor_spaces(end: f64) -> usize {
+        (end / EDITOR_STEP_MS).ceil().max(0.0) as usize
     }
 
     pub fn play_list(self_list:Vec<Note>, x: u32, x2: u32, keys_list: &mut Vec<GameKey>, key_speed: f32, flag: KeyFlag, app: &mut App) {
         let color = Color::RGB(0, 0, 0);
 
         for values in self_list.clone().iter_mut() {
-            if u128::from(values.time) > app.coordination_data.base_time.try_into().unwrap() {
-                if values.holding > 0 {
-                    for between_index in 0..values.holding {
+            if values.time > app.coordination_data.base_time {
+                // mines don't have a length, a mine with `holding` is still a single note
+                if values.holding > 0.0 && values.kind != NoteKind::Mine {
+                    let mut between = 0.0;
+                    while between < values.holding {
                         let new_arrow: GameKey;
-                        if between_index == 0 {
-                            new_arrow = GameKey::new(GameObject {active: true, x: x as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), key_speed, ((values.time as i128) + between_index as i128).try_into().unwrap(), Some(flag.clone()),None, false);
+                        if between == 0.0 {
+                            new_arrow = GameKey::new(GameObject {active: true, x: x as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), key_speed, values.time + between, Some(flag),None, false);
                         } else {
-                            new_arrow = GameKey::new(GameObject {active: true, x: x2 as f32, y: -100.0, width: 20.0, height: 50.0}, color, key_speed, ((values.time as i128) + between_index as i128).try_into().unwrap(), Some(flag.clone()), Some((values.time as i128).try_into().unwrap()), true, );
+                            new_arrow = GameKey::new(GameObject {active: true, x: x2 as f32, y: -100.0, width: 20.0, height: 50.0}, color, key_speed, values.time + between, Some(flag), Some(Self::editor_space(values.time) as u128), true, );
                         }
-                        keys_list.push(new_arrow);
+                        keys_list.push(GameKey { kind: values.kind, ..new_arrow });
+                        between += HOLD_STEP_MS;
                     }
                 } else {
-                    let new_arrow = GameKey::new(GameObject {active: true, x: x as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), key_speed, values.time as i128, Some(flag.clone()), None, false);
-                    keys_list.push(new_arrow);
+                    let new_arrow = GameKey::new(GameObject {active: true, x: x as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), key_speed, values.time, Some(flag), None, false);
+                    keys_list.push(GameKey { kind: values.kind, ..new_arrow });
                 }
             }
         }
@@ -107,96 +279,54 @@ impl Song {
 
     pub fn edit_list(self_list:Vec<Note>, x: u32, x2: u32, keys_list: &mut Vec<GameKey>, key_speed: f32, flag: KeyFlag) {
         for (i, _values) in keys_list.clone().iter().enumerate() {
-            match Self::contains_note(i as u128, self_list.clone()) {
-                Some(note) => {
-                    if note.holding >= 50 {
-                        keys_list[i] = GameKey::new(GameObject {active: true, x: x as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(200, 200, 0), key_speed, (note.time as i128).try_into().unwrap(), Some(flag.clone()),Some(i as u128 + note.holding), false);
-                        keys_list[i + note.holding as usize] = GameKey::new(GameObject {active: true, x: x2 as f32, y: -100.0, width: 20.0, height: 6.0}, Color::RGB(0, 0, 200), key_speed, (note.time as i128) + note.holding as i128, Some(flag.clone()), Some(i as u128), false)
-                    } else {
-                        keys_list[i] = GameKey::new(GameObject {active: true, x: x as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), key_speed, note.time as i128, Some(flag.clone()), None, false);
-                    }
-                },
-                None => {},
+            if let Some(note) = Self::contains_note(i, self_list.clone()) {
+                let end_space = i + Self::editor_space(note.holding);
+                if note.holding >= MIN_EDITOR_HOLD_MS && end_space < keys_list.len() {
+                    keys_list[i] = GameKey::new(GameObject {active: true, x: x as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(200, 200, 0), key_speed, note.time, Some(flag),Some(end_space as u128), false);
+                    keys_list[end_space] = GameKey::new(GameObject {active: true, x: x2 as f32, y: -100.0, width: 20.0, height: 6.0}, Color::RGB(0, 0, 200), key_speed, note.time + note.holding, Some(flag), Some(i as u128), false)
+                } else {
+                    keys_list[i] = GameKey::new(GameObject {active: true, x: x as f32, y: -100.0, width: 50.0, height: 50.0}, Color::RGB(0, 200, 0), key_speed, note.time, Some(flag), None, false);
+                }
+                // both ends of a hold get the kind, the editor saves it from the first one
+                keys_list[i].kind = note.kind;
+                if let Some(end) = keys_list[i].connected {
+                    keys_list[end as usize].kind = note.kind;
+                }
             }
         }
     }
 
     
-    pub fn bpm_list(end: u128, self_list:Vec<Note>, x: u32, x2: u32, keys_list: &mut Vec<GameKey>, key_speed: f32, flag: KeyFlag, app: &mut App, edit: bool, bpm: Option<Vec<Bpm>>) {
-        let mut actual_bpm = 0;
-        match bpm {
-            Some(bpm) => {
-                if bpm.len() > 0 {
-                    
-                    let mut value = 300;
-
-                    if edit {
-                        for (i, _values) in keys_list.clone().iter().enumerate() {
-                            if value == i.try_into().unwrap() {
-                                if bpm[actual_bpm].starting_at < i.try_into().unwrap() {
-                                    keys_list[i] = GameKey::new(GameObject { active: true, x: (app.width / 2 - (((app.width/2) - 200) / 2)) as f32, y: -125.0, width: (app.width/2) as f32  - 200.0, height: 6 as f32 }, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, i as i128, Some(flag.clone()), None, false);
-                                    value += 6000 / bpm[actual_bpm].bpm as u128;
-                                
-                                    if actual_bpm < bpm.len() - 1 {
-                                        if (i > bpm[actual_bpm + 1].starting_at.try_into().unwrap()) {
-                                            println!("yes ser");
-                                            actual_bpm += 1;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        for moment in 0..end {
-                            if moment == value {
-                                if bpm[actual_bpm].starting_at < moment.try_into().unwrap() {
-                                    keys_list.push(GameKey::new(GameObject { active: true, x: (app.width / 2 - (((app.width/2) - 500) / 2)) as f32, y: -100.0, width: (app.width/2) as f32  - 500.0, height: 6 as f32 }, Color::RGB(60, 56, 54), app.coordination_data.key_speed, moment as i128, Some(flag.clone()), None, false));
-                                    value += 6000 / bpm[actual_bpm].bpm as u128;
-                                    if actual_bpm < bpm.len() - 1 {
-                                        if (moment > bpm[actual_bpm + 1].starting_at.try_into().unwrap()) {
-                                            println!("yes ser");
-                                            actual_bpm += 1;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+    // the lines of the beats, the first beat of every measure is drawn bigger. they are a bit wider than the lanes
+    pub fn bpm_list(lines: Vec<(f64, bool)>, lane_count: usize, keys_list: &mut Vec<GameKey>, flag: KeyFlag, app: &mut App, edit: bool) {
+        let lanes_width = (lane_count * LANE_SPACING as usize) as f32;
+        for (time, measure) in lines {
+            let height = if measure { 10.0 } else { 6.0 };
+            if edit {
+                let space = Self::editor_space(time);
+                if space < keys_list.len() {
+                    let line_width = lanes_width + 360.0;
+                    keys_list[space] = GameKey::new(GameObject { active: true, x: (app.width / 2) as f32 - line_width / 2.0, y: -125.0, width: line_width, height }, Color::RGBA(0, 0, 0,0), app.coordination_data.key_speed, time, Some(flag), None, false);
                 }
+            } else {
+                let color = if measure { Color::RGB(146, 131, 116) } else { Color::RGB(60, 56, 54) };
+                let line_width = lanes_width + 60.0;
+                keys_list.push(GameKey::new(GameObject { active: true, x: (app.width / 2) as f32 - line_width / 2.0, y: -100.0, width: line_width, height }, color, app.coordination_data.key_speed, time, Some(flag), None, false));
             }
-            None => {},
         }
     }
 
-    pub fn contains_note (space: u128, vector:Vec<Note>) -> Option<Note> {
+    // the space of the editor grid that contains the given time
+    pub fn editor_space(time: f64) -> usize {
+        (time / EDITOR_STEP_MS).round().max(0.0) as usize
+    }
+
+    pub fn contains_note (space: usize, vector:Vec<Note>) -> Option<Note> {
         for note in vector {
-            if note.time == space {
+            if Self::editor_space(note.time) == space {
                 return Some(note);
             }
         }
         return None;
     }
 }
-
-#[derive(Clone, Copy)]
-struct BeatLine {
-    game_object: GameObject,
-    rect: Rect
-}
-
-impl BeatLine {
-    pub fn new(width: u32, _height: u32) -> Self {
-        let game_object = GameObject { active: true, x: (width / 2 - (((width/2) - 200) / 2)) as f32, y: -125.0, width: (width/2) as f32  - 200.0, height: _height as f32 };
-        let bar_rect = Rect::new(game_object.x as i32, game_object.y as i32, game_object.width as u32, game_object.height as u32);
-
-        BeatLine {
-            rect: bar_rect,
-            game_object
-        }
-    }
-
-    pub fn render(mut self, app: &mut App, deltatime: Duration) {
-        app.canvas.set_draw_color(Color::RGB(60, 56, 54));
-        app.canvas.fill_rect(self.rect).unwrap();
-    }
-}
\ No newline at end of file
@@ -1,10 +1,35 @@
 use app::App;
 
 mod app;
+mod cli;
 mod key;
 mod game_object;
 mod load_song;
 
+mod chart {
+    pub mod analysis;
+    pub mod audio;
+    pub mod autochart;
+    pub mod chart_id;
+    pub mod error;
+    pub mod library;
+    pub mod lyrics;
+    pub mod midi;
+    pub mod migration;
+    pub mod modifiers;
+    pub mod osu;
+    pub mod package;
+    pub mod scores;
+    pub mod scroll;
+    pub mod sections;
+    pub mod song_folder;
+    pub mod stepmania;
+    pub mod storyboard;
+    pub mod tempo;
+    pub mod timing;
+    pub mod validate;
+}
+
 mod ui {
     pub mod text;
 }
@@ -28,6 +53,11 @@ mod gameplay {
 }
 
 fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args);
+    }
+
     let app = App::new("Arrowner");
     app.render();
     Ok(())